@@ -7,7 +7,11 @@ pub mod types;
 
 use crate::aws::cloudformation::{AWSConfigState, CloudFormationParams};
 use aws_sdk_cloudformation::config::Region;
-use controller::apis::coredb_types::{CoreDB, CoreDBSpec};
+use controller::{
+    apis::coredb_types::{CoreDB, CoreDBSpec},
+    cloudnativepg::clusters::{Cluster, ClusterStatusConditionsStatus},
+    BACKUP_REQUESTED_AT, HIBERNATION,
+};
 use errors::ConductorError;
 
 use k8s_openapi::api::core::v1::{Namespace, Secret};
@@ -372,6 +376,94 @@ pub async fn restart_coredb(
     Ok(true)
 }
 
+// Request an on-demand backup by annotating the CoreDB resource with the time of the request.
+// The operator creates a CNPG Backup for every new value, and reports it in `status.backups`.
+// Returns the value of the annotation, to find the backup in the status.
+pub async fn request_backup(
+    client: Client,
+    namespace: &str,
+    cluster_name: &str,
+    msg_enqueued_at: DateTime<Utc>,
+) -> Result<String, ConductorError> {
+    let coredb_api: Api<CoreDB> = Api::namespaced(client, namespace);
+    let requested_at = msg_enqueued_at.to_rfc3339_opts(SecondsFormat::Secs, true);
+
+    let current_coredb = coredb_api
+        .get(cluster_name)
+        .await
+        .map_err(ConductorError::KubeError)?;
+    if current_coredb.annotations().get(BACKUP_REQUESTED_AT) == Some(&requested_at) {
+        debug!("CoreDB resource already has the backup annotation: {namespace}");
+        return Ok(requested_at);
+    }
+
+    let patch_json = serde_json::json!({
+        "metadata": {
+            "annotations": {
+                BACKUP_REQUESTED_AT: requested_at
+            }
+        }
+    });
+
+    info!("Applying `{BACKUP_REQUESTED_AT} == {requested_at}` to the CoreDB resource: {namespace}");
+
+    let params = PatchParams::default();
+    let _patch = coredb_api
+        .patch(cluster_name, &params, &Patch::Merge(patch_json))
+        .await
+        .map_err(ConductorError::KubeError)?;
+    Ok(requested_at)
+}
+
+// Hibernate or start the instance by setting `spec.stop` on the CoreDB resource.
+// The operator scales the instance down to zero, keeping its storage, while `stop` is true.
+pub async fn stop_coredb(
+    client: Client,
+    namespace: &str,
+    cluster_name: &str,
+    stop: bool,
+) -> Result<(), ConductorError> {
+    let coredb_api: Api<CoreDB> = Api::namespaced(client, namespace);
+    let patch_json = serde_json::json!({
+        "spec": {
+            "stop": stop
+        }
+    });
+
+    info!("Applying `stop == {stop}` to the CoreDB resource: {namespace}");
+
+    let params = PatchParams::default();
+    let _patch = coredb_api
+        .patch(cluster_name, &params, &Patch::Merge(patch_json))
+        .await
+        .map_err(ConductorError::KubeError)?;
+    Ok(())
+}
+
+// Returns true once the CNPG Cluster of the instance has been hibernated, that is when the
+// hibernation condition is True or there are no ready instances left
+pub async fn instance_stopped(
+    client: Client,
+    namespace: &str,
+    cluster_name: &str,
+) -> Result<bool, ConductorError> {
+    let cluster_api: Api<Cluster> = Api::namespaced(client, namespace);
+    let cluster = cluster_api.get(cluster_name).await?;
+    let status = match cluster.status {
+        Some(status) => status,
+        None => return Ok(false),
+    };
+    let hibernated = status
+        .conditions
+        .unwrap_or_default()
+        .iter()
+        .any(|condition| {
+            condition.r#type == HIBERNATION
+                && condition.status == ClusterStatusConditionsStatus::True
+        });
+    Ok(hibernated || status.ready_instances.unwrap_or(0) == 0)
+}
+
 // Create a cloudformation stack for the database.
 // This will create an IAM role for the database to use to access the backup archive bucket
 pub async fn create_cloudformation(
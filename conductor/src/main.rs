@@ -6,7 +6,8 @@ use conductor::monitoring::CustomMetrics;
 use conductor::{
     create_cloudformation, create_namespace, create_or_update, delete, delete_cloudformation,
     delete_namespace, generate_rand_schedule, generate_spec, get_coredb_error_without_status,
-    get_one, get_pg_conn, lookup_role_arn, restart_coredb, types,
+    get_one, get_pg_conn, instance_stopped, lookup_role_arn, request_backup, restart_coredb,
+    stop_coredb, types,
 };
 use controller::apis::coredb_types::{Backup, CoreDBSpec, S3Credentials, ServiceAccountTemplate};
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
@@ -35,6 +36,10 @@ const REQUEUE_VT_SEC_SHORT: i32 = 5;
 // that we would want to try again after awhile.
 const REQUEUE_VT_SEC_LONG: i32 = 300;
 
+// Number of reads of a backup request after which the operator should have started the backup,
+// otherwise backups are not enabled on the data plane.
+const BACKUP_STARTED_MAX_READ_CT: i32 = 12;
+
 async fn run(metrics: CustomMetrics) -> Result<(), ConductorError> {
     // Read connection info from environment variable
     let pg_conn_url =
@@ -434,6 +439,186 @@ async fn run(metrics: CustomMetrics) -> Result<(), ConductorError> {
                     connection: conn_info.ok(),
                 }
             }
+            Event::Stop | Event::Start => {
+                let stop = read_msg.message.event_type == Event::Stop;
+                info!("{}: handling instance stop={}", read_msg.msg_id, stop);
+                if let Err(err) = stop_coredb(client.clone(), &namespace, &namespace, stop).await {
+                    error!(
+                        "{}: Error setting stop={} on instance: {}",
+                        read_msg.msg_id, stop, err
+                    );
+                    requeue_short(&metrics, &control_plane_events_queue, &queue, &read_msg).await?;
+                    continue;
+                }
+
+                let current_resource =
+                    match get_coredb_error_without_status(client.clone(), &namespace).await {
+                        Ok(coredb) => coredb,
+                        Err(_) => {
+                            requeue_short(&metrics, &control_plane_events_queue, &queue, &read_msg)
+                                .await?;
+                            continue;
+                        }
+                    };
+
+                // Only report back once the operator has stopped or started the instance. A stop
+                // is complete when CNPG has hibernated the Cluster, status.running only reflects
+                // that Postgres is not accepting connections.
+                let done = if stop {
+                    match instance_stopped(client.clone(), &namespace, &namespace).await {
+                        Ok(stopped) => stopped,
+                        Err(err) => {
+                            error!(
+                                "{}: Error checking the hibernation of the instance: {}",
+                                read_msg.msg_id, err
+                            );
+                            false
+                        }
+                    }
+                } else {
+                    current_resource
+                        .status
+                        .as_ref()
+                        .map(|status| status.running)
+                        .unwrap_or(false)
+                };
+                if !done {
+                    info!(
+                        "{}: Instance has not reached stop={} yet, requeuing",
+                        read_msg.msg_id, stop
+                    );
+                    requeue_short(&metrics, &control_plane_events_queue, &queue, &read_msg).await?;
+                    continue;
+                }
+
+                let (report_event, conn_info) = if stop {
+                    (Event::StopComplete, None)
+                } else {
+                    let conn_info = get_pg_conn(
+                        client.clone(),
+                        &namespace,
+                        &data_plane_basedomain,
+                        &current_resource.spec,
+                    )
+                    .await;
+                    (Event::Started, conn_info.ok())
+                };
+
+                types::StateToControlPlane {
+                    data_plane_id: read_msg.message.data_plane_id,
+                    org_id: read_msg.message.org_id,
+                    inst_id: read_msg.message.inst_id,
+                    event_type: report_event,
+                    spec: Some(current_resource.spec),
+                    status: current_resource.status,
+                    connection: conn_info,
+                }
+            }
+            Event::Backup => {
+                info!("{}: handling instance backup", read_msg.msg_id);
+                let mut current_resource =
+                    match get_coredb_error_without_status(client.clone(), &namespace).await {
+                        Ok(coredb) => coredb,
+                        Err(_) => {
+                            requeue_short(&metrics, &control_plane_events_queue, &queue, &read_msg)
+                                .await?;
+                            continue;
+                        }
+                    };
+
+                // An instance without a backup destination can never be backed up
+                let report_event = if current_resource.spec.backup.destinationPath.is_none() {
+                    error!(
+                        "{}: Instance {} has no backup configured",
+                        read_msg.msg_id, namespace
+                    );
+                    metrics
+                        .conductor_errors
+                        .add(&opentelemetry::Context::current(), 1, &[]);
+                    Event::Error
+                } else {
+                    let requested_at = match request_backup(
+                        client.clone(),
+                        &namespace,
+                        &namespace,
+                        read_msg.enqueued_at,
+                    )
+                    .await
+                    {
+                        Ok(requested_at) => requested_at,
+                        Err(err) => {
+                            error!("{}: Error requesting backup: {}", read_msg.msg_id, err);
+                            requeue_short(&metrics, &control_plane_events_queue, &queue, &read_msg)
+                                .await?;
+                            continue;
+                        }
+                    };
+
+                    current_resource =
+                        match get_coredb_error_without_status(client.clone(), &namespace).await {
+                            Ok(coredb) => coredb,
+                            Err(_) => {
+                                requeue_short(
+                                    &metrics,
+                                    &control_plane_events_queue,
+                                    &queue,
+                                    &read_msg,
+                                )
+                                .await?;
+                                continue;
+                            }
+                        };
+
+                    // Only report back once the operator reports the backup as completed or failed
+                    let backup = current_resource
+                        .status
+                        .as_ref()
+                        .and_then(|status| status.backups.as_ref())
+                        .and_then(|backups| {
+                            backups
+                                .iter()
+                                .find(|backup| backup.requested_at == requested_at)
+                        });
+                    match backup.and_then(|backup| backup.phase.as_deref()) {
+                        Some("completed") => Event::BackupComplete,
+                        Some("failed") => Event::Error,
+                        // The operator never started the backup, backups are disabled on the data plane
+                        None if backup.is_none()
+                            && read_msg.read_ct >= BACKUP_STARTED_MAX_READ_CT =>
+                        {
+                            error!(
+                                "{}: Backup requested at {} was not started after {} reads",
+                                read_msg.msg_id, requested_at, read_msg.read_ct
+                            );
+                            metrics.conductor_errors.add(
+                                &opentelemetry::Context::current(),
+                                1,
+                                &[],
+                            );
+                            Event::Error
+                        }
+                        _ => {
+                            info!(
+                                "{}: Backup requested at {} has not finished yet, requeuing",
+                                read_msg.msg_id, requested_at
+                            );
+                            requeue_short(&metrics, &control_plane_events_queue, &queue, &read_msg)
+                                .await?;
+                            continue;
+                        }
+                    }
+                };
+
+                types::StateToControlPlane {
+                    data_plane_id: read_msg.message.data_plane_id,
+                    org_id: read_msg.message.org_id,
+                    inst_id: read_msg.message.inst_id,
+                    event_type: report_event,
+                    spec: Some(current_resource.spec),
+                    status: current_resource.status,
+                    connection: None,
+                }
+            }
             _ => {
                 warn!("Unhandled event_type: {:?}", read_msg.message.event_type);
                 metrics
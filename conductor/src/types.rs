@@ -32,6 +32,8 @@ pub enum Event {
     Started,
     Restore,
     Restored,
+    Backup,
+    BackupComplete,
 }
 
 /// message returned to control plane
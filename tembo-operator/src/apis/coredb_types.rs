@@ -10,10 +10,19 @@ use crate::{
 
 use k8s_openapi::{
     api::core::v1::ResourceRequirements,
-    apimachinery::pkg::{api::resource::Quantity, apis::meta::v1::ObjectMeta},
+    apimachinery::pkg::{
+        api::resource::Quantity,
+        apis::meta::v1::{Condition, ObjectMeta},
+    },
 };
 
-use crate::cloudnativepg::poolers::{PoolerPgbouncerPoolMode, PoolerTemplateSpecContainersResources};
+use crate::cloudnativepg::{
+    clusters::{
+        ClusterBackupBarmanObjectStoreDataCompression, ClusterBackupBarmanObjectStoreWalCompression,
+        ClusterExternalClustersBarmanObjectStoreWalCompression,
+    },
+    poolers::{PoolerPgbouncerPoolMode, PoolerTemplateSpecContainersResources},
+};
 use chrono::{DateTime, Utc};
 use kube::CustomResource;
 use schemars::JsonSchema;
@@ -75,6 +84,112 @@ pub struct S3CredentialsSessionToken {
     pub name: String,
 }
 
+/// Credentials to back up to Google Cloud Storage, with a `gs://` destination path
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
+pub struct GoogleCredentials {
+    /// Secret key with the JSON key of a Google service account
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        rename = "applicationCredentials"
+    )]
+    pub application_credentials: Option<GoogleCredentialsApplicationCredentials>,
+    /// Use the Google service account bound to the Kubernetes service account of the instance with
+    /// workload identity, instead of a JSON key
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "gkeEnvironment")]
+    pub gke_environment: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
+pub struct GoogleCredentialsApplicationCredentials {
+    pub key: String,
+    pub name: String,
+}
+
+/// Credentials to back up to Azure Blob Storage, with an `https://<account>.blob.core.windows.net`
+/// destination path. Use a connection string, a storage account with its key or a SAS token, or
+/// the Azure AD identity of the instance.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
+pub struct AzureCredentials {
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "connectionString")]
+    pub connection_string: Option<AzureCredentialsConnectionString>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        rename = "inheritFromAzureAD"
+    )]
+    pub inherit_from_azure_ad: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "storageAccount")]
+    pub storage_account: Option<AzureCredentialsStorageAccount>,
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "storageKey")]
+    pub storage_key: Option<AzureCredentialsStorageKey>,
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "storageSasToken")]
+    pub storage_sas_token: Option<AzureCredentialsStorageSasToken>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
+pub struct AzureCredentialsConnectionString {
+    pub key: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
+pub struct AzureCredentialsStorageAccount {
+    pub key: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
+pub struct AzureCredentialsStorageKey {
+    pub key: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
+pub struct AzureCredentialsStorageSasToken {
+    pub key: String,
+    pub name: String,
+}
+
+/// Compression of the base backups and WAL files in the object store
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, JsonSchema, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupCompression {
+    Gzip,
+    Bzip2,
+    Snappy,
+}
+
+impl From<BackupCompression> for ClusterBackupBarmanObjectStoreDataCompression {
+    fn from(compression: BackupCompression) -> Self {
+        match compression {
+            BackupCompression::Gzip => ClusterBackupBarmanObjectStoreDataCompression::Gzip,
+            BackupCompression::Bzip2 => ClusterBackupBarmanObjectStoreDataCompression::Bzip2,
+            BackupCompression::Snappy => ClusterBackupBarmanObjectStoreDataCompression::Snappy,
+        }
+    }
+}
+
+impl From<BackupCompression> for ClusterBackupBarmanObjectStoreWalCompression {
+    fn from(compression: BackupCompression) -> Self {
+        match compression {
+            BackupCompression::Gzip => ClusterBackupBarmanObjectStoreWalCompression::Gzip,
+            BackupCompression::Bzip2 => ClusterBackupBarmanObjectStoreWalCompression::Bzip2,
+            BackupCompression::Snappy => ClusterBackupBarmanObjectStoreWalCompression::Snappy,
+        }
+    }
+}
+
+impl From<BackupCompression> for ClusterExternalClustersBarmanObjectStoreWalCompression {
+    fn from(compression: BackupCompression) -> Self {
+        match compression {
+            BackupCompression::Gzip => ClusterExternalClustersBarmanObjectStoreWalCompression::Gzip,
+            BackupCompression::Bzip2 => ClusterExternalClustersBarmanObjectStoreWalCompression::Bzip2,
+            BackupCompression::Snappy => ClusterExternalClustersBarmanObjectStoreWalCompression::Snappy,
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
 #[allow(non_snake_case)]
 pub struct Backup {
@@ -90,6 +205,33 @@ pub struct Backup {
     pub endpoint_url: Option<String>,
     #[serde(default = "defaults::default_s3_credentials", rename = "s3Credentials")]
     pub s3_credentials: Option<S3Credentials>,
+    /// Back up to Google Cloud Storage instead of S3
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        rename = "googleCredentials"
+    )]
+    pub google_credentials: Option<GoogleCredentials>,
+    /// Back up to Azure Blob Storage instead of S3
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "azureCredentials")]
+    pub azure_credentials: Option<AzureCredentials>,
+    /// Compression of the base backups. Defaults to the setting of the stack.
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "dataCompression")]
+    pub data_compression: Option<BackupCompression>,
+    /// Compression of the archived WAL files. Defaults to the setting of the stack.
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "walCompression")]
+    pub wal_compression: Option<BackupCompression>,
+    /// Number of parallel jobs uploading a base backup. Defaults to the setting of the stack.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jobs: Option<i32>,
+    /// Maximum number of WAL files archived in one call of the archive command, and restored in
+    /// parallel during a recovery. Defaults to the setting of the stack.
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "walMaxParallel")]
+    pub wal_max_parallel: Option<i64>,
+    /// Take a base backup as soon as the instance is bootstrapped, instead of waiting for the
+    /// schedule. Defaults to true.
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "immediateBackup")]
+    pub immediate_backup: Option<bool>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
@@ -102,15 +244,131 @@ pub struct Restore {
     pub endpoint_url: Option<String>,
     #[serde(rename = "s3Credentials")]
     pub s3_credentials: Option<S3Credentials>,
+    /// Restore from Google Cloud Storage instead of S3
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        rename = "googleCredentials"
+    )]
+    pub google_credentials: Option<GoogleCredentials>,
+    /// Restore from Azure Blob Storage instead of S3
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "azureCredentials")]
+    pub azure_credentials: Option<AzureCredentials>,
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, ToSchema, Default)]
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PodAntiAffinityType {
+    /// Spread instances across topology domains when possible
+    #[default]
+    Preferred,
+    /// Never schedule two instances in the same topology domain
+    Required,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+pub struct HighAvailability {
+    /// Number of replicas that have to confirm a commit before it returns to the client.
+    /// 0 keeps replication asynchronous, it can be at most `replicas - 1`
+    #[serde(default, rename = "syncReplicas")]
+    pub sync_replicas: i32,
+    /// Only elect synchronous replicas running in another zone than the primary, as given by
+    /// the `topologyKey` node label
+    #[serde(default, rename = "zoneElectionConstraint")]
+    pub zone_election_constraint: bool,
+    #[serde(default, rename = "podAntiAffinityType")]
+    pub pod_anti_affinity_type: PodAntiAffinityType,
+    #[serde(default = "defaults::default_topology_key", rename = "topologyKey")]
+    pub topology_key: String,
+}
+
+impl Default for HighAvailability {
+    fn default() -> Self {
+        HighAvailability {
+            sync_replicas: 0,
+            zone_election_constraint: false,
+            pod_anti_affinity_type: PodAntiAffinityType::default(),
+            topology_key: defaults::default_topology_key(),
+        }
+    }
+}
+
+/// A separate volume for the write-ahead log
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+pub struct WalStorage {
+    pub size: Quantity,
+    /// Defaults to the storage class of the data volume
+    #[serde(rename = "storageClass")]
+    pub storage_class: Option<String>,
+}
+
+/// A database created in the instance, in addition to the `postgres` database
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
+pub struct Database {
+    pub name: String,
+    #[serde(default = "defaults::default_database_owner")]
+    pub owner: String,
+    /// Character set encoding, like UTF8. Defaults to the encoding of template1
+    pub encoding: Option<String>,
+    /// Sets both LC_COLLATE and LC_CTYPE. Defaults to the locale of template1
+    pub locale: Option<String>,
+}
+
+/// A role managed by CloudNativePG
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
+pub struct Role {
+    pub name: String,
+    #[serde(default = "defaults::default_role_login")]
+    pub login: bool,
+    /// Roles this role is a member of
+    #[serde(default, rename = "inRoles")]
+    pub in_roles: Vec<String>,
+    /// -1 means no limit
+    #[serde(
+        default = "defaults::default_role_connection_limit",
+        rename = "connectionLimit"
+    )]
+    pub connection_limit: i64,
+    /// Name of a kubernetes.io/basic-auth secret holding the username and password of the role.
+    /// The role has no password when it is not set.
+    #[serde(rename = "passwordSecret")]
+    pub password_secret: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, ToSchema)]
 #[allow(non_snake_case)]
 pub struct ConnectionPooler {
     #[serde(default = "defaults::default_conn_pooler_enabled")]
     pub enabled: bool,
     #[serde(default = "defaults::default_pgbouncer")]
     pub pooler: PgBouncer,
+    /// Also deploy a read-only pooler in front of the replicas
+    #[serde(default)]
+    pub readOnly: bool,
+    /// Number of PgBouncer instances of each pooler
+    #[serde(default = "defaults::default_pooler_instances")]
+    pub instances: i32,
+    /// Keep at least this many PgBouncer instances running during voluntary disruptions, like
+    /// node drains. No PodDisruptionBudget is created when it is not set.
+    pub minAvailable: Option<i32>,
+    /// Scale the PgBouncer instances on CPU utilization with a HorizontalPodAutoscaler
+    pub autoscaling: Option<PoolerAutoscaling>,
+}
+
+impl Default for ConnectionPooler {
+    fn default() -> Self {
+        defaults::default_conn_pooler()
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, ToSchema, Default)]
+#[allow(non_snake_case)]
+pub struct PoolerAutoscaling {
+    #[serde(default = "defaults::default_pooler_instances")]
+    pub minReplicas: i32,
+    pub maxReplicas: i32,
+    #[serde(default = "defaults::default_pooler_target_cpu_utilization")]
+    pub targetCPUUtilizationPercentage: i32,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, ToSchema, Default)]
@@ -147,6 +405,16 @@ pub struct CoreDBSpec {
     #[serde(default = "defaults::default_pkglibdir_storage")]
     pub pkglibdirStorage: Quantity,
 
+    /// Storage class of the data volume, the default storage class is used when not set
+    #[serde(rename = "storageClass")]
+    pub storage_class: Option<String>,
+
+    #[serde(rename = "walStorage")]
+    pub wal_storage: Option<WalStorage>,
+
+    #[serde(rename = "highAvailability")]
+    pub high_availability: Option<HighAvailability>,
+
     #[serde(default = "defaults::default_postgres_exporter_enabled")]
     pub postgresExporterEnabled: bool,
 
@@ -196,6 +464,14 @@ pub struct CoreDBSpec {
     #[serde(rename = "appServices")]
     pub app_services: Option<Vec<AppService>>,
 
+    /// Databases to create in addition to the `postgres` database
+    #[serde(default)]
+    pub databases: Vec<Database>,
+
+    /// Roles to manage in addition to the ones created by the operator
+    #[serde(default)]
+    pub roles: Vec<Role>,
+
     // instance restore from backup
     pub restore: Option<Restore>,
 }
@@ -206,6 +482,7 @@ impl CoreDBSpec {
     pub fn get_pg_configs(
         &self,
         requires_load: BTreeMap<String, String>,
+        extension_configs: BTreeMap<String, Vec<PgConfig>>,
     ) -> Result<Option<Vec<PgConfig>>, MergeError> {
         let stack_configs = self
             .stack
@@ -213,10 +490,22 @@ impl CoreDBSpec {
             .and_then(|s| s.postgres_config.clone())
             .unwrap_or_default();
         let mut runtime_configs = self.runtime_config.clone().unwrap_or_default();
-        // TODO: configs that come with extension installation
-        // e.g. let extension_configs = ...
-        // these extensions could be set by the operator, or trunk + operator
-        // trunk install pg_partman could come with something like `pg_partman_bgw.dbname = xxx`
+        // configs that come with enabling an extension, set by trunk or the operator
+        // e.g. enabling pg_partman comes with `pg_partman_bgw.dbname = postgres`
+        let mut enabled_extension_configs: Vec<PgConfig> = Vec::new();
+        for ext in self.extensions.iter() {
+            if ext.locations.iter().any(|location| location.enabled) {
+                enabled_extension_configs.extend(
+                    extension_configs
+                        .get(&ext.name)
+                        .cloned()
+                        .unwrap_or_default()
+                        .into_iter()
+                        // multi-value configs like shared_preload_libraries are merged separately
+                        .filter(|cfg| !MULTI_VAL_CONFIGS.contains(&cfg.name.as_str())),
+                );
+            }
+        }
 
         // Get list of extension names that require load
         let mut include_with_shared_preload_libraries = BTreeSet::new();
@@ -282,7 +571,8 @@ impl CoreDBSpec {
         // 1. stack configs
         // 2. runtime configs
         // 3. merged multivals
-        // 4. overrides
+        // 4. extension configs
+        // 5. overrides
         let mut pg_configs: BTreeMap<String, PgConfig> = BTreeMap::new();
 
         for p in stack_configs {
@@ -294,6 +584,9 @@ impl CoreDBSpec {
         for p in merged_multival_configs {
             pg_configs.insert(p.name.clone(), p);
         }
+        for p in enabled_extension_configs {
+            pg_configs.insert(p.name.clone(), p);
+        }
         if let Some(override_configs) = &self.override_configs {
             for p in override_configs {
                 pg_configs.insert(p.name.clone(), p.clone());
@@ -316,8 +609,9 @@ impl CoreDBSpec {
         &self,
         config_name: &str,
         requires_load: BTreeMap<String, String>,
+        extension_configs: BTreeMap<String, Vec<PgConfig>>,
     ) -> Result<Option<PgConfig>, MergeError> {
-        let all_configs = self.get_pg_configs(requires_load)?;
+        let all_configs = self.get_pg_configs(requires_load, extension_configs)?;
         for config in all_configs.unwrap_or_default() {
             if config.name == config_name {
                 return Ok(Some(config));
@@ -327,6 +621,54 @@ impl CoreDBSpec {
     }
 }
 
+/// An on-demand backup, requested with an annotation on the `CoreDB`, and its progress
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OnDemandBackupStatus {
+    /// Name of the CNPG `Backup`
+    pub name: String,
+    /// Value of the annotation that requested the backup
+    pub requested_at: String,
+    pub phase: Option<String>,
+    pub begin_wal: Option<String>,
+    pub end_wal: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub stopped_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+/// A backup in the CNPG backup catalog of the instance
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupCatalogEntry {
+    /// Name of the CNPG `Backup`
+    pub name: String,
+    /// ID of the backup in the object store
+    pub backup_id: Option<String>,
+    pub phase: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub stopped_at: Option<DateTime<Utc>>,
+    #[serde(rename = "beginLSN")]
+    pub begin_lsn: Option<String>,
+    #[serde(rename = "endLSN")]
+    pub end_lsn: Option<String>,
+    /// Size of the completed backup in bytes, read from the object store. It is unset when the
+    /// operator can't read the backups, like for backups with the IAM role of the instance.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<i64>,
+}
+
+/// The range of time the instance can be restored to with point-in-time recovery
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoverabilityWindow {
+    /// The oldest point that can be restored, the end of the oldest completed backup
+    pub first_recoverability_time: Option<DateTime<Utc>>,
+    /// The latest point that can be restored, when the last WAL was archived
+    pub last_archived_time: Option<DateTime<Utc>>,
+    pub last_archived_wal: Option<String>,
+}
+
 /// The status object of `CoreDB`
 #[derive(Deserialize, Serialize, Clone, Default, Debug, JsonSchema)]
 #[allow(non_snake_case)]
@@ -342,6 +684,30 @@ pub struct CoreDBStatus {
     pub first_recoverability_time: Option<DateTime<Utc>>,
     pub pg_postmaster_start_time: Option<DateTime<Utc>>,
     pub last_fully_reconciled_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conditions: Option<Vec<Condition>>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        rename = "observedGeneration"
+    )]
+    pub observed_generation: Option<i64>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        rename = "poolerReadyReplicas"
+    )]
+    pub pooler_ready_replicas: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backups: Option<Vec<OnDemandBackupStatus>>,
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "backupCatalog")]
+    pub backup_catalog: Option<Vec<BackupCatalogEntry>>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        rename = "recoverabilityWindow"
+    )]
+    pub recoverability_window: Option<RecoverabilityWindow>,
 }
 
 #[cfg(test)]
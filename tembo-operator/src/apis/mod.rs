@@ -1,2 +1,3 @@
 pub mod coredb_types;
 pub mod postgres_parameters;
+pub mod v1beta1;
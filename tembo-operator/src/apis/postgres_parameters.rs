@@ -377,7 +377,10 @@ impl<'de> Deserialize<'de> for PgConfig {
 #[cfg(test)]
 mod pg_param_tests {
     use super::*;
-    use crate::apis::coredb_types::{CoreDBSpec, Stack};
+    use crate::{
+        apis::coredb_types::{CoreDBSpec, Stack},
+        extensions::types::{Extension, ExtensionInstallLocation},
+    };
     use std::collections::BTreeMap;
 
     #[test]
@@ -437,7 +440,7 @@ mod pg_param_tests {
         requires_load.insert("pg_cron".to_string(), "pg_cron".to_string());
         requires_load.insert("pg_stat_statements".to_string(), "pg_stat_statements".to_string());
         let pg_configs = spec
-            .get_pg_configs(requires_load)
+            .get_pg_configs(requires_load, BTreeMap::new())
             .expect("failed to get pg configs")
             .expect("expected configs");
 
@@ -456,6 +459,59 @@ mod pg_param_tests {
         );
     }
 
+    #[test]
+    fn test_get_configs_with_extension_configs() {
+        let extension = |name: &str, enabled: bool| Extension {
+            name: name.to_string(),
+            description: None,
+            locations: vec![ExtensionInstallLocation {
+                enabled,
+                ..ExtensionInstallLocation::default()
+            }],
+        };
+        let spec = CoreDBSpec {
+            extensions: vec![extension("pg_partman", true), extension("pg_cron", false)],
+            runtime_config: Some(vec![PgConfig {
+                name: "pg_partman_bgw.interval".to_string(),
+                value: "3600".parse().unwrap(),
+            }]),
+            override_configs: Some(vec![PgConfig {
+                name: "pg_partman_bgw.role".to_string(),
+                value: "partman".parse().unwrap(),
+            }]),
+            ..Default::default()
+        };
+        let mut extension_configs: BTreeMap<String, Vec<PgConfig>> = BTreeMap::new();
+        extension_configs.insert("pg_partman".to_string(), vec![
+            PgConfig {
+                name: "pg_partman_bgw.interval".to_string(),
+                value: "60".parse().unwrap(),
+            },
+            PgConfig {
+                name: "pg_partman_bgw.role".to_string(),
+                value: "postgres".parse().unwrap(),
+            },
+        ]);
+        extension_configs.insert("pg_cron".to_string(), vec![PgConfig {
+            name: "cron.host".to_string(),
+            value: "/controller/run".parse().unwrap(),
+        }]);
+        let pg_configs = spec
+            .get_pg_configs(BTreeMap::new(), extension_configs)
+            .expect("failed to get pg configs")
+            .expect("expected configs");
+
+        // the configs of pg_cron are not included because it is disabled, extension configs
+        // take precedence over runtime configs, and overrides over extension configs
+        let pg_configs: BTreeMap<String, String> = pg_configs
+            .into_iter()
+            .map(|cfg| (cfg.name, cfg.value.to_string()))
+            .collect();
+        assert!(!pg_configs.contains_key("cron.host"));
+        assert_eq!(pg_configs["pg_partman_bgw.interval"], "60");
+        assert_eq!(pg_configs["pg_partman_bgw.role"], "partman");
+    }
+
     #[test]
     fn test_alpha_order_multiple() {
         // assert ordering of multi values is according to the priority list
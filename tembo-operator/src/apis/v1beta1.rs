@@ -0,0 +1,949 @@
+// The v1beta1 version of the CoreDB API. It carries the same information as v1alpha1, but every field
+// is consistently camelCased. v1alpha1 remains the storage version, the API server converts between
+// the two versions through the conversion webhook (see webhook.rs).
+use crate::{
+    apis::{
+        coredb_types as v1alpha1,
+        coredb_types::{
+            AzureCredentials, BackupCompression, Database, GoogleCredentials, HighAvailability,
+            PoolerAutoscaling, Role, S3Credentials, ServiceAccountTemplate, WalStorage,
+        },
+        postgres_parameters::PgConfig,
+    },
+    app_service::types::AppService,
+    cloudnativepg::poolers::{PoolerPgbouncerPoolMode, PoolerTemplateSpecContainersResources},
+    defaults,
+    extensions::types::{
+        Extension, ExtensionInstallLocationStatus as V1alpha1ExtensionInstallLocationStatus,
+        ExtensionStatus as V1alpha1ExtensionStatus, TrunkInstall,
+        TrunkInstallPodAttempt as V1alpha1TrunkInstallPodAttempt,
+        TrunkInstallStatus as V1alpha1TrunkInstallStatus,
+    },
+    postgres_exporter::PostgresMetrics,
+};
+use chrono::{DateTime, Utc};
+use k8s_openapi::{
+    api::core::v1::ResourceRequirements,
+    apimachinery::pkg::{api::resource::Quantity, apis::meta::v1::Condition},
+};
+use kube::CustomResource;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Stack {
+    pub name: String,
+    pub postgres_config: Option<Vec<PgConfig>>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Backup {
+    #[serde(default = "defaults::default_destination_path")]
+    pub destination_path: Option<String>,
+    #[serde(default = "defaults::default_encryption")]
+    pub encryption: Option<String>,
+    #[serde(default = "defaults::default_retention_policy")]
+    pub retention_policy: Option<String>,
+    #[serde(default = "defaults::default_backup_schedule")]
+    pub schedule: Option<String>,
+    #[serde(default)]
+    pub endpoint_url: Option<String>,
+    #[serde(default = "defaults::default_s3_credentials")]
+    pub s3_credentials: Option<S3Credentials>,
+    /// Back up to Google Cloud Storage instead of S3
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub google_credentials: Option<GoogleCredentials>,
+    /// Back up to Azure Blob Storage instead of S3
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub azure_credentials: Option<AzureCredentials>,
+    /// Compression of the base backups. Defaults to the setting of the stack.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_compression: Option<BackupCompression>,
+    /// Compression of the archived WAL files. Defaults to the setting of the stack.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wal_compression: Option<BackupCompression>,
+    /// Number of parallel jobs uploading a base backup. Defaults to the setting of the stack.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jobs: Option<i32>,
+    /// Maximum number of WAL files archived in one call of the archive command, and restored in
+    /// parallel during a recovery. Defaults to the setting of the stack.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wal_max_parallel: Option<i64>,
+    /// Take a base backup as soon as the instance is bootstrapped, instead of waiting for the
+    /// schedule. Defaults to true.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub immediate_backup: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Restore {
+    pub server_name: String,
+    pub recovery_target_time: Option<String>,
+    #[serde(default)]
+    pub endpoint_url: Option<String>,
+    pub s3_credentials: Option<S3Credentials>,
+    /// Restore from Google Cloud Storage instead of S3
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub google_credentials: Option<GoogleCredentials>,
+    /// Restore from Azure Blob Storage instead of S3
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub azure_credentials: Option<AzureCredentials>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionPooler {
+    #[serde(default = "defaults::default_conn_pooler_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_pgbouncer")]
+    pub pooler: PgBouncer,
+    /// Also deploy a read-only pooler in front of the replicas
+    #[serde(default)]
+    pub read_only: bool,
+    /// Number of PgBouncer instances of each pooler
+    #[serde(default = "defaults::default_pooler_instances")]
+    pub instances: i32,
+    /// Keep at least this many PgBouncer instances running during voluntary disruptions, like
+    /// node drains. No PodDisruptionBudget is created when it is not set.
+    pub min_available: Option<i32>,
+    /// Scale the PgBouncer instances on CPU utilization with a HorizontalPodAutoscaler
+    pub autoscaling: Option<PoolerAutoscaling>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PgBouncer {
+    #[serde(default = "defaults::default_pool_mode")]
+    pub pool_mode: PoolerPgbouncerPoolMode,
+    // Valid parameter values can be found at https://www.pgbouncer.org/config.html
+    pub parameters: Option<BTreeMap<String, String>>,
+    pub resources: Option<PoolerTemplateSpecContainersResources>,
+}
+
+/// Generate the Kubernetes wrapper struct `CoreDB` for the v1beta1 API version
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[cfg_attr(test, derive(Default))]
+#[kube(kind = "CoreDB", group = "coredb.io", version = "v1beta1", namespaced)]
+#[kube(status = "CoreDBStatus", shortname = "cdb")]
+#[serde(rename_all = "camelCase")]
+pub struct CoreDBSpec {
+    #[serde(default = "defaults::default_replicas")]
+    pub replicas: i32,
+
+    #[serde(default = "defaults::default_resources")]
+    pub resources: ResourceRequirements,
+
+    #[serde(default = "defaults::default_storage")]
+    pub storage: Quantity,
+
+    #[serde(default = "defaults::default_sharedir_storage")]
+    pub sharedir_storage: Quantity,
+
+    #[serde(default = "defaults::default_pkglibdir_storage")]
+    pub pkglibdir_storage: Quantity,
+
+    /// Storage class of the data volume, the default storage class is used when not set
+    pub storage_class: Option<String>,
+
+    pub wal_storage: Option<WalStorage>,
+
+    pub high_availability: Option<HighAvailability>,
+
+    #[serde(default = "defaults::default_postgres_exporter_enabled")]
+    pub postgres_exporter_enabled: bool,
+
+    #[serde(default = "defaults::default_image")]
+    pub image: String,
+
+    #[serde(default = "defaults::default_postgres_exporter_image")]
+    pub postgres_exporter_image: String,
+
+    #[serde(default = "defaults::default_port")]
+    pub port: i32,
+
+    #[serde(default = "defaults::default_uid")]
+    pub uid: i32,
+
+    #[serde(default = "defaults::default_extensions")]
+    pub extensions: Vec<Extension>,
+
+    #[serde(default = "defaults::default_trunk_installs")]
+    pub trunk_installs: Vec<TrunkInstall>,
+
+    #[serde(default = "defaults::default_stop")]
+    pub stop: bool,
+
+    #[serde(default = "defaults::default_service_account_template")]
+    pub service_account_template: ServiceAccountTemplate,
+
+    #[serde(default = "default_backup")]
+    pub backup: Backup,
+
+    pub metrics: Option<PostgresMetrics>,
+
+    pub extra_domains_rw: Option<Vec<String>>,
+
+    /// List of IPv4 CIDR blocks
+    pub ip_allow_list: Option<Vec<String>>,
+
+    pub stack: Option<Stack>,
+    // dynamic runtime configs
+    pub runtime_config: Option<Vec<PgConfig>>,
+    // configuration overrides, typically defined by the user
+    pub override_configs: Option<Vec<PgConfig>>,
+    // Connection pooler configuration
+    #[serde(default = "default_conn_pooler")]
+    pub connection_pooler: ConnectionPooler,
+
+    pub app_services: Option<Vec<AppService>>,
+
+    /// Databases to create in addition to the `postgres` database
+    #[serde(default)]
+    pub databases: Vec<Database>,
+
+    /// Roles to manage in addition to the ones created by the operator
+    #[serde(default)]
+    pub roles: Vec<Role>,
+
+    // instance restore from backup
+    pub restore: Option<Restore>,
+}
+
+/// The status object of `CoreDB`
+#[derive(Deserialize, Serialize, Clone, Default, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CoreDBStatus {
+    pub running: bool,
+    #[serde(default = "defaults::default_extensions_updating")]
+    pub extensions_updating: bool,
+    pub extensions: Option<Vec<ExtensionStatus>>,
+    pub trunk_installs: Option<Vec<TrunkInstallStatus>>,
+    pub storage: Option<Quantity>,
+    pub resources: Option<ResourceRequirements>,
+    pub runtime_config: Option<Vec<PgConfig>>,
+    pub first_recoverability_time: Option<DateTime<Utc>>,
+    pub pg_postmaster_start_time: Option<DateTime<Utc>>,
+    pub last_fully_reconciled_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conditions: Option<Vec<Condition>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub observed_generation: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pooler_ready_replicas: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backups: Option<Vec<OnDemandBackupStatus>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_catalog: Option<Vec<BackupCatalogEntry>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recoverability_window: Option<RecoverabilityWindow>,
+}
+
+/// A backup in the CNPG backup catalog of the instance
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupCatalogEntry {
+    /// Name of the CNPG `Backup`
+    pub name: String,
+    /// ID of the backup in the object store
+    pub backup_id: Option<String>,
+    pub phase: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub stopped_at: Option<DateTime<Utc>>,
+    #[serde(rename = "beginLSN")]
+    pub begin_lsn: Option<String>,
+    #[serde(rename = "endLSN")]
+    pub end_lsn: Option<String>,
+    /// Size of the completed backup in bytes, read from the object store. It is unset when the
+    /// operator can't read the backups, like for backups with the IAM role of the instance.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<i64>,
+}
+
+/// The range of time the instance can be restored to with point-in-time recovery
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoverabilityWindow {
+    /// The oldest point that can be restored, the end of the oldest completed backup
+    pub first_recoverability_time: Option<DateTime<Utc>>,
+    /// The latest point that can be restored, when the last WAL was archived
+    pub last_archived_time: Option<DateTime<Utc>>,
+    pub last_archived_wal: Option<String>,
+}
+
+/// An on-demand backup, requested with an annotation on the `CoreDB`, and its progress
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OnDemandBackupStatus {
+    /// Name of the CNPG `Backup`
+    pub name: String,
+    /// Value of the annotation that requested the backup
+    pub requested_at: String,
+    pub phase: Option<String>,
+    pub begin_wal: Option<String>,
+    pub end_wal: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub stopped_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, Hash, JsonSchema, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TrunkInstallStatus {
+    pub name: String,
+    pub version: Option<String>,
+    pub error: bool,
+    #[serde(default)]
+    pub loading: bool,
+    pub error_message: Option<String>,
+    pub installed_to_pods: Option<Vec<String>>,
+    #[serde(default)]
+    pub attempts: Option<Vec<TrunkInstallPodAttempt>>,
+}
+
+/// Install attempts of a trunk install on one pod, failed installs are retried with backoff
+#[derive(Clone, Debug, Deserialize, Eq, Hash, JsonSchema, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TrunkInstallPodAttempt {
+    pub pod_name: String,
+    pub attempts: i32,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, Hash, JsonSchema, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtensionStatus {
+    pub name: String,
+    #[serde(default = "defaults::default_description")]
+    pub description: Option<String>,
+    pub locations: Vec<ExtensionInstallLocationStatus>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, Hash, JsonSchema, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtensionInstallLocationStatus {
+    #[serde(default = "defaults::default_database")]
+    pub database: String,
+    pub schema: Option<String>,
+    pub version: Option<String>,
+    // None means this is not actually installed
+    pub enabled: Option<bool>,
+    pub error: Option<bool>,
+    pub error_message: Option<String>,
+    /// Version requested in the spec, when it differs from the installed version
+    #[serde(default)]
+    pub target_version: Option<String>,
+    /// Versions the extension goes through to reach the target version, like 1.0--1.1--1.2
+    #[serde(default)]
+    pub update_path: Option<String>,
+    /// Schema requested in the spec, when it differs from the schema the extension is in
+    #[serde(default)]
+    pub target_schema: Option<String>,
+}
+
+fn default_backup() -> Backup {
+    defaults::default_backup().into()
+}
+
+impl Default for ConnectionPooler {
+    fn default() -> Self {
+        default_conn_pooler()
+    }
+}
+
+fn default_conn_pooler() -> ConnectionPooler {
+    defaults::default_conn_pooler().into()
+}
+
+fn default_pgbouncer() -> PgBouncer {
+    defaults::default_pgbouncer().into()
+}
+
+// Conversions between v1alpha1 and v1beta1. Both versions carry the same fields, so converting
+// an object to the other version and back again results in the original object.
+
+impl From<v1alpha1::CoreDB> for CoreDB {
+    fn from(cdb: v1alpha1::CoreDB) -> Self {
+        CoreDB {
+            metadata: cdb.metadata,
+            spec: cdb.spec.into(),
+            status: cdb.status.map(Into::into),
+        }
+    }
+}
+
+impl From<CoreDB> for v1alpha1::CoreDB {
+    fn from(cdb: CoreDB) -> Self {
+        v1alpha1::CoreDB {
+            metadata: cdb.metadata,
+            spec: cdb.spec.into(),
+            status: cdb.status.map(Into::into),
+        }
+    }
+}
+
+impl From<v1alpha1::CoreDBSpec> for CoreDBSpec {
+    fn from(spec: v1alpha1::CoreDBSpec) -> Self {
+        CoreDBSpec {
+            replicas: spec.replicas,
+            resources: spec.resources,
+            storage: spec.storage,
+            sharedir_storage: spec.sharedirStorage,
+            pkglibdir_storage: spec.pkglibdirStorage,
+            storage_class: spec.storage_class,
+            wal_storage: spec.wal_storage,
+            high_availability: spec.high_availability,
+            postgres_exporter_enabled: spec.postgresExporterEnabled,
+            image: spec.image,
+            postgres_exporter_image: spec.postgresExporterImage,
+            port: spec.port,
+            uid: spec.uid,
+            extensions: spec.extensions,
+            trunk_installs: spec.trunk_installs,
+            stop: spec.stop,
+            service_account_template: spec.serviceAccountTemplate,
+            backup: spec.backup.into(),
+            metrics: spec.metrics,
+            extra_domains_rw: spec.extra_domains_rw,
+            ip_allow_list: spec.ip_allow_list,
+            stack: spec.stack.map(Into::into),
+            runtime_config: spec.runtime_config,
+            override_configs: spec.override_configs,
+            connection_pooler: spec.connectionPooler.into(),
+            app_services: spec.app_services,
+            databases: spec.databases,
+            roles: spec.roles,
+            restore: spec.restore.map(Into::into),
+        }
+    }
+}
+
+impl From<CoreDBSpec> for v1alpha1::CoreDBSpec {
+    fn from(spec: CoreDBSpec) -> Self {
+        v1alpha1::CoreDBSpec {
+            replicas: spec.replicas,
+            resources: spec.resources,
+            storage: spec.storage,
+            sharedirStorage: spec.sharedir_storage,
+            pkglibdirStorage: spec.pkglibdir_storage,
+            storage_class: spec.storage_class,
+            wal_storage: spec.wal_storage,
+            high_availability: spec.high_availability,
+            postgresExporterEnabled: spec.postgres_exporter_enabled,
+            image: spec.image,
+            postgresExporterImage: spec.postgres_exporter_image,
+            port: spec.port,
+            uid: spec.uid,
+            extensions: spec.extensions,
+            trunk_installs: spec.trunk_installs,
+            stop: spec.stop,
+            serviceAccountTemplate: spec.service_account_template,
+            backup: spec.backup.into(),
+            metrics: spec.metrics,
+            extra_domains_rw: spec.extra_domains_rw,
+            ip_allow_list: spec.ip_allow_list,
+            stack: spec.stack.map(Into::into),
+            runtime_config: spec.runtime_config,
+            override_configs: spec.override_configs,
+            connectionPooler: spec.connection_pooler.into(),
+            app_services: spec.app_services,
+            databases: spec.databases,
+            roles: spec.roles,
+            restore: spec.restore.map(Into::into),
+        }
+    }
+}
+
+impl From<v1alpha1::CoreDBStatus> for CoreDBStatus {
+    fn from(status: v1alpha1::CoreDBStatus) -> Self {
+        CoreDBStatus {
+            running: status.running,
+            extensions_updating: status.extensionsUpdating,
+            extensions: status
+                .extensions
+                .map(|exts| exts.into_iter().map(Into::into).collect()),
+            trunk_installs: status
+                .trunk_installs
+                .map(|installs| installs.into_iter().map(Into::into).collect()),
+            storage: status.storage,
+            resources: status.resources,
+            runtime_config: status.runtime_config,
+            first_recoverability_time: status.first_recoverability_time,
+            pg_postmaster_start_time: status.pg_postmaster_start_time,
+            last_fully_reconciled_at: status.last_fully_reconciled_at,
+            conditions: status.conditions,
+            observed_generation: status.observed_generation,
+            pooler_ready_replicas: status.pooler_ready_replicas,
+            backups: status
+                .backups
+                .map(|backups| backups.into_iter().map(Into::into).collect()),
+            backup_catalog: status
+                .backup_catalog
+                .map(|catalog| catalog.into_iter().map(Into::into).collect()),
+            recoverability_window: status.recoverability_window.map(Into::into),
+        }
+    }
+}
+
+impl From<CoreDBStatus> for v1alpha1::CoreDBStatus {
+    fn from(status: CoreDBStatus) -> Self {
+        v1alpha1::CoreDBStatus {
+            running: status.running,
+            extensionsUpdating: status.extensions_updating,
+            extensions: status
+                .extensions
+                .map(|exts| exts.into_iter().map(Into::into).collect()),
+            trunk_installs: status
+                .trunk_installs
+                .map(|installs| installs.into_iter().map(Into::into).collect()),
+            storage: status.storage,
+            resources: status.resources,
+            runtime_config: status.runtime_config,
+            first_recoverability_time: status.first_recoverability_time,
+            pg_postmaster_start_time: status.pg_postmaster_start_time,
+            last_fully_reconciled_at: status.last_fully_reconciled_at,
+            conditions: status.conditions,
+            observed_generation: status.observed_generation,
+            pooler_ready_replicas: status.pooler_ready_replicas,
+            backups: status
+                .backups
+                .map(|backups| backups.into_iter().map(Into::into).collect()),
+            backup_catalog: status
+                .backup_catalog
+                .map(|catalog| catalog.into_iter().map(Into::into).collect()),
+            recoverability_window: status.recoverability_window.map(Into::into),
+        }
+    }
+}
+
+impl From<v1alpha1::Stack> for Stack {
+    fn from(stack: v1alpha1::Stack) -> Self {
+        Stack {
+            name: stack.name,
+            postgres_config: stack.postgres_config,
+        }
+    }
+}
+
+impl From<Stack> for v1alpha1::Stack {
+    fn from(stack: Stack) -> Self {
+        v1alpha1::Stack {
+            name: stack.name,
+            postgres_config: stack.postgres_config,
+        }
+    }
+}
+
+impl From<v1alpha1::Backup> for Backup {
+    fn from(backup: v1alpha1::Backup) -> Self {
+        Backup {
+            destination_path: backup.destinationPath,
+            encryption: backup.encryption,
+            retention_policy: backup.retentionPolicy,
+            schedule: backup.schedule,
+            endpoint_url: backup.endpoint_url,
+            s3_credentials: backup.s3_credentials,
+            google_credentials: backup.google_credentials,
+            azure_credentials: backup.azure_credentials,
+            data_compression: backup.data_compression,
+            wal_compression: backup.wal_compression,
+            jobs: backup.jobs,
+            wal_max_parallel: backup.wal_max_parallel,
+            immediate_backup: backup.immediate_backup,
+        }
+    }
+}
+
+impl From<Backup> for v1alpha1::Backup {
+    fn from(backup: Backup) -> Self {
+        v1alpha1::Backup {
+            destinationPath: backup.destination_path,
+            encryption: backup.encryption,
+            retentionPolicy: backup.retention_policy,
+            schedule: backup.schedule,
+            endpoint_url: backup.endpoint_url,
+            s3_credentials: backup.s3_credentials,
+            google_credentials: backup.google_credentials,
+            azure_credentials: backup.azure_credentials,
+            data_compression: backup.data_compression,
+            wal_compression: backup.wal_compression,
+            jobs: backup.jobs,
+            wal_max_parallel: backup.wal_max_parallel,
+            immediate_backup: backup.immediate_backup,
+        }
+    }
+}
+
+impl From<v1alpha1::Restore> for Restore {
+    fn from(restore: v1alpha1::Restore) -> Self {
+        Restore {
+            server_name: restore.server_name,
+            recovery_target_time: restore.recovery_target_time,
+            endpoint_url: restore.endpoint_url,
+            s3_credentials: restore.s3_credentials,
+            google_credentials: restore.google_credentials,
+            azure_credentials: restore.azure_credentials,
+        }
+    }
+}
+
+impl From<Restore> for v1alpha1::Restore {
+    fn from(restore: Restore) -> Self {
+        v1alpha1::Restore {
+            server_name: restore.server_name,
+            recovery_target_time: restore.recovery_target_time,
+            endpoint_url: restore.endpoint_url,
+            s3_credentials: restore.s3_credentials,
+            google_credentials: restore.google_credentials,
+            azure_credentials: restore.azure_credentials,
+        }
+    }
+}
+
+impl From<v1alpha1::ConnectionPooler> for ConnectionPooler {
+    fn from(pooler: v1alpha1::ConnectionPooler) -> Self {
+        ConnectionPooler {
+            enabled: pooler.enabled,
+            pooler: pooler.pooler.into(),
+            read_only: pooler.readOnly,
+            instances: pooler.instances,
+            min_available: pooler.minAvailable,
+            autoscaling: pooler.autoscaling,
+        }
+    }
+}
+
+impl From<ConnectionPooler> for v1alpha1::ConnectionPooler {
+    fn from(pooler: ConnectionPooler) -> Self {
+        v1alpha1::ConnectionPooler {
+            enabled: pooler.enabled,
+            pooler: pooler.pooler.into(),
+            readOnly: pooler.read_only,
+            instances: pooler.instances,
+            minAvailable: pooler.min_available,
+            autoscaling: pooler.autoscaling,
+        }
+    }
+}
+
+impl From<v1alpha1::PgBouncer> for PgBouncer {
+    fn from(pgbouncer: v1alpha1::PgBouncer) -> Self {
+        PgBouncer {
+            pool_mode: pgbouncer.poolMode,
+            parameters: pgbouncer.parameters,
+            resources: pgbouncer.resources,
+        }
+    }
+}
+
+impl From<PgBouncer> for v1alpha1::PgBouncer {
+    fn from(pgbouncer: PgBouncer) -> Self {
+        v1alpha1::PgBouncer {
+            poolMode: pgbouncer.pool_mode,
+            parameters: pgbouncer.parameters,
+            resources: pgbouncer.resources,
+        }
+    }
+}
+
+impl From<V1alpha1TrunkInstallStatus> for TrunkInstallStatus {
+    fn from(status: V1alpha1TrunkInstallStatus) -> Self {
+        TrunkInstallStatus {
+            name: status.name,
+            version: status.version,
+            error: status.error,
+            loading: status.loading,
+            error_message: status.error_message,
+            installed_to_pods: status.installed_to_pods,
+            attempts: status
+                .attempts
+                .map(|attempts| attempts.into_iter().map(Into::into).collect()),
+        }
+    }
+}
+
+impl From<TrunkInstallStatus> for V1alpha1TrunkInstallStatus {
+    fn from(status: TrunkInstallStatus) -> Self {
+        V1alpha1TrunkInstallStatus {
+            name: status.name,
+            version: status.version,
+            error: status.error,
+            loading: status.loading,
+            error_message: status.error_message,
+            installed_to_pods: status.installed_to_pods,
+            attempts: status
+                .attempts
+                .map(|attempts| attempts.into_iter().map(Into::into).collect()),
+        }
+    }
+}
+
+impl From<v1alpha1::OnDemandBackupStatus> for OnDemandBackupStatus {
+    fn from(backup: v1alpha1::OnDemandBackupStatus) -> Self {
+        OnDemandBackupStatus {
+            name: backup.name,
+            requested_at: backup.requested_at,
+            phase: backup.phase,
+            begin_wal: backup.begin_wal,
+            end_wal: backup.end_wal,
+            started_at: backup.started_at,
+            stopped_at: backup.stopped_at,
+            error: backup.error,
+        }
+    }
+}
+
+impl From<OnDemandBackupStatus> for v1alpha1::OnDemandBackupStatus {
+    fn from(backup: OnDemandBackupStatus) -> Self {
+        v1alpha1::OnDemandBackupStatus {
+            name: backup.name,
+            requested_at: backup.requested_at,
+            phase: backup.phase,
+            begin_wal: backup.begin_wal,
+            end_wal: backup.end_wal,
+            started_at: backup.started_at,
+            stopped_at: backup.stopped_at,
+            error: backup.error,
+        }
+    }
+}
+
+impl From<v1alpha1::BackupCatalogEntry> for BackupCatalogEntry {
+    fn from(backup: v1alpha1::BackupCatalogEntry) -> Self {
+        BackupCatalogEntry {
+            name: backup.name,
+            backup_id: backup.backup_id,
+            phase: backup.phase,
+            started_at: backup.started_at,
+            stopped_at: backup.stopped_at,
+            begin_lsn: backup.begin_lsn,
+            end_lsn: backup.end_lsn,
+            size: backup.size,
+        }
+    }
+}
+
+impl From<BackupCatalogEntry> for v1alpha1::BackupCatalogEntry {
+    fn from(backup: BackupCatalogEntry) -> Self {
+        v1alpha1::BackupCatalogEntry {
+            name: backup.name,
+            backup_id: backup.backup_id,
+            phase: backup.phase,
+            started_at: backup.started_at,
+            stopped_at: backup.stopped_at,
+            begin_lsn: backup.begin_lsn,
+            end_lsn: backup.end_lsn,
+            size: backup.size,
+        }
+    }
+}
+
+impl From<v1alpha1::RecoverabilityWindow> for RecoverabilityWindow {
+    fn from(window: v1alpha1::RecoverabilityWindow) -> Self {
+        RecoverabilityWindow {
+            first_recoverability_time: window.first_recoverability_time,
+            last_archived_time: window.last_archived_time,
+            last_archived_wal: window.last_archived_wal,
+        }
+    }
+}
+
+impl From<RecoverabilityWindow> for v1alpha1::RecoverabilityWindow {
+    fn from(window: RecoverabilityWindow) -> Self {
+        v1alpha1::RecoverabilityWindow {
+            first_recoverability_time: window.first_recoverability_time,
+            last_archived_time: window.last_archived_time,
+            last_archived_wal: window.last_archived_wal,
+        }
+    }
+}
+
+impl From<V1alpha1TrunkInstallPodAttempt> for TrunkInstallPodAttempt {
+    fn from(attempt: V1alpha1TrunkInstallPodAttempt) -> Self {
+        TrunkInstallPodAttempt {
+            pod_name: attempt.pod_name,
+            attempts: attempt.attempts,
+            last_attempt_at: attempt.last_attempt_at,
+            last_error: attempt.last_error,
+        }
+    }
+}
+
+impl From<TrunkInstallPodAttempt> for V1alpha1TrunkInstallPodAttempt {
+    fn from(attempt: TrunkInstallPodAttempt) -> Self {
+        V1alpha1TrunkInstallPodAttempt {
+            pod_name: attempt.pod_name,
+            attempts: attempt.attempts,
+            last_attempt_at: attempt.last_attempt_at,
+            last_error: attempt.last_error,
+        }
+    }
+}
+
+impl From<V1alpha1ExtensionStatus> for ExtensionStatus {
+    fn from(status: V1alpha1ExtensionStatus) -> Self {
+        ExtensionStatus {
+            name: status.name,
+            description: status.description,
+            locations: status.locations.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<ExtensionStatus> for V1alpha1ExtensionStatus {
+    fn from(status: ExtensionStatus) -> Self {
+        V1alpha1ExtensionStatus {
+            name: status.name,
+            description: status.description,
+            locations: status.locations.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<V1alpha1ExtensionInstallLocationStatus> for ExtensionInstallLocationStatus {
+    fn from(status: V1alpha1ExtensionInstallLocationStatus) -> Self {
+        ExtensionInstallLocationStatus {
+            database: status.database,
+            schema: status.schema,
+            version: status.version,
+            enabled: status.enabled,
+            error: status.error,
+            error_message: status.error_message,
+            target_version: status.target_version,
+            update_path: status.update_path,
+            target_schema: status.target_schema,
+        }
+    }
+}
+
+impl From<ExtensionInstallLocationStatus> for V1alpha1ExtensionInstallLocationStatus {
+    fn from(status: ExtensionInstallLocationStatus) -> Self {
+        V1alpha1ExtensionInstallLocationStatus {
+            database: status.database,
+            schema: status.schema,
+            version: status.version,
+            enabled: status.enabled,
+            error: status.error,
+            error_message: status.error_message,
+            target_version: status.target_version,
+            update_path: status.update_path,
+            target_schema: status.target_schema,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn v1alpha1_coredb() -> v1alpha1::CoreDB {
+        serde_json::from_value(json!({
+            "apiVersion": "coredb.io/v1alpha1",
+            "kind": "CoreDB",
+            "metadata": {"name": "test", "namespace": "default"},
+            "spec": {
+                "replicas": 2,
+                "sharedirStorage": "2Gi",
+                "postgresExporterEnabled": false,
+                "trunk_installs": [{"name": "pgmq", "version": "0.10.2"}],
+                "extra_domains_rw": ["test.example.com"],
+                "ipAllowList": ["10.0.0.0/8"],
+                "stack": {
+                    "name": "MessageQueue",
+                    "postgres_config": [{"name": "max_connections", "value": "300"}]
+                },
+                "runtime_config": [{"name": "shared_buffers", "value": "1GB"}],
+                "override_configs": [{"name": "work_mem", "value": "64MB"}],
+                "backup": {
+                    "destinationPath": "s3://bucket/test",
+                    "endpointURL": "https://s3.example.com"
+                },
+                "restore": {
+                    "serverName": "original",
+                    "recoveryTargetTime": "2023-09-26T21:15:42Z"
+                },
+                "connectionPooler": {"enabled": true, "pooler": {"poolMode": "session"}}
+            },
+            "status": {
+                "running": true,
+                "extensionsUpdating": false,
+                "trunk_installs": [{
+                    "name": "pgmq",
+                    "version": "0.10.2",
+                    "error": false,
+                    "error_message": null,
+                    "installed_to_pods": ["test-1"]
+                }],
+                "extensions": [{
+                    "name": "pgmq",
+                    "description": null,
+                    "locations": [{"database": "postgres", "enabled": true, "error": true, "error_message": "oops"}]
+                }],
+                "first_recoverability_time": "2023-09-26T21:15:42Z",
+                "conditions": [{
+                    "type": "Ready",
+                    "status": "True",
+                    "reason": "Reconciled",
+                    "message": "",
+                    "lastTransitionTime": "2023-09-26T21:15:42Z",
+                    "observedGeneration": 3
+                }],
+                "observedGeneration": 3
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_v1alpha1_to_v1beta1_field_names() {
+        let cdb: CoreDB = v1alpha1_coredb().into();
+        let value = serde_json::to_value(&cdb).unwrap();
+        assert_eq!(value["spec"]["sharedirStorage"], "2Gi");
+        assert_eq!(value["spec"]["trunkInstalls"][0]["name"], "pgmq");
+        assert_eq!(value["spec"]["extraDomainsRw"][0], "test.example.com");
+        assert_eq!(value["spec"]["stack"]["postgresConfig"][0]["value"], "300");
+        assert_eq!(value["spec"]["runtimeConfig"][0]["name"], "shared_buffers");
+        assert_eq!(value["spec"]["overrideConfigs"][0]["name"], "work_mem");
+        assert_eq!(value["spec"]["backup"]["destinationPath"], "s3://bucket/test");
+        assert_eq!(value["spec"]["backup"]["endpointUrl"], "https://s3.example.com");
+        assert_eq!(
+            value["spec"]["restore"]["recoveryTargetTime"],
+            "2023-09-26T21:15:42Z"
+        );
+        assert_eq!(value["spec"]["connectionPooler"]["pooler"]["poolMode"], "session");
+        assert_eq!(
+            value["status"]["trunkInstalls"][0]["installedToPods"][0],
+            "test-1"
+        );
+        assert_eq!(
+            value["status"]["extensions"][0]["locations"][0]["errorMessage"],
+            "oops"
+        );
+        assert_eq!(value["status"]["firstRecoverabilityTime"], "2023-09-26T21:15:42Z");
+        assert_eq!(value["status"]["conditions"][0]["type"], "Ready");
+        assert_eq!(value["status"]["observedGeneration"], 3);
+    }
+
+    #[test]
+    fn test_round_trip_conversion() {
+        let original = v1alpha1_coredb();
+        let beta: CoreDB = original.clone().into();
+        let alpha: v1alpha1::CoreDB = beta.clone().into();
+        assert_eq!(
+            serde_json::to_value(&original).unwrap(),
+            serde_json::to_value(&alpha).unwrap()
+        );
+
+        // And the other way around, starting from a v1beta1 manifest
+        let beta_again: CoreDB = alpha.into();
+        assert_eq!(
+            serde_json::to_value(&beta).unwrap(),
+            serde_json::to_value(&beta_again).unwrap()
+        );
+    }
+}
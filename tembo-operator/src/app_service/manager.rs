@@ -1,4 +1,6 @@
-use crate::{apis::coredb_types::CoreDB, ingress_route_crd::IngressRouteRoutes, Context, Error, Result};
+use crate::{
+    apis::coredb_types::CoreDB, ingress_route_crd::IngressRouteRoutes, publish_event, Context, Error, Result,
+};
 use k8s_openapi::{
     api::{
         apps::v1::{Deployment, DeploymentSpec},
@@ -14,7 +16,7 @@ use k8s_openapi::{
 };
 use kube::{
     api::{Api, ListParams, ObjectMeta, Patch, PatchParams, ResourceExt},
-    runtime::controller::Action,
+    runtime::{controller::Action, events::EventType},
     Client, Resource,
 };
 use std::{collections::BTreeMap, sync::Arc, time::Duration};
@@ -42,13 +44,14 @@ fn generate_resource(
     namespace: &str,
     oref: OwnerReference,
     domain: String,
+    stop: bool,
 ) -> AppServiceResources {
     let resource_name = format!("{}-{}", coredb_name, appsvc.name.clone());
     let service = appsvc
         .routing
         .as_ref()
         .map(|_| generate_service(appsvc, coredb_name, &resource_name, namespace, oref.clone()));
-    let deployment = generate_deployment(appsvc, coredb_name, &resource_name, namespace, oref);
+    let deployment = generate_deployment(appsvc, coredb_name, &resource_name, namespace, oref, stop);
 
     let host_matcher = format!(
         "Host(`{subdomain}.{domain}`)",
@@ -130,6 +133,7 @@ fn generate_deployment(
     resource_name: &str,
     namespace: &str,
     oref: OwnerReference,
+    stop: bool,
 ) -> Deployment {
     let mut labels: BTreeMap<String, String> = BTreeMap::new();
     labels.insert("app".to_owned(), resource_name.to_string());
@@ -330,7 +334,15 @@ fn generate_deployment(
         spec: Some(pod_spec),
     };
 
+    // 0 replicas on deployment when stopping
+    // 1 replica in all other cases
+    let replicas = match stop {
+        true => 0,
+        false => 1,
+    };
+
     let deployment_spec = DeploymentSpec {
+        replicas: Some(replicas),
         selector: LabelSelector {
             match_labels: Some(labels.clone()),
             ..LabelSelector::default()
@@ -553,7 +565,16 @@ pub async fn reconcile_app_services(cdb: &CoreDB, ctx: Arc<Context>) -> Result<(
     };
     let resources: Vec<AppServiceResources> = appsvcs
         .iter()
-        .map(|appsvc| generate_resource(appsvc, &coredb_name, &ns, oref.clone(), domain.to_owned()))
+        .map(|appsvc| {
+            generate_resource(
+                appsvc,
+                &coredb_name,
+                &ns,
+                oref.clone(),
+                domain.to_owned(),
+                cdb.spec.stop,
+            )
+        })
         .collect();
     let apply_errored = apply_resources(resources.clone(), &client, &ns).await;
 
@@ -592,6 +613,14 @@ pub async fn reconcile_app_services(cdb: &CoreDB, ctx: Arc<Context>) -> Result<(
     }
 
     if has_errors || apply_errored {
+        publish_event(
+            cdb,
+            ctx.clone(),
+            EventType::Warning,
+            "AppServiceError",
+            format!("Error reconciling AppServices for {}, requeuing", coredb_name),
+        )
+        .await;
         return Err(Action::requeue(Duration::from_secs(300)));
     }
     Ok(())
@@ -0,0 +1,332 @@
+use crate::{
+    apis::coredb_types::{BackupCatalogEntry, CoreDB, RecoverabilityWindow},
+    cloudnativepg::{
+        backups::{Backup, BackupStatus},
+        cnpg::is_s3_backup,
+        restore_validation::{get_object, object_store_client, parse_backup_info},
+    },
+    config::Config,
+    postgres_client, Context,
+};
+use chrono::{DateTime, Utc};
+use kube::{runtime::controller::Action, ResourceExt};
+use sqlx::Row;
+use std::{collections::HashMap, sync::Arc};
+use tracing::{debug, instrument, warn};
+
+// Only the most recent backups are listed in the status
+const MAX_BACKUPS_IN_CATALOG: usize = 20;
+
+// Timestamps are formatted by Postgres, so they are parsed the same from psql and the native client
+const LAST_ARCHIVED_WAL_QUERY: &str = r#"SELECT last_archived_wal, to_char(last_archived_time AT TIME ZONE 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS.US"Z"') FROM pg_stat_archiver;"#;
+
+fn parse_timestamp(timestamp: Option<&str>) -> Option<DateTime<Utc>> {
+    timestamp
+        .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+}
+
+/// The most recent backups of the cluster, oldest first
+pub fn backup_catalog(backup_list: &[Backup]) -> Vec<BackupCatalogEntry> {
+    let mut catalog: Vec<BackupCatalogEntry> = backup_list
+        .iter()
+        .map(|backup| {
+            let status = backup.status.clone().unwrap_or_default();
+            BackupCatalogEntry {
+                name: backup.name_any(),
+                backup_id: status.backup_id,
+                phase: status.phase,
+                started_at: parse_timestamp(status.started_at.as_deref()),
+                stopped_at: parse_timestamp(status.stopped_at.as_deref()),
+                begin_lsn: status.begin_lsn,
+                end_lsn: status.end_lsn,
+                size: None,
+            }
+        })
+        .collect();
+    // Backups that haven't started yet are the most recent ones
+    catalog.sort_by_key(|backup| (backup.started_at.is_none(), backup.started_at));
+    if catalog.len() > MAX_BACKUPS_IN_CATALOG {
+        catalog.drain(..catalog.len() - MAX_BACKUPS_IN_CATALOG);
+    }
+    catalog
+}
+
+// Barman writes the backup.info of a base backup to `<destination>/<server>/base/<backup id>/`
+fn backup_info_location(status: &BackupStatus) -> Option<(String, String)> {
+    let path = status.destination_path.as_deref()?.strip_prefix("s3://")?;
+    let (bucket, path) = path.split_once('/').unwrap_or((path, ""));
+    if bucket.is_empty() {
+        return None;
+    }
+    let key = format!(
+        "{}/base/{}/backup.info",
+        status.server_name.as_deref()?,
+        status.backup_id.as_deref()?
+    );
+    let key = match path.trim_matches('/') {
+        "" => key,
+        path => format!("{}/{}", path, key),
+    };
+    Some((bucket.to_string(), key))
+}
+
+/// Fill in the size of the completed backups of the catalog from their backup.info in the object
+/// store. Sizes are only read once, the ones in the status are kept. The operator can only read
+/// the bucket with the access keys of the backups, so the sizes of backups to Google Cloud Storage,
+/// Azure or S3 with the IAM role of the instance are left unset.
+#[instrument(skip(cdb, ctx, backup_list, catalog), fields(cdb_name = %cdb.name_any()))]
+pub async fn backup_sizes(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    backup_list: &[Backup],
+    catalog: &mut [BackupCatalogEntry],
+) {
+    let known_sizes: HashMap<String, i64> = cdb
+        .status
+        .as_ref()
+        .and_then(|status| status.backup_catalog.as_ref())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| Some((entry.name.clone(), entry.size?)))
+        .collect();
+    let mut missing = vec![];
+    for entry in catalog.iter_mut() {
+        entry.size = known_sizes.get(&entry.name).copied();
+        if entry.size.is_none() && entry.phase.as_deref() == Some("completed") {
+            missing.push(entry);
+        }
+    }
+    if missing.is_empty() {
+        return;
+    }
+
+    let backup = &cdb.spec.backup;
+    let s3_credentials = backup.s3_credentials.clone().unwrap_or_default();
+    if !is_s3_backup(backup)
+        || s3_credentials.access_key_id.is_none()
+        || s3_credentials.secret_access_key.is_none()
+    {
+        debug!(
+            "The backups of {} are not read with access keys, leaving their size unset",
+            cdb.name_any()
+        );
+        return;
+    }
+    let client = match object_store_client(cdb, ctx, &s3_credentials, backup.endpoint_url.as_deref()).await {
+        Ok(client) => client,
+        Err(e) => {
+            warn!(
+                "Failed to create the object store client of {}: {}",
+                cdb.name_any(),
+                e
+            );
+            return;
+        }
+    };
+    let locations: HashMap<String, (String, String)> = backup_list
+        .iter()
+        .filter_map(|backup| {
+            let location = backup_info_location(backup.status.as_ref()?)?;
+            Some((backup.name_any(), location))
+        })
+        .collect();
+    for entry in missing {
+        let Some((bucket, key)) = locations.get(&entry.name) else {
+            continue;
+        };
+        // The size is informational, it is read again on the next reconcile
+        match get_object(&client, bucket, key).await {
+            Ok(content) => entry.size = parse_backup_info(&content).size,
+            Err(e) => warn!("Failed to read the size of backup {}: {}", entry.name, e),
+        }
+    }
+}
+
+// The psql output has the column names on the first line, a separator on the second, and the
+// values on the third
+fn parse_last_archived_wal(output: &str) -> Option<(Option<String>, Option<DateTime<Utc>>)> {
+    let row = output.lines().nth(2)?;
+    let mut columns = row.split('|').map(str::trim);
+    let wal = columns.next().filter(|wal| !wal.is_empty()).map(str::to_string);
+    let time = parse_timestamp(columns.next().filter(|time| !time.is_empty()));
+    Some((wal, time))
+}
+
+/// The last WAL archived by the primary, which is the latest point that can be restored
+#[instrument(skip(cdb, ctx), fields(cdb_name = %cdb.name_any()))]
+pub async fn get_last_archived_wal(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    cfg: &Config,
+) -> Result<(Option<String>, Option<DateTime<Utc>>), Action> {
+    if let Some(rows) = postgres_client::try_fetch(
+        cdb,
+        ctx.clone(),
+        cfg,
+        "postgres",
+        LAST_ARCHIVED_WAL_QUERY,
+        |row| {
+            Ok((
+                row.try_get::<Option<String>, _>(0)?,
+                row.try_get::<Option<String>, _>(1)?,
+            ))
+        },
+    )
+    .await
+    {
+        return Ok(rows
+            .into_iter()
+            .next()
+            .map(|(wal, time)| (wal, parse_timestamp(time.as_deref())))
+            .unwrap_or_default());
+    }
+    let psql_out = cdb
+        .psql(LAST_ARCHIVED_WAL_QUERY.to_owned(), "postgres".to_owned(), ctx)
+        .await?;
+    match psql_out.stdout.as_deref().and_then(parse_last_archived_wal) {
+        Some(last_archived_wal) => Ok(last_archived_wal),
+        None => {
+            warn!("Failed to parse the last archived WAL of {}", cdb.name_any());
+            Ok((None, None))
+        }
+    }
+}
+
+/// The range of time that can be restored, from the end of the oldest completed backup to the
+/// last archived WAL. There is nothing to restore until a backup completed.
+pub fn recoverability_window(
+    first_recoverability_time: Option<DateTime<Utc>>,
+    last_archived_wal: Option<String>,
+    last_archived_time: Option<DateTime<Utc>>,
+) -> Option<RecoverabilityWindow> {
+    first_recoverability_time?;
+    Some(RecoverabilityWindow {
+        first_recoverability_time,
+        last_archived_time,
+        last_archived_wal,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cloudnativepg::backups::BackupSpec;
+
+    fn backup(name: &str, started_at: Option<&str>) -> Backup {
+        let mut backup = Backup::new(name, BackupSpec::default());
+        backup.status = Some(BackupStatus {
+            phase: Some("completed".to_string()),
+            backup_id: Some(format!("id-{}", name)),
+            started_at: started_at.map(str::to_string),
+            begin_lsn: Some("0/2000028".to_string()),
+            end_lsn: Some("0/2000100".to_string()),
+            ..BackupStatus::default()
+        });
+        backup
+    }
+
+    #[test]
+    fn test_backup_catalog() {
+        let backups = vec![
+            backup("pending", None),
+            backup("newer", Some("2023-09-27T21:15:42Z")),
+            backup("older", Some("2023-09-26T21:15:42Z")),
+        ];
+        let catalog = backup_catalog(&backups);
+        let names: Vec<&str> = catalog.iter().map(|backup| backup.name.as_str()).collect();
+        assert_eq!(names, vec!["older", "newer", "pending"]);
+        assert_eq!(catalog[0].backup_id.as_deref(), Some("id-older"));
+        assert_eq!(catalog[0].begin_lsn.as_deref(), Some("0/2000028"));
+        assert_eq!(
+            catalog[0].started_at,
+            Some("2023-09-26T21:15:42Z".parse::<DateTime<Utc>>().unwrap())
+        );
+
+        let many: Vec<Backup> = (0..25)
+            .map(|day| {
+                backup(
+                    &format!("backup-{}", day),
+                    Some(&format!("2023-09-{:02}T00:00:00Z", day + 1)),
+                )
+            })
+            .collect();
+        let catalog = backup_catalog(&many);
+        assert_eq!(catalog.len(), MAX_BACKUPS_IN_CATALOG);
+        assert_eq!(catalog[0].name, "backup-5");
+        assert_eq!(catalog.last().unwrap().name, "backup-24");
+    }
+
+    #[test]
+    fn test_backup_info_location() {
+        let mut status = BackupStatus {
+            destination_path: Some("s3://tembo-backup/coredb/".to_string()),
+            server_name: Some("org-test-inst".to_string()),
+            backup_id: Some("20230926T211545".to_string()),
+            ..BackupStatus::default()
+        };
+        assert_eq!(
+            backup_info_location(&status),
+            Some((
+                "tembo-backup".to_string(),
+                "coredb/org-test-inst/base/20230926T211545/backup.info".to_string()
+            ))
+        );
+
+        status.destination_path = Some("s3://tembo-backup".to_string());
+        assert_eq!(
+            backup_info_location(&status),
+            Some((
+                "tembo-backup".to_string(),
+                "org-test-inst/base/20230926T211545/backup.info".to_string()
+            ))
+        );
+
+        status.destination_path = Some("gs://tembo-backup/coredb".to_string());
+        assert_eq!(backup_info_location(&status), None);
+
+        status.destination_path = Some("s3://tembo-backup/coredb".to_string());
+        status.backup_id = None;
+        assert_eq!(backup_info_location(&status), None);
+    }
+
+    #[test]
+    fn test_parse_last_archived_wal() {
+        let output = " last_archived_wal        |           to_char\n\
+                      --------------------------+-----------------------------\n \
+                      000000010000000000000003 | 2023-09-26T21:16:02.123456Z\n\
+                      (1 row)\n";
+        assert_eq!(
+            parse_last_archived_wal(output),
+            Some((
+                Some("000000010000000000000003".to_string()),
+                Some("2023-09-26T21:16:02.123456Z".parse::<DateTime<Utc>>().unwrap())
+            ))
+        );
+
+        // Nothing was archived yet
+        let output =
+            " last_archived_wal | to_char\n-------------------+---------\n                   | \n(1 row)\n";
+        assert_eq!(parse_last_archived_wal(output), Some((None, None)));
+        assert_eq!(parse_last_archived_wal(""), None);
+    }
+
+    #[test]
+    fn test_recoverability_window() {
+        let first = "2023-09-26T21:16:02Z".parse::<DateTime<Utc>>().unwrap();
+        let last = "2023-09-27T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(recoverability_window(None, None, Some(last)), None);
+        assert_eq!(
+            recoverability_window(
+                Some(first),
+                Some("000000010000000000000009".to_string()),
+                Some(last)
+            ),
+            Some(RecoverabilityWindow {
+                first_recoverability_time: Some(first),
+                last_archived_time: Some(last),
+                last_archived_wal: Some("000000010000000000000009".to_string()),
+            })
+        );
+    }
+}
@@ -1,27 +1,43 @@
 use crate::{
     apis::{
-        coredb_types::{CoreDB, S3Credentials},
-        postgres_parameters::MergeError,
+        coredb_types::{
+            AzureCredentials, Backup, BackupCompression, CoreDB, GoogleCredentials, HighAvailability,
+            PodAntiAffinityType, S3Credentials,
+        },
+        postgres_parameters::{ConfigValue, MergeError, PgConfig},
     },
     cloudnativepg::{
         clusters::{
             Cluster, ClusterAffinity, ClusterBackup, ClusterBackupBarmanObjectStore,
-            ClusterBackupBarmanObjectStoreData, ClusterBackupBarmanObjectStoreDataCompression,
-            ClusterBackupBarmanObjectStoreDataEncryption, ClusterBackupBarmanObjectStoreS3Credentials,
+            ClusterBackupBarmanObjectStoreAzureCredentials,
+            ClusterBackupBarmanObjectStoreAzureCredentialsConnectionString,
+            ClusterBackupBarmanObjectStoreAzureCredentialsStorageAccount,
+            ClusterBackupBarmanObjectStoreAzureCredentialsStorageKey,
+            ClusterBackupBarmanObjectStoreAzureCredentialsStorageSasToken,
+            ClusterBackupBarmanObjectStoreData, ClusterBackupBarmanObjectStoreDataEncryption,
+            ClusterBackupBarmanObjectStoreGoogleCredentials,
+            ClusterBackupBarmanObjectStoreGoogleCredentialsApplicationCredentials,
+            ClusterBackupBarmanObjectStoreS3Credentials,
             ClusterBackupBarmanObjectStoreS3CredentialsAccessKeyId,
             ClusterBackupBarmanObjectStoreS3CredentialsRegion,
             ClusterBackupBarmanObjectStoreS3CredentialsSecretAccessKey,
             ClusterBackupBarmanObjectStoreS3CredentialsSessionToken, ClusterBackupBarmanObjectStoreWal,
-            ClusterBackupBarmanObjectStoreWalCompression, ClusterBackupBarmanObjectStoreWalEncryption,
-            ClusterBootstrap, ClusterBootstrapInitdb, ClusterBootstrapRecovery,
-            ClusterBootstrapRecoveryRecoveryTarget, ClusterCertificates, ClusterExternalClusters,
-            ClusterExternalClustersBarmanObjectStore, ClusterExternalClustersBarmanObjectStoreS3Credentials,
+            ClusterBackupBarmanObjectStoreWalEncryption, ClusterBootstrap, ClusterBootstrapInitdb,
+            ClusterBootstrapRecovery, ClusterBootstrapRecoveryRecoveryTarget, ClusterCertificates,
+            ClusterExternalClusters, ClusterExternalClustersBarmanObjectStore,
+            ClusterExternalClustersBarmanObjectStoreAzureCredentials,
+            ClusterExternalClustersBarmanObjectStoreAzureCredentialsConnectionString,
+            ClusterExternalClustersBarmanObjectStoreAzureCredentialsStorageAccount,
+            ClusterExternalClustersBarmanObjectStoreAzureCredentialsStorageKey,
+            ClusterExternalClustersBarmanObjectStoreAzureCredentialsStorageSasToken,
+            ClusterExternalClustersBarmanObjectStoreGoogleCredentials,
+            ClusterExternalClustersBarmanObjectStoreGoogleCredentialsApplicationCredentials,
+            ClusterExternalClustersBarmanObjectStoreS3Credentials,
             ClusterExternalClustersBarmanObjectStoreS3CredentialsAccessKeyId,
             ClusterExternalClustersBarmanObjectStoreS3CredentialsRegion,
             ClusterExternalClustersBarmanObjectStoreS3CredentialsSecretAccessKey,
             ClusterExternalClustersBarmanObjectStoreS3CredentialsSessionToken,
             ClusterExternalClustersBarmanObjectStoreWal,
-            ClusterExternalClustersBarmanObjectStoreWalCompression,
             ClusterExternalClustersBarmanObjectStoreWalEncryption, ClusterExternalClustersPassword,
             ClusterLogLevel, ClusterManaged, ClusterManagedRoles, ClusterManagedRolesEnsure,
             ClusterManagedRolesPasswordSecret, ClusterMonitoring, ClusterMonitoringCustomQueriesConfigMap,
@@ -29,32 +45,54 @@ use crate::{
             ClusterPrimaryUpdateMethod, ClusterPrimaryUpdateStrategy, ClusterReplicationSlots,
             ClusterReplicationSlotsHighAvailability, ClusterResources, ClusterServiceAccountTemplate,
             ClusterServiceAccountTemplateMetadata, ClusterSpec, ClusterStorage, ClusterSuperuserSecret,
+            ClusterWalStorage,
         },
         poolers::{
             Pooler, PoolerCluster, PoolerPgbouncer, PoolerSpec, PoolerTemplate, PoolerTemplateSpec,
             PoolerTemplateSpecContainers, PoolerType,
         },
+        restore_validation::validate_restore,
         scheduledbackups::{
             ScheduledBackup, ScheduledBackupBackupOwnerReference, ScheduledBackupCluster, ScheduledBackupSpec,
         },
     },
+    conditions::{requeue_with_condition, set_condition, CLUSTER_READY, POOLER_READY, STORAGE_READY},
     config::Config,
     defaults::{default_image, default_llm_image},
     errors::ValueError,
+    extensions::uninstall::libraries_to_unload,
     is_postgres_ready, patch_cdb_status_merge,
     psql::PsqlOutput,
-    trunk::extensions_that_require_load,
-    Context, RESTARTED_AT,
+    publish_event,
+    stacks::types::StackType,
+    trunk::{extension_configs, extensions_that_require_load},
+    Context, HIBERNATION, RESTARTED_AT,
 };
 use chrono::{DateTime, NaiveDateTime, Offset};
-use k8s_openapi::{api::core::v1::Pod, apimachinery::pkg::apis::meta::v1::ObjectMeta};
+use k8s_openapi::{
+    api::{
+        apps::v1::Deployment,
+        autoscaling::v2::{
+            CrossVersionObjectReference, HorizontalPodAutoscaler, HorizontalPodAutoscalerSpec, MetricSpec,
+            MetricTarget, ResourceMetricSource,
+        },
+        core::v1::Pod,
+        policy::v1::{PodDisruptionBudget, PodDisruptionBudgetSpec},
+    },
+    apimachinery::pkg::{
+        api::resource::Quantity,
+        apis::meta::v1::{Condition as StatusCondition, LabelSelector, ObjectMeta},
+        util::intstr::IntOrString,
+    },
+};
 use kube::{
     api::{DeleteParams, Patch, PatchParams},
-    runtime::{controller::Action, wait::Condition},
+    runtime::{controller::Action, events::EventType, wait::Condition},
     Api, Resource, ResourceExt,
 };
+use serde::{de::DeserializeOwned, Serialize};
 use serde_json::json;
-use std::{collections::BTreeMap, sync::Arc};
+use std::{collections::BTreeMap, str::FromStr, sync::Arc};
 use tokio::time::Duration;
 use tracing::{debug, error, info, instrument, warn};
 
@@ -63,8 +101,75 @@ pub struct PostgresConfig {
     pub shared_preload_libraries: Option<Vec<String>>,
 }
 
+// S3 is used unless the credentials of another provider are set
+pub(crate) fn is_s3_backup(backup: &Backup) -> bool {
+    backup.google_credentials.is_none() && backup.azure_credentials.is_none()
+}
+
+// Settings of the backups that aren't set in the spec default to the ones of the stack
+#[derive(Debug, PartialEq)]
+struct BackupTuning {
+    data_compression: BackupCompression,
+    wal_compression: BackupCompression,
+    jobs: Option<i32>,
+    wal_max_parallel: i64,
+}
+
+impl Default for BackupTuning {
+    fn default() -> Self {
+        Self {
+            data_compression: BackupCompression::Snappy,
+            wal_compression: BackupCompression::Snappy,
+            jobs: None,
+            wal_max_parallel: 5,
+        }
+    }
+}
+
+fn stack_backup_tuning(cdb: &CoreDB) -> BackupTuning {
+    let default = BackupTuning::default();
+    match cdb
+        .spec
+        .stack
+        .as_ref()
+        .and_then(|s| StackType::from_str(&s.name).ok())
+    {
+        // Analytical stacks have large databases and write a lot of WAL with bulk loads, so base
+        // backups are uploaded with more jobs and more WAL files are archived at once
+        Some(StackType::DataWarehouse) | Some(StackType::OLAP) => BackupTuning {
+            jobs: Some(4),
+            wal_max_parallel: 8,
+            ..default
+        },
+        // Queues write a lot of WAL, but the databases stay small
+        Some(StackType::MessageQueue) => BackupTuning {
+            wal_max_parallel: 8,
+            ..default
+        },
+        _ => default,
+    }
+}
+
+fn backup_tuning(cdb: &CoreDB) -> BackupTuning {
+    let backup = &cdb.spec.backup;
+    let stack_tuning = stack_backup_tuning(cdb);
+    BackupTuning {
+        data_compression: backup.data_compression.unwrap_or(stack_tuning.data_compression),
+        wal_compression: backup.wal_compression.unwrap_or(stack_tuning.wal_compression),
+        jobs: backup.jobs.or(stack_tuning.jobs),
+        wal_max_parallel: backup.wal_max_parallel.unwrap_or(stack_tuning.wal_max_parallel),
+    }
+}
+
 fn create_cluster_backup_barman_data(cdb: &CoreDB) -> Option<ClusterBackupBarmanObjectStoreData> {
-    let encryption = match &cdb.spec.backup.encryption {
+    // Server-side encryption is only supported by S3
+    let encryption = match cdb
+        .spec
+        .backup
+        .encryption
+        .as_ref()
+        .filter(|_| is_s3_backup(&cdb.spec.backup))
+    {
         Some(encryption) => match encryption.as_str() {
             "AES256" => Some(ClusterBackupBarmanObjectStoreDataEncryption::Aes256),
             "aws:kms" => Some(ClusterBackupBarmanObjectStoreDataEncryption::AwsKms),
@@ -73,16 +178,23 @@ fn create_cluster_backup_barman_data(cdb: &CoreDB) -> Option<ClusterBackupBarman
         _ => None,
     };
 
+    let tuning = backup_tuning(cdb);
     Some(ClusterBackupBarmanObjectStoreData {
-        compression: Some(ClusterBackupBarmanObjectStoreDataCompression::Snappy),
+        compression: Some(tuning.data_compression.into()),
         encryption,
         immediate_checkpoint: Some(true),
-        ..ClusterBackupBarmanObjectStoreData::default()
+        jobs: tuning.jobs,
     })
 }
 
 fn create_cluster_backup_barman_wal(cdb: &CoreDB) -> Option<ClusterBackupBarmanObjectStoreWal> {
-    let encryption = match &cdb.spec.backup.encryption {
+    let encryption = match cdb
+        .spec
+        .backup
+        .encryption
+        .as_ref()
+        .filter(|_| is_s3_backup(&cdb.spec.backup))
+    {
         Some(encryption) => match encryption.as_str() {
             "AES256" => Some(ClusterBackupBarmanObjectStoreWalEncryption::Aes256),
             "aws:kms" => Some(ClusterBackupBarmanObjectStoreWalEncryption::AwsKms),
@@ -91,15 +203,26 @@ fn create_cluster_backup_barman_wal(cdb: &CoreDB) -> Option<ClusterBackupBarmanO
         _ => None,
     };
 
-    if encryption.is_some() {
-        Some(ClusterBackupBarmanObjectStoreWal {
-            compression: Some(ClusterBackupBarmanObjectStoreWalCompression::Snappy),
-            encryption,
-            max_parallel: Some(5),
-        })
-    } else {
-        None
+    // Without encryption or WAL settings in the spec or the stack, the WAL is archived with the
+    // defaults of CNPG, uncompressed
+    let backup = &cdb.spec.backup;
+    let stack_tuning = stack_backup_tuning(cdb);
+    let default = BackupTuning::default();
+    if encryption.is_none()
+        && backup.wal_compression.is_none()
+        && backup.wal_max_parallel.is_none()
+        && stack_tuning.wal_compression == default.wal_compression
+        && stack_tuning.wal_max_parallel == default.wal_max_parallel
+    {
+        return None;
     }
+
+    let tuning = backup_tuning(cdb);
+    Some(ClusterBackupBarmanObjectStoreWal {
+        compression: Some(tuning.wal_compression.into()),
+        encryption,
+        max_parallel: Some(tuning.wal_max_parallel),
+    })
 }
 
 fn create_cluster_backup_barman_object_store(
@@ -112,7 +235,19 @@ fn create_cluster_backup_barman_object_store(
         data: create_cluster_backup_barman_data(cdb),
         endpoint_url: Some(endpoint_url.to_string()),
         destination_path: backup_path.to_string(),
-        s3_credentials: Some(s3_credentials.clone()),
+        s3_credentials: is_s3_backup(&cdb.spec.backup).then(|| s3_credentials.clone()),
+        google_credentials: cdb
+            .spec
+            .backup
+            .google_credentials
+            .as_ref()
+            .map(generate_google_backup_credentials),
+        azure_credentials: cdb
+            .spec
+            .backup
+            .azure_credentials
+            .as_ref()
+            .map(generate_azure_backup_credentials),
         wal: create_cluster_backup_barman_wal(cdb),
         ..ClusterBackupBarmanObjectStore::default()
     }
@@ -195,6 +330,19 @@ pub fn cnpg_backup_configuration(
         return (None, None);
     }
 
+    // The EKS role ARN only applies to S3, the other providers use their own credentials or the
+    // workload identity of the service account
+    if !is_s3_backup(&cdb.spec.backup) {
+        let endpoint_url = cdb.spec.backup.endpoint_url.as_deref().unwrap_or_default();
+        let cluster_backup = create_cluster_backup(
+            cdb,
+            endpoint_url,
+            &backup_path.unwrap(),
+            &ClusterBackupBarmanObjectStoreS3Credentials::default(),
+        );
+        return (cluster_backup, workload_identity_service_account_template(cdb));
+    }
+
     let should_set_service_account_template = (cdb.spec.backup.endpoint_url.is_none()
         && cdb.spec.backup.s3_credentials.is_none())
         || (cdb
@@ -280,7 +428,7 @@ pub fn cnpg_backup_configuration(
 // YYYY-MM-DDTHH24:MI:SS±TZH:TZM     (RFC3339)
 // YYYY-MM-DDTHH24:MI:SSS±TZH:TZM	   (RFC3339Micro)
 // YYYY-MM-DDTHH24:MI:SS             (modified RFC3339)
-fn parse_target_time(target_time: Option<&str>) -> Result<Option<String>, ValueError> {
+pub(crate) fn parse_target_time(target_time: Option<&str>) -> Result<Option<String>, ValueError> {
     if let Some(time_str) = target_time {
         // Try to parse the target_time with the following formats in order
         // 1. YYYY-MM-DD HH24:MI:SS
@@ -392,6 +540,8 @@ pub fn cnpg_cluster_bootstrap_from_cdb(
     let superuser_secret_name = format!("{}-connection", cluster_name);
 
     let coredb_cluster = if let Some(restore) = &cdb.spec.restore {
+        let restore_from_s3 = restore.google_credentials.is_none() && restore.azure_credentials.is_none();
+        let tuning = backup_tuning(cdb);
         let s3_credentials = generate_s3_restore_credentials(restore.s3_credentials.as_ref());
         // Find destination_path from Backup to generate the restore destination path
         let restore_destination_path = match &cdb.spec.backup.destinationPath {
@@ -403,11 +553,20 @@ pub fn cnpg_cluster_bootstrap_from_cdb(
             barman_object_store: Some(ClusterExternalClustersBarmanObjectStore {
                 destination_path: format!("{}/{}", restore_destination_path, restore.server_name),
                 endpoint_url: restore.endpoint_url.clone(),
-                s3_credentials: Some(s3_credentials),
+                s3_credentials: restore_from_s3.then_some(s3_credentials),
+                google_credentials: restore
+                    .google_credentials
+                    .as_ref()
+                    .map(generate_google_restore_credentials),
+                azure_credentials: restore
+                    .azure_credentials
+                    .as_ref()
+                    .map(generate_azure_restore_credentials),
                 wal: Some(ClusterExternalClustersBarmanObjectStoreWal {
-                    max_parallel: Some(5),
-                    encryption: Some(ClusterExternalClustersBarmanObjectStoreWalEncryption::Aes256),
-                    compression: Some(ClusterExternalClustersBarmanObjectStoreWalCompression::Snappy),
+                    max_parallel: Some(tuning.wal_max_parallel),
+                    encryption: restore_from_s3
+                        .then_some(ClusterExternalClustersBarmanObjectStoreWalEncryption::Aes256),
+                    compression: Some(tuning.wal_compression.into()),
                     ..ClusterExternalClustersBarmanObjectStoreWal::default()
                 }),
                 server_name: Some(restore.server_name.clone()),
@@ -445,8 +604,11 @@ pub fn cnpg_cluster_bootstrap_from_cdb(
 fn cnpg_postgres_config(
     cdb: &CoreDB,
     requires_load: BTreeMap<String, String>,
+    extension_configs: BTreeMap<String, Vec<PgConfig>>,
 ) -> Result<PostgresConfig, MergeError> {
-    match cdb.spec.get_pg_configs(requires_load) {
+    // Libraries of trunk installs that are being uninstalled are not loaded anymore
+    let libraries_to_unload = libraries_to_unload(cdb, &requires_load);
+    match cdb.spec.get_pg_configs(requires_load, extension_configs) {
         Ok(Some(pg_configs)) => {
             let mut postgres_parameters: BTreeMap<String, String> = BTreeMap::new();
             let mut shared_preload_libraries: Vec<String> = Vec::new();
@@ -454,7 +616,16 @@ fn cnpg_postgres_config(
             for pg_config in pg_configs {
                 match &pg_config.name[..] {
                     "shared_preload_libraries" => {
-                        shared_preload_libraries.push(pg_config.value.to_string());
+                        let value = match pg_config.value {
+                            ConfigValue::Multiple(libraries) => ConfigValue::Multiple(
+                                libraries.difference(&libraries_to_unload).cloned().collect(),
+                            ),
+                            value => value,
+                        };
+                        let value = value.to_string();
+                        if !value.is_empty() && !libraries_to_unload.contains(&value) {
+                            shared_preload_libraries.push(value);
+                        }
                     }
                     _ => {
                         postgres_parameters.insert(pg_config.name.clone(), pg_config.value.to_string());
@@ -495,13 +666,145 @@ fn cnpg_cluster_storage(cdb: &CoreDB) -> Option<ClusterStorage> {
     Some(ClusterStorage {
         resize_in_use_volumes: Some(true),
         size: Some(storage),
-        // TODO: pass storage class from cdb
-        // storage_class: Some("gp3-enc".to_string()),
-        storage_class: None,
+        storage_class: cdb.spec.storage_class.clone(),
         ..ClusterStorage::default()
     })
 }
 
+// A separate WAL volume is only created when it is requested in the spec
+fn cnpg_cluster_wal_storage(cdb: &CoreDB) -> Option<ClusterWalStorage> {
+    cdb.spec
+        .wal_storage
+        .as_ref()
+        .map(|wal_storage| ClusterWalStorage {
+            resize_in_use_volumes: Some(true),
+            size: Some(wal_storage.size.0.clone()),
+            storage_class: wal_storage
+                .storage_class
+                .clone()
+                .or_else(|| cdb.spec.storage_class.clone()),
+            ..ClusterWalStorage::default()
+        })
+}
+
+fn cnpg_cluster_affinity(high_availability: &HighAvailability) -> ClusterAffinity {
+    let pod_anti_affinity_type = match high_availability.pod_anti_affinity_type {
+        PodAntiAffinityType::Preferred => "preferred",
+        PodAntiAffinityType::Required => "required",
+    };
+    ClusterAffinity {
+        pod_anti_affinity_type: Some(pod_anti_affinity_type.to_string()),
+        topology_key: Some(high_availability.topology_key.clone()),
+        ..ClusterAffinity::default()
+    }
+}
+
+// With the zone election constraint, a replica in the same zone as the primary is never elected
+// as a synchronous replica, so a committed transaction survives the loss of a zone
+fn cnpg_sync_replica_election_constraint(
+    high_availability: &HighAvailability,
+) -> ClusterPostgresqlSyncReplicaElectionConstraint {
+    if high_availability.zone_election_constraint {
+        ClusterPostgresqlSyncReplicaElectionConstraint {
+            enabled: true,
+            node_labels_anti_affinity: Some(vec![high_availability.topology_key.clone()]),
+        }
+    } else {
+        ClusterPostgresqlSyncReplicaElectionConstraint {
+            enabled: false,
+            ..ClusterPostgresqlSyncReplicaElectionConstraint::default()
+        }
+    }
+}
+
+// Parses a Kubernetes quantity like 10Gi or 500M into a number of bytes
+pub(crate) fn parse_storage_size(quantity: &Quantity) -> Result<f64, ValueError> {
+    let value = quantity.0.trim();
+    let suffixes: [(&str, f64); 13] = [
+        ("Ki", 1024_f64),
+        ("Mi", 1024_f64.powi(2)),
+        ("Gi", 1024_f64.powi(3)),
+        ("Ti", 1024_f64.powi(4)),
+        ("Pi", 1024_f64.powi(5)),
+        ("Ei", 1024_f64.powi(6)),
+        ("m", 1e-3),
+        ("k", 1e3),
+        ("M", 1e6),
+        ("G", 1e9),
+        ("T", 1e12),
+        ("P", 1e15),
+        ("E", 1e18),
+    ];
+    let (number, multiplier) = suffixes
+        .iter()
+        .find_map(|(suffix, multiplier)| value.strip_suffix(suffix).map(|number| (number, *multiplier)))
+        .unwrap_or((value, 1_f64));
+    if number.is_empty() {
+        return Err(ValueError::Invalid(format!(
+            "'{}' is not a valid quantity",
+            value
+        )));
+    }
+    Ok(number.parse::<f64>()? * multiplier)
+}
+
+// Volumes can be expanded online, but never shrunk. For every volume the new cluster spec would
+// shrink, keep the size of the current cluster and return a message explaining why.
+fn keep_current_volume_sizes(cluster: &mut Cluster, current_cluster: &Cluster) -> Vec<String> {
+    let mut rejected = Vec::new();
+
+    let is_shrink = |requested: Option<&String>, current: Option<&String>| match (requested, current) {
+        (Some(requested), Some(current)) => {
+            match (
+                parse_storage_size(&Quantity(requested.clone())),
+                parse_storage_size(&Quantity(current.clone())),
+            ) {
+                (Ok(requested), Ok(current)) => requested < current,
+                _ => false,
+            }
+        }
+        _ => false,
+    };
+
+    if let (Some(storage), Some(current_storage)) = (
+        cluster.spec.storage.as_mut(),
+        current_cluster.spec.storage.as_ref(),
+    ) {
+        if is_shrink(storage.size.as_ref(), current_storage.size.as_ref()) {
+            rejected.push(format!(
+                "storage can not be shrunk from {} to {}",
+                current_storage.size.clone().unwrap_or_default(),
+                storage.size.clone().unwrap_or_default()
+            ));
+            storage.size = current_storage.size.clone();
+        }
+    }
+
+    match (
+        cluster.spec.wal_storage.as_mut(),
+        current_cluster.spec.wal_storage.as_ref(),
+    ) {
+        (Some(wal_storage), Some(current_wal_storage))
+            if is_shrink(wal_storage.size.as_ref(), current_wal_storage.size.as_ref()) =>
+        {
+            rejected.push(format!(
+                "walStorage can not be shrunk from {} to {}",
+                current_wal_storage.size.clone().unwrap_or_default(),
+                wal_storage.size.clone().unwrap_or_default()
+            ));
+            wal_storage.size = current_wal_storage.size.clone();
+        }
+        // Removing the WAL volume would lose the WAL, keep it
+        (None, Some(current_wal_storage)) => {
+            rejected.push("walStorage can not be removed from an existing instance".to_string());
+            cluster.spec.wal_storage = Some(current_wal_storage.clone());
+        }
+        _ => {}
+    }
+
+    rejected
+}
+
 // Check replica count to enable HA
 fn cnpg_high_availability(cdb: &CoreDB) -> Option<ClusterReplicationSlots> {
     if cdb.spec.replicas > 1 {
@@ -537,6 +840,7 @@ pub fn cnpg_cluster_from_cdb(
     cdb: &CoreDB,
     fenced_pods: Option<Vec<String>>,
     requires_load: BTreeMap<String, String>,
+    extension_configs: BTreeMap<String, Vec<PgConfig>>,
 ) -> Cluster {
     let cfg = Config::default();
     let name = cdb.name_any();
@@ -546,12 +850,14 @@ pub fn cnpg_cluster_from_cdb(
     let (bootstrap, external_clusters, superuser_secret) = cnpg_cluster_bootstrap_from_cdb(cdb);
     let (backup, service_account_template) = cnpg_backup_configuration(cdb, &cfg);
     let storage = cnpg_cluster_storage(cdb);
+    let wal_storage = cnpg_cluster_wal_storage(cdb);
     let replication = cnpg_high_availability(cdb);
+    let high_availability = cdb.spec.high_availability.clone().unwrap_or_default();
 
     let PostgresConfig {
         postgres_parameters,
         shared_preload_libraries,
-    } = match cnpg_postgres_config(cdb, requires_load) {
+    } = match cnpg_postgres_config(cdb, requires_load, extension_configs) {
         Ok(config) => config,
         Err(e) => {
             error!("Error generating postgres parameters: {}", e);
@@ -562,6 +868,14 @@ pub fn cnpg_cluster_from_cdb(
         }
     };
 
+    // Hibernation deletes the pods of the cluster but keeps the PVCs, so the instance
+    // comes back with its data when the annotation is turned off again
+    let hibernation = match cdb.spec.stop {
+        true => "on",
+        false => "off",
+    };
+    annotations.insert(HIBERNATION.to_string(), hibernation.to_string());
+
     // Format fenced pods annotation if we have any
     if let Some(fenced_pods) = fenced_pods {
         let fenced_instances = format!("{:?}", fenced_pods);
@@ -591,11 +905,7 @@ pub fn cnpg_cluster_from_cdb(
             ..ObjectMeta::default()
         },
         spec: ClusterSpec {
-            affinity: Some(ClusterAffinity {
-                pod_anti_affinity_type: Some("preferred".to_string()),
-                topology_key: Some("topology.kubernetes.io/zone".to_string()),
-                ..ClusterAffinity::default()
-            }),
+            affinity: Some(cnpg_cluster_affinity(&high_availability)),
             backup,
             service_account_template,
             bootstrap,
@@ -607,9 +917,9 @@ pub fn cnpg_cluster_from_cdb(
             image_name: Some(image),
             instances: cdb.spec.replicas as i64,
             log_level: Some(ClusterLogLevel::Info),
-            managed: cluster_managed(&name),
-            max_sync_replicas: Some(0),
-            min_sync_replicas: Some(0),
+            managed: cluster_managed(cdb),
+            max_sync_replicas: Some(high_availability.sync_replicas as i64),
+            min_sync_replicas: Some(high_availability.sync_replicas as i64),
             monitoring: Some(ClusterMonitoring {
                 custom_queries_config_map: Some(vec![ClusterMonitoringCustomQueriesConfigMap {
                     key: "queries".to_string(),
@@ -624,10 +934,9 @@ pub fn cnpg_cluster_from_cdb(
             postgresql: Some(ClusterPostgresql {
                 ldap: None,
                 parameters: postgres_parameters,
-                sync_replica_election_constraint: Some(ClusterPostgresqlSyncReplicaElectionConstraint {
-                    enabled: false,
-                    ..ClusterPostgresqlSyncReplicaElectionConstraint::default()
-                }),
+                sync_replica_election_constraint: Some(cnpg_sync_replica_election_constraint(
+                    &high_availability,
+                )),
                 shared_preload_libraries,
                 pg_hba: None,
                 ..ClusterPostgresql::default()
@@ -645,6 +954,7 @@ pub fn cnpg_cluster_from_cdb(
             // The time in seconds that is allowed for a PostgreSQL instance to gracefully shutdown
             stop_delay: Some(30),
             storage,
+            wal_storage,
             // The time in seconds that is allowed for a primary PostgreSQL instance
             // to gracefully shutdown during a switchover
             switchover_delay: Some(60),
@@ -662,31 +972,46 @@ pub fn cnpg_cluster_from_cdb(
     }
 }
 
-fn cluster_managed(name: &str) -> Option<ClusterManaged> {
-    Some(ClusterManaged {
-        roles: Some(vec![
-            ClusterManagedRoles {
-                name: "readonly".to_string(),
-                ensure: Some(ClusterManagedRolesEnsure::Present),
-                login: Some(true),
-                password_secret: Some(ClusterManagedRolesPasswordSecret {
-                    name: format!("{}-ro", name).to_string(),
-                }),
-                in_roles: Some(vec!["pg_read_all_data".to_string()]),
-                ..ClusterManagedRoles::default()
-            },
-            ClusterManagedRoles {
-                name: "postgres_exporter".to_string(),
-                ensure: Some(ClusterManagedRolesEnsure::Present),
-                login: Some(true),
-                password_secret: Some(ClusterManagedRolesPasswordSecret {
-                    name: format!("{}-exporter", name).to_string(),
-                }),
-                in_roles: Some(vec!["pg_read_all_stats".to_string(), "pg_monitor".to_string()]),
-                ..ClusterManagedRoles::default()
-            },
-        ]),
-    })
+fn cluster_managed(cdb: &CoreDB) -> Option<ClusterManaged> {
+    let name = cdb.name_any();
+    let mut roles = vec![
+        ClusterManagedRoles {
+            name: "readonly".to_string(),
+            ensure: Some(ClusterManagedRolesEnsure::Present),
+            login: Some(true),
+            password_secret: Some(ClusterManagedRolesPasswordSecret {
+                name: format!("{}-ro", name).to_string(),
+            }),
+            in_roles: Some(vec!["pg_read_all_data".to_string()]),
+            ..ClusterManagedRoles::default()
+        },
+        ClusterManagedRoles {
+            name: "postgres_exporter".to_string(),
+            ensure: Some(ClusterManagedRolesEnsure::Present),
+            login: Some(true),
+            password_secret: Some(ClusterManagedRolesPasswordSecret {
+                name: format!("{}-exporter", name).to_string(),
+            }),
+            in_roles: Some(vec!["pg_read_all_stats".to_string(), "pg_monitor".to_string()]),
+            ..ClusterManagedRoles::default()
+        },
+    ];
+    // Roles requested in the spec
+    roles.extend(cdb.spec.roles.iter().map(|role| {
+        ClusterManagedRoles {
+            name: role.name.clone(),
+            ensure: Some(ClusterManagedRolesEnsure::Present),
+            login: Some(role.login),
+            in_roles: Some(role.in_roles.clone()),
+            connection_limit: Some(role.connection_limit),
+            password_secret: role
+                .password_secret
+                .clone()
+                .map(|name| ClusterManagedRolesPasswordSecret { name }),
+            ..ClusterManagedRoles::default()
+        }
+    }));
+    Some(ClusterManaged { roles: Some(roles) })
 }
 
 // This is a synchronous function that takes the latest_generated_node and diff_instances
@@ -844,13 +1169,61 @@ fn update_restarted_at(cdb: &CoreDB, maybe_cluster: Option<&Cluster>, new_spec:
 }
 
 #[instrument(skip(cdb, ctx) fields(trace_id, instance_name = %cdb.name_any()))]
-pub async fn reconcile_cnpg(cdb: &CoreDB, ctx: Arc<Context>) -> Result<(), Action> {
-    let pods_to_fence = pods_to_fence(cdb, ctx.clone()).await?;
-    let requires_load =
-        extensions_that_require_load(ctx.client.clone(), &cdb.metadata.namespace.clone().unwrap()).await?;
+pub async fn reconcile_cnpg(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    cfg: &Config,
+    conditions: &mut Vec<StatusCondition>,
+) -> Result<(), Action> {
+    let pods_to_fence = match pods_to_fence(cdb, ctx.clone()).await {
+        Ok(pods_to_fence) => pods_to_fence,
+        Err(action) => {
+            return Err(requeue_with_condition(
+                cdb,
+                ctx.clone(),
+                conditions,
+                CLUSTER_READY,
+                "ClusterNotReady",
+                "Error determining the pods to fence",
+                action,
+            )
+            .await);
+        }
+    };
+    let namespace = cdb.metadata.namespace.clone().unwrap();
+    let requires_load = match extensions_that_require_load(ctx.client.clone(), &namespace).await {
+        Ok(requires_load) => requires_load,
+        Err(action) => {
+            return Err(requeue_with_condition(
+                cdb,
+                ctx.clone(),
+                conditions,
+                CLUSTER_READY,
+                "ClusterNotReady",
+                "Error reading the extensions that require shared_preload_libraries from the trunk metadata",
+                action,
+            )
+            .await);
+        }
+    };
+    let extension_configs = match extension_configs(ctx.client.clone(), &namespace).await {
+        Ok(extension_configs) => extension_configs,
+        Err(action) => {
+            return Err(requeue_with_condition(
+                cdb,
+                ctx.clone(),
+                conditions,
+                CLUSTER_READY,
+                "ClusterNotReady",
+                "Error reading the extension configurations from the trunk metadata",
+                action,
+            )
+            .await);
+        }
+    };
 
     debug!("Generating CNPG spec");
-    let mut cluster = cnpg_cluster_from_cdb(cdb, Some(pods_to_fence), requires_load);
+    let mut cluster = cnpg_cluster_from_cdb(cdb, Some(pods_to_fence), requires_load, extension_configs);
 
     debug!("Getting namespace of cluster");
     let namespace = cluster
@@ -868,9 +1241,21 @@ pub async fn reconcile_cnpg(cdb: &CoreDB, ctx: Arc<Context>) -> Result<(), Actio
     let cluster_api: Api<Cluster> = Api::namespaced(ctx.client.clone(), namespace.as_str());
     let maybe_cluster = cluster_api.get(&name).await;
 
+    // A Cluster is only bootstrapped from a backup once, when it is created
+    let cluster_not_found = matches!(&maybe_cluster, Err(kube::Error::Api(e)) if e.code == 404);
+    if cluster_not_found && cfg.enable_restore_validation {
+        // Boxed, the futures of the S3 client are too deep to be nested in the reconcile
+        Box::pin(validate_restore(cdb, ctx.clone(), conditions)).await?;
+    }
+
     let restart_annotation_updated = update_restarted_at(cdb, maybe_cluster.as_ref().ok(), &mut cluster);
 
-    let mut _restart_required = false;
+    let rejected_volume_changes = match maybe_cluster.as_ref() {
+        Ok(current_cluster) => keep_current_volume_sizes(&mut cluster, current_cluster),
+        Err(_) => vec![],
+    };
+
+    let mut restart_required = false;
 
     match cluster
         .spec
@@ -885,6 +1270,15 @@ pub async fn reconcile_cnpg(cdb: &CoreDB, ctx: Arc<Context>) -> Result<(), Actio
         Some(new_libs) => {
             debug!("We are setting shared_preload_libraries, so we have to check if the files are already installed");
             match maybe_cluster {
+                // There are no pods to check for installed files while hibernated, keep the current libraries
+                Ok(current_cluster) if cdb.spec.stop => {
+                    if let Some(postgresql) = cluster.spec.postgresql.as_mut() {
+                        postgresql.shared_preload_libraries = current_cluster
+                            .spec
+                            .postgresql
+                            .and_then(|p| p.shared_preload_libraries);
+                    }
+                }
                 Ok(current_cluster) => {
                     let current_shared_preload_libraries = match current_cluster
                         .spec
@@ -903,24 +1297,60 @@ pub async fn reconcile_cnpg(cdb: &CoreDB, ctx: Arc<Context>) -> Result<(), Actio
                     if current_shared_preload_libraries != new_libs {
                         let mut libs_that_are_installed: Vec<String> = vec![];
                         // If we can't find the existing primary pod, returns a requeue
-                        let primary_pod_cnpg = cdb.primary_pod_cnpg_ready_or_not(ctx.client.clone()).await?;
+                        let primary_pod_cnpg =
+                            match cdb.primary_pod_cnpg_ready_or_not(ctx.client.clone()).await {
+                                Ok(primary_pod_cnpg) => primary_pod_cnpg,
+                                Err(action) => {
+                                    return Err(requeue_with_condition(
+                                        cdb,
+                                        ctx.clone(),
+                                        conditions,
+                                        CLUSTER_READY,
+                                        "ClusterNotReady",
+                                        "Waiting for the primary pod to check the installed libraries",
+                                        action,
+                                    )
+                                    .await);
+                                }
+                            };
                         // Check if the file is already installed
                         let command = vec![
                             "/bin/sh".to_string(),
                             "-c".to_string(),
                             "ls $(pg_config --pkglibdir)".to_string(),
                         ];
-                        let result = cdb
+                        let result = match cdb
                             .exec(primary_pod_cnpg.name_any(), ctx.client.clone(), &command)
                             .await
-                            .map_err(|e| {
+                        {
+                            Ok(result) => result,
+                            Err(e) => {
                                 error!("Error checking for presence of extension files: {:?}", e);
-                                Action::requeue(Duration::from_secs(30))
-                            })?;
+                                return Err(requeue_with_condition(
+                                    cdb,
+                                    ctx.clone(),
+                                    conditions,
+                                    CLUSTER_READY,
+                                    "ClusterNotReady",
+                                    &format!("Error checking for presence of extension files: {}", e),
+                                    Action::requeue(Duration::from_secs(30)),
+                                )
+                                .await);
+                            }
+                        };
                         let available_libs = match result.stdout {
                             None => {
                                 error!("Error checking for presence of extension files");
-                                return Err(Action::requeue(Duration::from_secs(30)));
+                                return Err(requeue_with_condition(
+                                    cdb,
+                                    ctx.clone(),
+                                    conditions,
+                                    CLUSTER_READY,
+                                    "ClusterNotReady",
+                                    "Error checking for presence of extension files: no output",
+                                    Action::requeue(Duration::from_secs(30)),
+                                )
+                                .await);
                             }
                             Some(output) => {
                                 output.split('\n').map(|s| s.to_string()).collect::<Vec<String>>()
@@ -933,7 +1363,7 @@ pub async fn reconcile_cnpg(cdb: &CoreDB, ctx: Arc<Context>) -> Result<(), Actio
                                     info!("Changing shared_preload_libraries on {}, found {} is installed, so including it", &name, &new_lib);
                                     libs_that_are_installed.push(new_lib.clone());
                                     if !current_shared_preload_libraries.contains(&new_lib) {
-                                        _restart_required = true;
+                                        restart_required = true;
                                     }
                                 } else {
                                     info!("Changing shared_preload_libraries on {}, found {} is NOT installed, so dropping it", &name, &new_lib);
@@ -960,16 +1390,73 @@ pub async fn reconcile_cnpg(cdb: &CoreDB, ctx: Arc<Context>) -> Result<(), Actio
     }
 
     let ps = PatchParams::apply("cntrlr");
-    let _o = cluster_api
-        .patch(&name, &ps, &Patch::Apply(&cluster))
-        .await
-        .map_err(|e| {
-            error!("Error patching cluster: {}", e);
-            Action::requeue(Duration::from_secs(300))
-        })?;
+    if let Err(e) = cluster_api.patch(&name, &ps, &Patch::Apply(&cluster)).await {
+        error!("Error patching cluster: {}", e);
+        publish_event(
+            cdb,
+            ctx.clone(),
+            EventType::Warning,
+            "ClusterPatchError",
+            format!("Error patching CNPG cluster {}: {}", name, e),
+        )
+        .await;
+        return Err(requeue_with_condition(
+            cdb,
+            ctx.clone(),
+            conditions,
+            CLUSTER_READY,
+            "ClusterPatchError",
+            &format!("Error patching CNPG cluster {}: {}", name, e),
+            Action::requeue(Duration::from_secs(300)),
+        )
+        .await);
+    }
+
+    // The rest of the cluster is still reconciled when a volume change is rejected, the condition
+    // stays False until the spec asks for a supported size again
+    if rejected_volume_changes.is_empty() {
+        set_condition(
+            conditions,
+            STORAGE_READY,
+            true,
+            "StorageReady",
+            "",
+            cdb.metadata.generation,
+        );
+    } else {
+        let message = rejected_volume_changes.join("; ");
+        warn!("Rejected volume changes for {}: {}", name, message);
+        set_condition(
+            conditions,
+            STORAGE_READY,
+            false,
+            "StorageShrinkRejected",
+            &message,
+            cdb.metadata.generation,
+        );
+    }
+
+    if restart_required {
+        publish_event(
+            cdb,
+            ctx.clone(),
+            EventType::Normal,
+            "RestartTriggered",
+            "Restarting Postgres to load new shared_preload_libraries".to_string(),
+        )
+        .await;
+    }
 
     // If we updated the restartedAt annotation, set `status.running` in CoreDB to false
     if restart_annotation_updated {
+        publish_event(
+            cdb,
+            ctx.clone(),
+            EventType::Normal,
+            "RestartTriggered",
+            format!("Restart requested through the {} annotation", RESTARTED_AT),
+        )
+        .await;
         let cdb_cluster: Api<CoreDB> = Api::namespaced(ctx.client.clone(), &namespace);
         let cluster_name = &name;
 
@@ -987,112 +1474,400 @@ pub async fn reconcile_cnpg(cdb: &CoreDB, ctx: Arc<Context>) -> Result<(), Actio
             "Updated status.running to false in {}, requeuing 10 seconds",
             &name
         );
-        return Err(Action::requeue(Duration::from_secs(10)));
+        return Err(requeue_with_condition(
+            cdb,
+            ctx.clone(),
+            conditions,
+            CLUSTER_READY,
+            "Restarting",
+            &format!("Restart requested through the {} annotation", RESTARTED_AT),
+            Action::requeue(Duration::from_secs(10)),
+        )
+        .await);
     }
 
     Ok(())
 }
 
-// Reconcile a Pooler
-#[instrument(skip(cdb, ctx) fields(trace_id, instance_name = %cdb.name_any()))]
-pub async fn reconcile_pooler(cdb: &CoreDB, ctx: Arc<Context>) -> Result<(), Action> {
-    let client = ctx.client.clone();
-    let name = cdb.name_any() + "-pooler";
-    let namespace = cdb.namespace().unwrap();
-    let pooler_api: Api<Pooler> = Api::namespaced(client.clone(), namespace.as_str());
-
-    let owner_reference = cdb.controller_owner_ref(&()).unwrap();
-
-    // If pooler is enabled, create or update
-    if cdb.spec.connectionPooler.enabled {
-        debug!("Configuraing pooler instance for {}", cdb.name_any());
-        let pooler = Pooler {
-            metadata: ObjectMeta {
-                name: Some(name.clone()),
-                namespace: Some(namespace.clone()),
-                owner_references: Some(vec![owner_reference]),
-                ..ObjectMeta::default()
+fn cnpg_pooler(cdb: &CoreDB, name: &str, pooler_type: PoolerType) -> Pooler {
+    Pooler {
+        metadata: ObjectMeta {
+            name: Some(name.to_string()),
+            namespace: cdb.namespace(),
+            owner_references: Some(vec![cdb.controller_owner_ref(&()).unwrap()]),
+            ..ObjectMeta::default()
+        },
+        spec: PoolerSpec {
+            cluster: PoolerCluster { name: cdb.name_any() },
+            deployment_strategy: None,
+            // Scale the pooler to zero while the instance is hibernated
+            instances: if cdb.spec.stop {
+                0
+            } else {
+                cdb.spec.connectionPooler.instances
             },
-            spec: PoolerSpec {
-                cluster: PoolerCluster { name: cdb.name_any() },
-                deployment_strategy: None,
-                instances: 1,
-                monitoring: None,
-                pgbouncer: PoolerPgbouncer {
-                    auth_query: None,
-                    auth_query_secret: None,
-                    parameters: cdb.spec.connectionPooler.pooler.parameters.clone(),
-                    paused: None,
-                    pg_hba: None,
-                    pool_mode: cdb.spec.connectionPooler.pooler.poolMode.clone(),
-                },
-                template: Some(PoolerTemplate {
-                    metadata: None,
-                    spec: Some(PoolerTemplateSpec {
-                        containers: vec![PoolerTemplateSpecContainers {
-                            name: "pgbouncer".to_string(),
-                            resources: cdb.spec.connectionPooler.pooler.resources.clone(),
-                            ..Default::default()
-                        }],
+            monitoring: None,
+            pgbouncer: PoolerPgbouncer {
+                auth_query: None,
+                auth_query_secret: None,
+                parameters: cdb.spec.connectionPooler.pooler.parameters.clone(),
+                paused: None,
+                pg_hba: None,
+                pool_mode: cdb.spec.connectionPooler.pooler.poolMode.clone(),
+            },
+            template: Some(PoolerTemplate {
+                metadata: None,
+                spec: Some(PoolerTemplateSpec {
+                    containers: vec![PoolerTemplateSpecContainers {
+                        name: "pgbouncer".to_string(),
+                        resources: cdb.spec.connectionPooler.pooler.resources.clone(),
                         ..Default::default()
-                    }),
+                    }],
+                    ..Default::default()
                 }),
-                r#type: PoolerType::Rw,
+            }),
+            r#type: pooler_type,
+        },
+        status: None,
+    }
+}
+
+// The Pooler as it is applied. While the pooler is autoscaled, spec.instances is left out so that
+// the HorizontalPodAutoscaler owns it through the scale subresource of the Pooler.
+fn pooler_patch(cdb: &CoreDB, pooler: &Pooler) -> serde_json::Value {
+    let mut patch = serde_json::to_value(pooler).expect("a Pooler can always be serialized");
+    if pooler_autoscaled(cdb) {
+        if let Some(spec) = patch.get_mut("spec").and_then(|spec| spec.as_object_mut()) {
+            spec.remove("instances");
+        }
+    }
+    patch
+}
+
+// The autoscaler owns spec.instances of an autoscaled Pooler through the scale subresource. The
+// apply is forced to take it back when autoscaling is removed or the instance is hibernated.
+fn pooler_patch_params() -> PatchParams {
+    PatchParams::apply("cntrlr").force()
+}
+
+async fn apply_pooler(cdb: &CoreDB, pooler_api: &Api<Pooler>, pooler: &Pooler) -> Result<(), kube::Error> {
+    let name = pooler.name_any();
+    debug!("Patching Pooler {name}");
+    let ps = pooler_patch_params();
+    let _o = pooler_api
+        .patch(&name, &ps, &Patch::Apply(pooler_patch(cdb, pooler)))
+        .await
+        .map_err(|e| {
+            error!("Error patching Pooler: {}", e);
+            e
+        })?;
+    Ok(())
+}
+
+// CNPG labels the PgBouncer pods of a Pooler with the name of the Pooler
+fn pooler_selector(pooler_name: &str) -> LabelSelector {
+    LabelSelector {
+        match_labels: Some(BTreeMap::from([(
+            "cnpg.io/poolerName".to_string(),
+            pooler_name.to_string(),
+        )])),
+        ..LabelSelector::default()
+    }
+}
+
+fn pooler_pod_disruption_budget(cdb: &CoreDB, pooler_name: &str) -> Option<PodDisruptionBudget> {
+    let min_available = cdb.spec.connectionPooler.minAvailable?;
+    Some(PodDisruptionBudget {
+        metadata: ObjectMeta {
+            name: Some(pooler_name.to_string()),
+            namespace: cdb.namespace(),
+            owner_references: Some(vec![cdb.controller_owner_ref(&()).unwrap()]),
+            ..ObjectMeta::default()
+        },
+        spec: Some(PodDisruptionBudgetSpec {
+            min_available: Some(IntOrString::Int(min_available)),
+            selector: Some(pooler_selector(pooler_name)),
+            ..PodDisruptionBudgetSpec::default()
+        }),
+        status: None,
+    })
+}
+
+// There is no autoscaling while the instance is hibernated, it would scale the pooler back up
+fn pooler_autoscaled(cdb: &CoreDB) -> bool {
+    cdb.spec.connectionPooler.autoscaling.is_some() && !cdb.spec.stop
+}
+
+// The HorizontalPodAutoscaler scales the Pooler through its scale subresource, CNPG then sizes the
+// Deployment of the Pooler from spec.instances
+fn pooler_horizontal_pod_autoscaler(cdb: &CoreDB, pooler_name: &str) -> Option<HorizontalPodAutoscaler> {
+    if !pooler_autoscaled(cdb) {
+        return None;
+    }
+    let autoscaling = cdb.spec.connectionPooler.autoscaling.as_ref()?;
+    Some(HorizontalPodAutoscaler {
+        metadata: ObjectMeta {
+            name: Some(pooler_name.to_string()),
+            namespace: cdb.namespace(),
+            owner_references: Some(vec![cdb.controller_owner_ref(&()).unwrap()]),
+            ..ObjectMeta::default()
+        },
+        spec: Some(HorizontalPodAutoscalerSpec {
+            scale_target_ref: CrossVersionObjectReference {
+                api_version: Some("postgresql.cnpg.io/v1".to_string()),
+                kind: "Pooler".to_string(),
+                name: pooler_name.to_string(),
             },
-            status: None,
-        };
+            min_replicas: Some(autoscaling.minReplicas),
+            max_replicas: autoscaling.maxReplicas,
+            metrics: Some(vec![MetricSpec {
+                type_: "Resource".to_string(),
+                resource: Some(ResourceMetricSource {
+                    name: "cpu".to_string(),
+                    target: MetricTarget {
+                        type_: "Utilization".to_string(),
+                        average_utilization: Some(autoscaling.targetCPUUtilizationPercentage),
+                        ..MetricTarget::default()
+                    },
+                }),
+                ..MetricSpec::default()
+            }]),
+            ..HorizontalPodAutoscalerSpec::default()
+        }),
+        status: None,
+    })
+}
 
-        debug!("Patching Pooler {name}");
-        let ps = PatchParams::apply("cntrlr");
-        let _o = pooler_api
-            .patch(&name, &ps, &Patch::Apply(&pooler))
-            .await
-            .map_err(|e| {
-                error!("Error patching Pooler: {}", e);
-                Action::requeue(Duration::from_secs(300))
-            })?;
-
-        // Check to see if the primary pod is ready, if it is the setup pgbouncer.  If the pod is
-        // not ready then just continue on and wait for the next reconcile.
-        let primary_pod = cdb.primary_pod_cnpg_ready_or_not(client.clone()).await?;
-        if !is_postgres_ready().matches_object(Some(&primary_pod)) {
-            debug!("Primary pod is not ready, skipping setup_pgbouncer");
-            return Ok(());
+async fn apply_or_delete<K>(api: &Api<K>, name: &str, resource: Option<K>) -> Result<(), kube::Error>
+where
+    K: Resource + Clone + DeserializeOwned + Serialize + std::fmt::Debug,
+{
+    match resource {
+        Some(resource) => {
+            debug!("Patching {}", name);
+            let ps = PatchParams::apply("cntrlr");
+            api.patch(name, &ps, &Patch::Apply(&resource))
+                .await
+                .map_err(|e| {
+                    error!("Error patching {}: {}", name, e);
+                    e
+                })?;
+        }
+        None => {
+            if api.get_opt(name).await.ok().flatten().is_some() {
+                debug!("Deleting {}", name);
+                api.delete(name, &DeleteParams::default()).await.map_err(|e| {
+                    error!("Error deleting {}: {}", name, e);
+                    e
+                })?;
+            }
         }
+    }
+    Ok(())
+}
 
-        match setup_pgbouncer_function(cdb, ctx.clone()).await {
-            Ok(_) => debug!(
-                "Successfully created setup_pgbouncer function on instance {}",
-                cdb.name_any()
-            ),
+// The PodDisruptionBudget and HorizontalPodAutoscaler of a pooler, deleted when the pooler is disabled
+async fn reconcile_pooler_scaling(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    pooler_name: &str,
+    enabled: bool,
+) -> Result<(), kube::Error> {
+    let namespace = cdb.namespace().unwrap();
+    let pdb_api: Api<PodDisruptionBudget> = Api::namespaced(ctx.client.clone(), &namespace);
+    let hpa_api: Api<HorizontalPodAutoscaler> = Api::namespaced(ctx.client.clone(), &namespace);
+    let (pdb, hpa) = match enabled {
+        true => (
+            pooler_pod_disruption_budget(cdb, pooler_name),
+            pooler_horizontal_pod_autoscaler(cdb, pooler_name),
+        ),
+        false => (None, None),
+    };
+    apply_or_delete(&pdb_api, pooler_name, pdb).await?;
+    apply_or_delete(&hpa_api, pooler_name, hpa).await?;
+    Ok(())
+}
+
+// Ready PgBouncer instances of the read-write and read-only poolers, None when the pooler is disabled
+pub async fn pooler_ready_replicas(cdb: &CoreDB, ctx: Arc<Context>) -> Option<i32> {
+    if !cdb.spec.connectionPooler.enabled {
+        return None;
+    }
+    let mut pooler_names = vec![format!("{}-pooler", cdb.name_any())];
+    if cdb.spec.connectionPooler.readOnly {
+        pooler_names.push(format!("{}-pooler-ro", cdb.name_any()));
+    }
+    let deployment_api: Api<Deployment> = Api::namespaced(ctx.client.clone(), &cdb.namespace().unwrap());
+    let mut ready_replicas = 0;
+    for pooler_name in pooler_names {
+        match deployment_api.get_opt(&pooler_name).await {
+            Ok(deployment) => {
+                ready_replicas += deployment
+                    .and_then(|d| d.status)
+                    .and_then(|s| s.ready_replicas)
+                    .unwrap_or(0)
+            }
             Err(e) => {
-                warn!("Did not create setup_pgbouncer function, will requeue: {:?}", e);
-                return Err(Action::requeue(Duration::from_secs(30)));
+                warn!("Error getting the pooler deployment {}: {}", pooler_name, e);
+                return None;
             }
         }
-        // Run the setup_pgbouncer function
-        cdb.psql(
+    }
+    Some(ready_replicas)
+}
+
+// If the pooler is disabled and exists, delete it
+async fn delete_pooler(pooler_api: &Api<Pooler>, name: &str) -> Result<(), kube::Error> {
+    let pooler = pooler_api.get(name).await;
+    if pooler.is_err() {
+        debug!("Pooler {name} does not exist. Skipping deletion");
+    } else {
+        debug!("Found pooler {name} and pooler is disabled. Deleting Pooler {name}");
+        let dp = DeleteParams::default();
+        pooler_api.delete(name, &dp).await.map_err(|e| {
+            error!("Error deleting Pooler: {}", e);
+            e
+        })?;
+    }
+    Ok(())
+}
+
+// Reconcile a Pooler, and the optional read-only Pooler in front of the replicas
+#[instrument(skip(cdb, ctx, conditions, cfg) fields(trace_id, instance_name = %cdb.name_any()))]
+pub async fn reconcile_pooler(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    cfg: &Config,
+    conditions: &mut Vec<StatusCondition>,
+) -> Result<(), Action> {
+    let client = ctx.client.clone();
+
+    if let Err(e) = apply_or_delete_poolers(cdb, ctx.clone()).await {
+        return Err(requeue_with_condition(
+            cdb,
+            ctx.clone(),
+            conditions,
+            POOLER_READY,
+            "PoolerNotReady",
+            &format!("Error reconciling the connection pooler: {}", e),
+            Action::requeue(Duration::from_secs(300)),
+        )
+        .await);
+    }
+
+    if !cdb.spec.connectionPooler.enabled {
+        return Ok(());
+    }
+
+    if cdb.spec.stop {
+        debug!("Instance {} is stopped, skipping setup_pgbouncer", cdb.name_any());
+        return Ok(());
+    }
+
+    // Check to see if the primary pod is ready, if it is the setup pgbouncer.  If the pod is
+    // not ready then just continue on and wait for the next reconcile.
+    let primary_pod = match cdb.primary_pod_cnpg_ready_or_not(client.clone()).await {
+        Ok(primary_pod) => primary_pod,
+        Err(action) => {
+            return Err(requeue_with_condition(
+                cdb,
+                ctx.clone(),
+                conditions,
+                POOLER_READY,
+                "PoolerNotReady",
+                "Waiting for the primary pod to set up PgBouncer authentication",
+                action,
+            )
+            .await);
+        }
+    };
+    if !is_postgres_ready().matches_object(Some(&primary_pod)) {
+        debug!("Primary pod is not ready, skipping setup_pgbouncer");
+        return Ok(());
+    }
+
+    match setup_pgbouncer_function(cdb, ctx.clone(), cfg).await {
+        Ok(_) => debug!(
+            "Successfully created setup_pgbouncer function on instance {}",
+            cdb.name_any()
+        ),
+        Err(e) => {
+            warn!("Did not create setup_pgbouncer function, will requeue: {:?}", e);
+            return Err(requeue_with_condition(
+                cdb,
+                ctx.clone(),
+                conditions,
+                POOLER_READY,
+                "PoolerNotReady",
+                "Error creating the setup_pgbouncer function",
+                Action::requeue(Duration::from_secs(30)),
+            )
+            .await);
+        }
+    }
+    // Run the setup_pgbouncer function
+    if let Err(action) = cdb
+        .execute_sql(
             "SELECT setup_pgbouncer();".to_string(),
             "postgres".to_string(),
             ctx.clone(),
+            cfg,
         )
-        .await?;
-    } else {
-        // If pooler is disabled and exists, delete
-        let pooler = pooler_api.get(&name).await;
-        if pooler.is_err() {
-            debug!("Pooler {name} does not exist. Skipping deletion");
-            return Ok(());
+        .await
+    {
+        return Err(requeue_with_condition(
+            cdb,
+            ctx.clone(),
+            conditions,
+            POOLER_READY,
+            "PoolerNotReady",
+            "Error running the setup_pgbouncer function",
+            action,
+        )
+        .await);
+    }
+
+    Ok(())
+}
+
+// Create or update the Poolers that are enabled with their scaling resources, and delete the rest
+async fn apply_or_delete_poolers(cdb: &CoreDB, ctx: Arc<Context>) -> Result<(), kube::Error> {
+    let name = cdb.name_any() + "-pooler";
+    let name_read_only = cdb.name_any() + "-pooler-ro";
+    let namespace = cdb.namespace().unwrap();
+    let pooler_api: Api<Pooler> = Api::namespaced(ctx.client.clone(), namespace.as_str());
+
+    // If pooler is enabled, create or update
+    if cdb.spec.connectionPooler.enabled {
+        debug!("Configuraing pooler instance for {}", cdb.name_any());
+        apply_pooler(cdb, &pooler_api, &cnpg_pooler(cdb, &name, PoolerType::Rw)).await?;
+
+        reconcile_pooler_scaling(cdb, ctx.clone(), &name, true).await?;
+
+        if cdb.spec.connectionPooler.readOnly {
+            debug!("Configuring read-only pooler instance for {}", cdb.name_any());
+            apply_pooler(
+                cdb,
+                &pooler_api,
+                &cnpg_pooler(cdb, &name_read_only, PoolerType::Ro),
+            )
+            .await?;
         } else {
-            debug!("Found pooler {name} and pooler is disabled. Deleting Pooler {name}");
-            let dp = DeleteParams::default();
-            pooler_api.delete(&name, &dp).await.map_err(|e| {
-                error!("Error deleting Pooler: {}", e);
-                Action::requeue(Duration::from_secs(300))
-            })?;
+            delete_pooler(&pooler_api, &name_read_only).await?;
         }
+        reconcile_pooler_scaling(
+            cdb,
+            ctx.clone(),
+            &name_read_only,
+            cdb.spec.connectionPooler.readOnly,
+        )
+        .await?;
+    } else {
+        delete_pooler(&pooler_api, &name).await?;
+        delete_pooler(&pooler_api, &name_read_only).await?;
+        reconcile_pooler_scaling(cdb, ctx.clone(), &name, false).await?;
+        reconcile_pooler_scaling(cdb, ctx, &name_read_only, false).await?;
     }
-
     Ok(())
 }
 
@@ -1158,15 +1933,20 @@ BEGIN
 END;
 $$;"#;
 
-#[instrument(skip(coredb, ctx) fields(trace_id, instance_name = %coredb.name_any()))]
-async fn setup_pgbouncer_function(coredb: &CoreDB, ctx: Arc<Context>) -> Result<PsqlOutput, Action> {
+#[instrument(skip(coredb, ctx, cfg) fields(trace_id, instance_name = %coredb.name_any()))]
+async fn setup_pgbouncer_function(
+    coredb: &CoreDB,
+    ctx: Arc<Context>,
+    cfg: &Config,
+) -> Result<PsqlOutput, Action> {
     // execute the PGBOUNCER_SETUP_FUNCTION to install/update the function
     // on the instance
     let query = coredb
-        .psql(
+        .execute_sql(
             PGBOUNCER_SETUP_FUNCTION.to_string(),
             "postgres".to_string(),
             ctx.clone(),
+            cfg,
         )
         .await?;
     Ok(query)
@@ -1221,7 +2001,7 @@ fn cnpg_scheduled_backup(cdb: &CoreDB) -> ScheduledBackup {
         spec: ScheduledBackupSpec {
             backup_owner_reference: Some(ScheduledBackupBackupOwnerReference::Cluster),
             cluster: Some(ScheduledBackupCluster { name }),
-            immediate: Some(true),
+            immediate: Some(cdb.spec.backup.immediate_backup.unwrap_or(true)),
             schedule: schedule_expression_from_cdb(cdb),
             suspend: Some(false),
             ..ScheduledBackupSpec::default()
@@ -1232,7 +2012,7 @@ fn cnpg_scheduled_backup(cdb: &CoreDB) -> ScheduledBackup {
 
 // Reconcile a SheduledBackup
 #[instrument(skip(cdb, ctx), fields(trace_id, instance_name = %cdb.name_any()))]
-pub async fn reconcile_cnpg_scheduled_backup(cdb: &CoreDB, ctx: Arc<Context>) -> Result<(), Action> {
+pub async fn reconcile_cnpg_scheduled_backup(cdb: &CoreDB, ctx: Arc<Context>) -> Result<(), kube::Error> {
     let scheduledbackup = cnpg_scheduled_backup(cdb);
     let client = ctx.client.clone();
     let name = scheduledbackup
@@ -1254,7 +2034,7 @@ pub async fn reconcile_cnpg_scheduled_backup(cdb: &CoreDB, ctx: Arc<Context>) ->
         .await
         .map_err(|e| {
             error!("Error patching ScheduledBackup: {}", e);
-            Action::requeue(Duration::from_secs(300))
+            e
         })?;
     debug!("Applied ScheduledBackup");
     Ok(())
@@ -1550,6 +2330,14 @@ pub async fn unfence_pod(cdb: &CoreDB, ctx: Arc<Context>, pod_name: &str) -> Res
                     Action::requeue(Duration::from_secs(30))
                 })?;
             debug!("Cluster annotations patched for instance {}", instance_name);
+            publish_event(
+                cdb,
+                ctx.clone(),
+                EventType::Normal,
+                "PodUnfenced",
+                format!("Unfenced pod {}", pod_name),
+            )
+            .await;
             Ok(())
         } else {
             debug!("The fencedInstances annotation is not set in the Cluster Status for instance {}. Removing the key.", instance_name);
@@ -1599,6 +2387,125 @@ fn generate_restore_destination_path(path: &str) -> String {
     parts.join("/")
 }
 
+// With workload identity, the Google service account or the Azure identity is bound to the
+// Kubernetes service account of the instance with annotations, which are copied to the service
+// account template of the Cluster
+fn workload_identity_service_account_template(cdb: &CoreDB) -> Option<ClusterServiceAccountTemplate> {
+    let backup = &cdb.spec.backup;
+    let workload_identity = backup
+        .google_credentials
+        .as_ref()
+        .and_then(|creds| creds.gke_environment)
+        .unwrap_or(false)
+        || backup
+            .azure_credentials
+            .as_ref()
+            .and_then(|creds| creds.inherit_from_azure_ad)
+            .unwrap_or(false);
+    if !workload_identity {
+        return None;
+    }
+    let metadata = cdb.spec.serviceAccountTemplate.metadata.as_ref()?;
+    Some(ClusterServiceAccountTemplate {
+        metadata: ClusterServiceAccountTemplateMetadata {
+            annotations: metadata.annotations.clone(),
+            labels: metadata.labels.clone(),
+        },
+    })
+}
+
+fn generate_google_backup_credentials(
+    creds: &GoogleCredentials,
+) -> ClusterBackupBarmanObjectStoreGoogleCredentials {
+    ClusterBackupBarmanObjectStoreGoogleCredentials {
+        application_credentials: creds.application_credentials.as_ref().map(|app_creds| {
+            ClusterBackupBarmanObjectStoreGoogleCredentialsApplicationCredentials {
+                key: app_creds.key.clone(),
+                name: app_creds.name.clone(),
+            }
+        }),
+        gke_environment: creds.gke_environment,
+    }
+}
+
+fn generate_azure_backup_credentials(
+    creds: &AzureCredentials,
+) -> ClusterBackupBarmanObjectStoreAzureCredentials {
+    ClusterBackupBarmanObjectStoreAzureCredentials {
+        connection_string: creds.connection_string.as_ref().map(|conn| {
+            ClusterBackupBarmanObjectStoreAzureCredentialsConnectionString {
+                key: conn.key.clone(),
+                name: conn.name.clone(),
+            }
+        }),
+        inherit_from_azure_ad: creds.inherit_from_azure_ad,
+        storage_account: creds.storage_account.as_ref().map(|account| {
+            ClusterBackupBarmanObjectStoreAzureCredentialsStorageAccount {
+                key: account.key.clone(),
+                name: account.name.clone(),
+            }
+        }),
+        storage_key: creds.storage_key.as_ref().map(|storage_key| {
+            ClusterBackupBarmanObjectStoreAzureCredentialsStorageKey {
+                key: storage_key.key.clone(),
+                name: storage_key.name.clone(),
+            }
+        }),
+        storage_sas_token: creds.storage_sas_token.as_ref().map(|token| {
+            ClusterBackupBarmanObjectStoreAzureCredentialsStorageSasToken {
+                key: token.key.clone(),
+                name: token.name.clone(),
+            }
+        }),
+    }
+}
+
+fn generate_google_restore_credentials(
+    creds: &GoogleCredentials,
+) -> ClusterExternalClustersBarmanObjectStoreGoogleCredentials {
+    ClusterExternalClustersBarmanObjectStoreGoogleCredentials {
+        application_credentials: creds.application_credentials.as_ref().map(|app_creds| {
+            ClusterExternalClustersBarmanObjectStoreGoogleCredentialsApplicationCredentials {
+                key: app_creds.key.clone(),
+                name: app_creds.name.clone(),
+            }
+        }),
+        gke_environment: creds.gke_environment,
+    }
+}
+
+fn generate_azure_restore_credentials(
+    creds: &AzureCredentials,
+) -> ClusterExternalClustersBarmanObjectStoreAzureCredentials {
+    ClusterExternalClustersBarmanObjectStoreAzureCredentials {
+        connection_string: creds.connection_string.as_ref().map(|conn| {
+            ClusterExternalClustersBarmanObjectStoreAzureCredentialsConnectionString {
+                key: conn.key.clone(),
+                name: conn.name.clone(),
+            }
+        }),
+        inherit_from_azure_ad: creds.inherit_from_azure_ad,
+        storage_account: creds.storage_account.as_ref().map(|account| {
+            ClusterExternalClustersBarmanObjectStoreAzureCredentialsStorageAccount {
+                key: account.key.clone(),
+                name: account.name.clone(),
+            }
+        }),
+        storage_key: creds.storage_key.as_ref().map(|storage_key| {
+            ClusterExternalClustersBarmanObjectStoreAzureCredentialsStorageKey {
+                key: storage_key.key.clone(),
+                name: storage_key.name.clone(),
+            }
+        }),
+        storage_sas_token: creds.storage_sas_token.as_ref().map(|token| {
+            ClusterExternalClustersBarmanObjectStoreAzureCredentialsStorageSasToken {
+                key: token.key.clone(),
+                name: token.name.clone(),
+            }
+        }),
+    }
+}
+
 // generate_s3_backup_credentials function will generate the s3 backup credentials from
 // S3Credentials object and return a ClusterBackupBarmanObjectStoreS3Credentials object
 #[instrument(fields(trace_id, creds))]
@@ -1701,8 +2608,16 @@ fn generate_s3_restore_credentials(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        apis::coredb_types::{CoreDBSpec, CoreDBStatus, Stack},
+        cloudnativepg::clusters::{
+            ClusterBackupBarmanObjectStoreDataCompression, ClusterBackupBarmanObjectStoreWalCompression,
+            ClusterExternalClustersBarmanObjectStoreWalCompression,
+        },
+        extensions::types::{Extension, ExtensionInstallLocation, TrunkInstallStatus},
+    };
     use serde_json::json;
-    use std::collections::BTreeMap;
+    use std::collections::{BTreeMap, BTreeSet};
 
     #[test]
     fn test_deserialize_cluster() {
@@ -2120,6 +3035,473 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_cnpg_google_backup() {
+        let cdb_yaml = r#"
+        apiVersion: coredb.io/v1alpha1
+        kind: CoreDB
+        metadata:
+          name: test
+          namespace: default
+        spec:
+          backup:
+            destinationPath: gs://gcs-bucket/tembo/backup
+            googleCredentials:
+              gkeEnvironment: true
+            retentionPolicy: "30"
+            schedule: 17 9 * * *
+          serviceAccountTemplate:
+            metadata:
+              annotations:
+                iam.gke.io/gcp-service-account: tembo-backup@project.iam.gserviceaccount.com
+          storage: 1Gi
+        "#;
+        let cdb: CoreDB = from_str(cdb_yaml).unwrap();
+        let cfg = Config::default();
+
+        let (backup, service_account_template) = cnpg_backup_configuration(&cdb, &cfg);
+
+        let barman_object_store = backup.unwrap().barman_object_store.unwrap();
+        assert_eq!(
+            barman_object_store.destination_path,
+            "gs://gcs-bucket/tembo/backup".to_string()
+        );
+        assert!(barman_object_store.s3_credentials.is_none());
+        assert!(barman_object_store.azure_credentials.is_none());
+        assert_eq!(
+            barman_object_store.google_credentials.unwrap().gke_environment,
+            Some(true)
+        );
+        // Encryption is only supported with S3
+        assert!(barman_object_store.data.unwrap().encryption.is_none());
+        assert!(barman_object_store.wal.is_none());
+
+        // The service account is bound to the Google service account
+        assert_eq!(
+            service_account_template
+                .unwrap()
+                .metadata
+                .annotations
+                .unwrap()
+                .get("iam.gke.io/gcp-service-account")
+                .unwrap(),
+            "tembo-backup@project.iam.gserviceaccount.com"
+        );
+    }
+
+    #[test]
+    fn test_cnpg_azure_restore() {
+        let cdb_yaml = r#"
+        apiVersion: coredb.io/v1alpha1
+        kind: CoreDB
+        metadata:
+          name: test
+          namespace: default
+        spec:
+          backup:
+            destinationPath: https://account.blob.core.windows.net/tembo/backup
+            azureCredentials:
+              storageAccount:
+                name: azure-creds
+                key: account
+              storageKey:
+                name: azure-creds
+                key: key
+          restore:
+            serverName: original
+            azureCredentials:
+              connectionString:
+                name: azure-creds
+                key: connection-string
+          storage: 1Gi
+        "#;
+        let cdb: CoreDB = from_str(cdb_yaml).unwrap();
+
+        let (_, external_clusters, _) = cnpg_cluster_bootstrap_from_cdb(&cdb);
+
+        let barman_object_store = external_clusters.unwrap()[0].barman_object_store.clone().unwrap();
+        assert_eq!(
+            barman_object_store.destination_path,
+            "https://account.blob.core.windows.net/tembo/original".to_string()
+        );
+        assert!(barman_object_store.s3_credentials.is_none());
+        assert!(barman_object_store.google_credentials.is_none());
+        let connection_string = barman_object_store
+            .azure_credentials
+            .unwrap()
+            .connection_string
+            .unwrap();
+        assert_eq!(connection_string.name, "azure-creds");
+        assert_eq!(connection_string.key, "connection-string");
+        assert!(barman_object_store.wal.unwrap().encryption.is_none());
+
+        // Without workload identity, the service account template is left alone
+        let (backup, service_account_template) = cnpg_backup_configuration(&cdb, &Config::default());
+        assert!(service_account_template.is_none());
+        let azure_credentials = backup
+            .unwrap()
+            .barman_object_store
+            .unwrap()
+            .azure_credentials
+            .unwrap();
+        assert_eq!(azure_credentials.storage_key.unwrap().key, "key");
+    }
+
+    #[test]
+    fn test_cnpg_backup_tuning() {
+        let cdb_yaml = r#"
+        apiVersion: coredb.io/v1alpha1
+        kind: CoreDB
+        metadata:
+          name: test
+          namespace: default
+        spec:
+          backup:
+            destinationPath: s3://aws-s3-bucket/tembo/backup
+            encryption: AES256
+          restore:
+            serverName: original
+          stack:
+            name: DataWarehouse
+          storage: 1Gi
+        "#;
+        let mut cdb: CoreDB = from_str(cdb_yaml).unwrap();
+        let cfg = Config::default();
+
+        // The defaults of the stack
+        assert_eq!(backup_tuning(&cdb), BackupTuning {
+            data_compression: BackupCompression::Snappy,
+            wal_compression: BackupCompression::Snappy,
+            jobs: Some(4),
+            wal_max_parallel: 8,
+        });
+        cdb.spec.stack = None;
+        assert_eq!(backup_tuning(&cdb).jobs, None);
+        assert_eq!(backup_tuning(&cdb).wal_max_parallel, 5);
+
+        // Without encryption or WAL settings, the WAL section is left to CNPG
+        cdb.spec.backup.encryption = None;
+        assert!(create_cluster_backup_barman_wal(&cdb).is_none());
+        cdb.spec.stack = Some(Stack {
+            name: "MessageQueue".to_string(),
+            ..Stack::default()
+        });
+        assert_eq!(
+            create_cluster_backup_barman_wal(&cdb).unwrap().max_parallel,
+            Some(8)
+        );
+        cdb.spec.stack = None;
+        cdb.spec.backup.encryption = Some("AES256".to_string());
+
+        // The spec overrides the defaults of the stack
+        cdb.spec.backup.data_compression = Some(BackupCompression::Bzip2);
+        cdb.spec.backup.wal_compression = Some(BackupCompression::Gzip);
+        cdb.spec.backup.jobs = Some(2);
+        cdb.spec.backup.wal_max_parallel = Some(16);
+        cdb.spec.backup.immediate_backup = Some(false);
+
+        let (backup, _) = cnpg_backup_configuration(&cdb, &cfg);
+        let barman_object_store = backup.unwrap().barman_object_store.unwrap();
+        let data = barman_object_store.data.unwrap();
+        assert!(matches!(
+            data.compression,
+            Some(ClusterBackupBarmanObjectStoreDataCompression::Bzip2)
+        ));
+        assert_eq!(data.jobs, Some(2));
+        let wal = barman_object_store.wal.unwrap();
+        assert!(matches!(
+            wal.compression,
+            Some(ClusterBackupBarmanObjectStoreWalCompression::Gzip)
+        ));
+        assert!(matches!(
+            wal.encryption,
+            Some(ClusterBackupBarmanObjectStoreWalEncryption::Aes256)
+        ));
+        assert_eq!(wal.max_parallel, Some(16));
+
+        // WAL files are restored with the same settings
+        let (_, external_clusters, _) = cnpg_cluster_bootstrap_from_cdb(&cdb);
+        let restore_wal = external_clusters.unwrap()[0]
+            .barman_object_store
+            .clone()
+            .unwrap()
+            .wal
+            .unwrap();
+        assert_eq!(restore_wal.max_parallel, Some(16));
+        assert!(matches!(
+            restore_wal.compression,
+            Some(ClusterExternalClustersBarmanObjectStoreWalCompression::Gzip)
+        ));
+
+        assert_eq!(cnpg_scheduled_backup(&cdb).spec.immediate, Some(false));
+    }
+
+    #[test]
+    fn test_cnpg_cluster_hibernation() {
+        let cdb_yaml = r#"
+        apiVersion: coredb.io/v1alpha1
+        kind: CoreDB
+        metadata:
+          name: test
+          namespace: default
+          uid: 752d59ef-2671-4890-9feb-0097459b18c8
+        spec:
+          replicas: 1
+          stop: true
+        "#;
+        let mut cdb: CoreDB = from_str(cdb_yaml).unwrap();
+
+        let cluster = cnpg_cluster_from_cdb(&cdb, None, BTreeMap::new(), BTreeMap::new());
+        let annotations = cluster.metadata.annotations.unwrap();
+        assert_eq!(annotations.get(HIBERNATION).unwrap(), "on");
+        // Hibernation keeps the instance count, CNPG deletes the pods and keeps the PVCs
+        assert_eq!(cluster.spec.instances, 1);
+
+        cdb.spec.stop = false;
+        let cluster = cnpg_cluster_from_cdb(&cdb, None, BTreeMap::new(), BTreeMap::new());
+        let annotations = cluster.metadata.annotations.unwrap();
+        assert_eq!(annotations.get(HIBERNATION).unwrap(), "off");
+    }
+
+    #[test]
+    fn test_cnpg_cluster_managed_roles() {
+        let cdb_yaml = r#"
+        apiVersion: coredb.io/v1alpha1
+        kind: CoreDB
+        metadata:
+          name: test
+          namespace: default
+          uid: 752d59ef-2671-4890-9feb-0097459b18c8
+        spec:
+          roles:
+          - name: app
+            inRoles:
+            - pg_read_all_data
+            connectionLimit: 10
+            passwordSecret: app-credentials
+          - name: app_owner
+            login: false
+        "#;
+        let cdb: CoreDB = from_str(cdb_yaml).unwrap();
+
+        let cluster = cnpg_cluster_from_cdb(&cdb, None, BTreeMap::new(), BTreeMap::new());
+        let roles = cluster.spec.managed.unwrap().roles.unwrap();
+        // The operator roles come first
+        assert_eq!(roles.len(), 4);
+        assert_eq!(roles[0].name, "readonly");
+        assert_eq!(roles[1].name, "postgres_exporter");
+        assert_eq!(roles[2].name, "app");
+        assert_eq!(roles[2].login, Some(true));
+        assert_eq!(roles[2].in_roles, Some(vec!["pg_read_all_data".to_string()]));
+        assert_eq!(roles[2].connection_limit, Some(10));
+        assert_eq!(roles[2].password_secret.as_ref().unwrap().name, "app-credentials");
+        assert_eq!(roles[3].name, "app_owner");
+        assert_eq!(roles[3].login, Some(false));
+        assert_eq!(roles[3].connection_limit, Some(-1));
+        assert!(roles[3].password_secret.is_none());
+    }
+
+    #[test]
+    fn test_cnpg_cluster_high_availability() {
+        let cdb_yaml = r#"
+        apiVersion: coredb.io/v1alpha1
+        kind: CoreDB
+        metadata:
+          name: test
+          namespace: default
+          uid: 752d59ef-2671-4890-9feb-0097459b18c8
+        spec:
+          replicas: 3
+        "#;
+        let mut cdb: CoreDB = from_str(cdb_yaml).unwrap();
+
+        // Without a highAvailability section replication stays asynchronous
+        let cluster = cnpg_cluster_from_cdb(&cdb, None, BTreeMap::new(), BTreeMap::new());
+        assert_eq!(cluster.spec.min_sync_replicas, Some(0));
+        assert_eq!(cluster.spec.max_sync_replicas, Some(0));
+        let affinity = cluster.spec.affinity.unwrap();
+        assert_eq!(affinity.pod_anti_affinity_type.unwrap(), "preferred");
+        assert_eq!(affinity.topology_key.unwrap(), "topology.kubernetes.io/zone");
+        assert!(
+            !cluster
+                .spec
+                .postgresql
+                .unwrap()
+                .sync_replica_election_constraint
+                .unwrap()
+                .enabled
+        );
+
+        let high_availability: HighAvailability = from_str(
+            r#"
+            syncReplicas: 1
+            zoneElectionConstraint: true
+            podAntiAffinityType: required
+            topologyKey: kubernetes.io/hostname
+            "#,
+        )
+        .unwrap();
+        cdb.spec.high_availability = Some(high_availability);
+        let cluster = cnpg_cluster_from_cdb(&cdb, None, BTreeMap::new(), BTreeMap::new());
+        assert_eq!(cluster.spec.min_sync_replicas, Some(1));
+        assert_eq!(cluster.spec.max_sync_replicas, Some(1));
+        let affinity = cluster.spec.affinity.unwrap();
+        assert_eq!(affinity.pod_anti_affinity_type.unwrap(), "required");
+        assert_eq!(affinity.topology_key.unwrap(), "kubernetes.io/hostname");
+        let constraint = cluster
+            .spec
+            .postgresql
+            .unwrap()
+            .sync_replica_election_constraint
+            .unwrap();
+        assert!(constraint.enabled);
+        assert_eq!(constraint.node_labels_anti_affinity.unwrap(), vec![
+            "kubernetes.io/hostname".to_string()
+        ]);
+    }
+
+    #[test]
+    fn test_pooler_scaling() {
+        let cdb_yaml = r#"
+        apiVersion: coredb.io/v1alpha1
+        kind: CoreDB
+        metadata:
+          name: test
+          namespace: default
+          uid: 752d59ef-2671-4890-9feb-0097459b18c8
+        spec:
+          connectionPooler:
+            enabled: true
+            instances: 3
+            minAvailable: 2
+            autoscaling:
+              maxReplicas: 6
+        "#;
+        let mut cdb: CoreDB = from_str(cdb_yaml).unwrap();
+
+        let pooler = cnpg_pooler(&cdb, "test-pooler", PoolerType::Rw);
+        assert_eq!(pooler.spec.instances, 3);
+
+        let pdb = pooler_pod_disruption_budget(&cdb, "test-pooler").unwrap();
+        let pdb_spec = pdb.spec.unwrap();
+        assert_eq!(pdb_spec.min_available, Some(IntOrString::Int(2)));
+        assert_eq!(
+            pdb_spec.selector.unwrap().match_labels.unwrap()["cnpg.io/poolerName"],
+            "test-pooler"
+        );
+
+        let hpa = pooler_horizontal_pod_autoscaler(&cdb, "test-pooler").unwrap();
+        let hpa_spec = hpa.spec.unwrap();
+        assert_eq!(
+            hpa_spec.scale_target_ref.api_version,
+            Some("postgresql.cnpg.io/v1".to_string())
+        );
+        assert_eq!(hpa_spec.scale_target_ref.kind, "Pooler");
+        assert_eq!(hpa_spec.scale_target_ref.name, "test-pooler");
+        assert_eq!(hpa_spec.min_replicas, Some(1));
+        assert_eq!(hpa_spec.max_replicas, 6);
+        let target = hpa_spec.metrics.unwrap()[0].resource.clone().unwrap().target;
+        assert_eq!(target.average_utilization, Some(80));
+
+        // The autoscaler owns the instances of the Pooler
+        let patch = pooler_patch(&cdb, &pooler);
+        assert!(patch["spec"].get("instances").is_none());
+        assert_eq!(patch["spec"]["cluster"]["name"], "test");
+
+        // A hibernated instance has no pooler instances and no autoscaling
+        cdb.spec.stop = true;
+        let pooler = cnpg_pooler(&cdb, "test-pooler", PoolerType::Rw);
+        assert_eq!(pooler.spec.instances, 0);
+        assert_eq!(pooler_patch(&cdb, &pooler)["spec"]["instances"], 0);
+        assert!(pooler_horizontal_pod_autoscaler(&cdb, "test-pooler").is_none());
+        // The instances are taken back from the autoscaler
+        assert!(pooler_patch_params().force);
+
+        // Without autoscaling, the Pooler goes back to the instances of the spec
+        cdb.spec.stop = false;
+        cdb.spec.connectionPooler.autoscaling = None;
+        let pooler = cnpg_pooler(&cdb, "test-pooler", PoolerType::Rw);
+        assert_eq!(pooler_patch(&cdb, &pooler)["spec"]["instances"], 3);
+        assert!(pooler_horizontal_pod_autoscaler(&cdb, "test-pooler").is_none());
+
+        cdb.spec.connectionPooler.minAvailable = None;
+        assert!(pooler_pod_disruption_budget(&cdb, "test-pooler").is_none());
+    }
+
+    #[test]
+    fn test_parse_storage_size() {
+        assert_eq!(
+            parse_storage_size(&Quantity("10Gi".to_string())).unwrap(),
+            10_f64 * 1024_f64.powi(3)
+        );
+        assert_eq!(parse_storage_size(&Quantity("500M".to_string())).unwrap(), 500e6);
+        assert_eq!(
+            parse_storage_size(&Quantity("1024".to_string())).unwrap(),
+            1024_f64
+        );
+        assert!(parse_storage_size(&Quantity("Gi".to_string())).is_err());
+        assert!(parse_storage_size(&Quantity("ten gigs".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_cnpg_cluster_storage() {
+        let cdb_yaml = r#"
+        apiVersion: coredb.io/v1alpha1
+        kind: CoreDB
+        metadata:
+          name: test
+          namespace: default
+          uid: 752d59ef-2671-4890-9feb-0097459b18c8
+        spec:
+          storage: 10Gi
+          storageClass: gp3-enc
+          walStorage:
+            size: 2Gi
+        "#;
+        let cdb: CoreDB = from_str(cdb_yaml).unwrap();
+        let mut cluster = cnpg_cluster_from_cdb(&cdb, None, BTreeMap::new(), BTreeMap::new());
+
+        let storage = cluster.spec.storage.clone().unwrap();
+        assert_eq!(storage.size.unwrap(), "10Gi");
+        assert_eq!(storage.storage_class.unwrap(), "gp3-enc");
+        // The WAL volume falls back to the storage class of the data volume
+        let wal_storage = cluster.spec.wal_storage.clone().unwrap();
+        assert_eq!(wal_storage.size.unwrap(), "2Gi");
+        assert_eq!(wal_storage.storage_class.unwrap(), "gp3-enc");
+
+        // Growing a volume is allowed
+        let mut current_cluster = cluster.clone();
+        current_cluster.spec.storage.as_mut().unwrap().size = Some("5Gi".to_string());
+        assert!(keep_current_volume_sizes(&mut cluster, &current_cluster).is_empty());
+        assert_eq!(
+            cluster.spec.storage.as_ref().unwrap().size.as_deref(),
+            Some("10Gi")
+        );
+
+        // Shrinking keeps the current size
+        current_cluster.spec.storage.as_mut().unwrap().size = Some("20Gi".to_string());
+        current_cluster.spec.wal_storage.as_mut().unwrap().size = Some("4Gi".to_string());
+        let rejected = keep_current_volume_sizes(&mut cluster, &current_cluster);
+        assert_eq!(rejected, vec![
+            "storage can not be shrunk from 20Gi to 10Gi".to_string(),
+            "walStorage can not be shrunk from 4Gi to 2Gi".to_string(),
+        ]);
+        assert_eq!(
+            cluster.spec.storage.as_ref().unwrap().size.as_deref(),
+            Some("20Gi")
+        );
+        assert_eq!(
+            cluster.spec.wal_storage.as_ref().unwrap().size.as_deref(),
+            Some("4Gi")
+        );
+
+        // The WAL volume can not be removed
+        cluster.spec.wal_storage = None;
+        let rejected = keep_current_volume_sizes(&mut cluster, &current_cluster);
+        assert_eq!(rejected.len(), 1);
+        assert!(cluster.spec.wal_storage.is_some());
+    }
+
     #[test]
     fn test_get_fenced_instances_from_annotations() {
         // Annotation exists and is valid
@@ -2337,4 +3719,47 @@ mod tests {
         let result = parse_target_time(Some("invalid-format"));
         assert!(result.is_err()); // check for error
     }
+
+    #[test]
+    fn test_cnpg_postgres_config_unloads_uninstalled_libraries() {
+        let trunk_install_status = |name: &str| TrunkInstallStatus {
+            name: name.to_string(),
+            version: Some("1.0.0".to_string()),
+            error: false,
+            loading: false,
+            error_message: None,
+            installed_to_pods: None,
+            attempts: None,
+        };
+        // pg_partman was removed from the trunk installs, while pg_cron is still enabled
+        let mut cdb = CoreDB::new("test", CoreDBSpec {
+            extensions: vec![Extension {
+                name: "pg_cron".to_string(),
+                description: None,
+                locations: vec![ExtensionInstallLocation {
+                    enabled: true,
+                    ..ExtensionInstallLocation::default()
+                }],
+            }],
+            runtime_config: Some(vec![PgConfig {
+                name: "shared_preload_libraries".to_string(),
+                value: ConfigValue::Multiple(BTreeSet::from(["pg_partman_bgw".to_string()])),
+            }]),
+            ..CoreDBSpec::default()
+        });
+        cdb.status = Some(CoreDBStatus {
+            trunk_installs: Some(vec![
+                trunk_install_status("pg_cron"),
+                trunk_install_status("pg_partman"),
+            ]),
+            ..CoreDBStatus::default()
+        });
+        let requires_load = BTreeMap::from([
+            ("pg_cron".to_string(), "pg_cron".to_string()),
+            ("pg_partman".to_string(), "pg_partman_bgw".to_string()),
+        ]);
+
+        let config = cnpg_postgres_config(&cdb, requires_load, BTreeMap::new()).unwrap();
+        assert_eq!(config.shared_preload_libraries, Some(vec!["pg_cron".to_string()]));
+    }
 }
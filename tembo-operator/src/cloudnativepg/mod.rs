@@ -1,5 +1,8 @@
+pub(crate) mod backup_catalog;
 pub mod backups;
 pub mod clusters;
 pub(crate) mod cnpg;
+pub(crate) mod on_demand_backups;
 pub mod poolers;
+pub(crate) mod restore_validation;
 mod scheduledbackups;
@@ -0,0 +1,273 @@
+use crate::{
+    apis::coredb_types::{CoreDB, OnDemandBackupStatus},
+    cloudnativepg::backups::{Backup, BackupCluster, BackupSpec},
+    publish_event, Context, BACKUP_REQUESTED_AT,
+};
+use chrono::{DateTime, Utc};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use kube::{
+    api::{Patch, PatchParams},
+    runtime::{controller::Action, events::EventType},
+    Api, Resource, ResourceExt,
+};
+use std::{sync::Arc, time::Duration};
+use tracing::{debug, error, info, instrument, warn};
+
+// Phases of a CNPG Backup that don't change anymore
+const BACKUP_COMPLETED: &str = "completed";
+const BACKUP_FAILED: &str = "failed";
+
+// Only the latest on-demand backups are kept in the status
+const MAX_BACKUPS_IN_STATUS: usize = 10;
+
+// The name of the Backup is derived from the request, so it is only created once
+fn backup_name(cdb: &CoreDB, requested_at: &str) -> Option<String> {
+    let requested_at = DateTime::parse_from_rfc3339(requested_at).ok()?;
+    Some(format!(
+        "{}-{}",
+        cdb.name_any(),
+        requested_at.with_timezone(&Utc).format("%Y%m%d%H%M%S")
+    ))
+}
+
+// Generate an on-demand Backup of the CNPG cluster
+fn cnpg_backup(cdb: &CoreDB, name: &str) -> Backup {
+    let oref = cdb.controller_owner_ref(&()).unwrap();
+    Backup {
+        metadata: ObjectMeta {
+            name: Some(name.to_string()),
+            namespace: cdb.namespace(),
+            owner_references: Some(vec![oref]),
+            ..ObjectMeta::default()
+        },
+        spec: BackupSpec {
+            cluster: Some(BackupCluster { name: cdb.name_any() }),
+            target: None,
+        },
+        status: None,
+    }
+}
+
+fn is_finished(backup: &OnDemandBackupStatus) -> bool {
+    matches!(
+        backup.phase.as_deref(),
+        Some(BACKUP_COMPLETED) | Some(BACKUP_FAILED)
+    )
+}
+
+fn parse_timestamp(timestamp: &Option<String>) -> Option<DateTime<Utc>> {
+    timestamp
+        .as_deref()
+        .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+}
+
+// Copy the progress of a CNPG Backup into the status of the on-demand backup
+fn backup_status_from_cnpg(backup: &OnDemandBackupStatus, cnpg_backup: &Backup) -> OnDemandBackupStatus {
+    let Some(status) = &cnpg_backup.status else {
+        return backup.clone();
+    };
+    OnDemandBackupStatus {
+        name: backup.name.clone(),
+        requested_at: backup.requested_at.clone(),
+        phase: status.phase.clone(),
+        begin_wal: status.begin_wal.clone(),
+        end_wal: status.end_wal.clone(),
+        started_at: parse_timestamp(&status.started_at),
+        stopped_at: parse_timestamp(&status.stopped_at),
+        error: status.error.clone(),
+    }
+}
+
+/// Create a CNPG Backup when a new one is requested with the backup annotation, and follow the
+/// backups that are in progress. Returns the on-demand backups to report in the status.
+#[instrument(skip(cdb, ctx), fields(trace_id, instance_name = %cdb.name_any()))]
+pub async fn reconcile_on_demand_backups(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+) -> Result<Option<Vec<OnDemandBackupStatus>>, Action> {
+    let mut backups = cdb
+        .status
+        .as_ref()
+        .and_then(|status| status.backups.clone())
+        .unwrap_or_default();
+    let backup_api: Api<Backup> = Api::namespaced(ctx.client.clone(), &cdb.namespace().unwrap());
+
+    if let Some(requested_at) = cdb.annotations().get(BACKUP_REQUESTED_AT) {
+        if !backups.iter().any(|backup| &backup.requested_at == requested_at) {
+            backups.push(request_backup(cdb, ctx.clone(), &backup_api, requested_at).await?);
+        }
+    }
+
+    for backup in backups.iter_mut().filter(|backup| !is_finished(backup)) {
+        let cnpg_backup = backup_api.get_opt(&backup.name).await.map_err(|e| {
+            error!("Error getting Backup {}: {}", backup.name, e);
+            Action::requeue(Duration::from_secs(30))
+        })?;
+        *backup = match cnpg_backup {
+            Some(cnpg_backup) => backup_status_from_cnpg(backup, &cnpg_backup),
+            None => OnDemandBackupStatus {
+                phase: Some(BACKUP_FAILED.to_string()),
+                error: Some("The Backup was deleted before it finished".to_string()),
+                ..backup.clone()
+            },
+        };
+        match backup.phase.as_deref() {
+            Some(BACKUP_COMPLETED) => {
+                info!("Backup {} of {} completed", backup.name, cdb.name_any());
+                publish_event(
+                    cdb,
+                    ctx.clone(),
+                    EventType::Normal,
+                    "BackupCompleted",
+                    format!("Backup {} completed", backup.name),
+                )
+                .await;
+            }
+            Some(BACKUP_FAILED) => {
+                warn!(
+                    "Backup {} of {} failed: {}",
+                    backup.name,
+                    cdb.name_any(),
+                    backup.error.clone().unwrap_or_default()
+                );
+                publish_event(
+                    cdb,
+                    ctx.clone(),
+                    EventType::Warning,
+                    "BackupFailed",
+                    format!(
+                        "Backup {} failed: {}",
+                        backup.name,
+                        backup.error.clone().unwrap_or_default()
+                    ),
+                )
+                .await;
+            }
+            phase => debug!("Backup {} is in phase {:?}", backup.name, phase),
+        }
+    }
+
+    if backups.len() > MAX_BACKUPS_IN_STATUS {
+        backups.drain(..backups.len() - MAX_BACKUPS_IN_STATUS);
+    }
+    Ok((!backups.is_empty()).then_some(backups))
+}
+
+async fn request_backup(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    backup_api: &Api<Backup>,
+    requested_at: &str,
+) -> Result<OnDemandBackupStatus, Action> {
+    let mut backup = OnDemandBackupStatus {
+        name: String::new(),
+        requested_at: requested_at.to_string(),
+        phase: None,
+        begin_wal: None,
+        end_wal: None,
+        started_at: None,
+        stopped_at: None,
+        error: None,
+    };
+    // An invalid request is reported once in the status, instead of on every reconcile
+    let Some(name) = backup_name(cdb, requested_at) else {
+        warn!(
+            "Invalid {} annotation on {}: {}",
+            BACKUP_REQUESTED_AT,
+            cdb.name_any(),
+            requested_at
+        );
+        backup.phase = Some(BACKUP_FAILED.to_string());
+        backup.error = Some(format!(
+            "The {} annotation should be an RFC 3339 timestamp",
+            BACKUP_REQUESTED_AT
+        ));
+        return Ok(backup);
+    };
+
+    debug!("Applying Backup {}", name);
+    let ps = PatchParams::apply("cntrlr");
+    backup_api
+        .patch(&name, &ps, &Patch::Apply(&cnpg_backup(cdb, &name)))
+        .await
+        .map_err(|e| {
+            error!("Error patching Backup {}: {}", name, e);
+            Action::requeue(Duration::from_secs(30))
+        })?;
+    publish_event(
+        cdb,
+        ctx,
+        EventType::Normal,
+        "BackupRequested",
+        format!("Requested backup {}", name),
+    )
+    .await;
+    backup.name = name;
+    Ok(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{apis::coredb_types::CoreDBSpec, cloudnativepg::backups::BackupStatus};
+
+    fn requested_backup(name: &str) -> OnDemandBackupStatus {
+        OnDemandBackupStatus {
+            name: name.to_string(),
+            requested_at: "2023-09-26T21:15:42Z".to_string(),
+            phase: None,
+            begin_wal: None,
+            end_wal: None,
+            started_at: None,
+            stopped_at: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_backup_name() {
+        let cdb = CoreDB::new("test", CoreDBSpec::default());
+        assert_eq!(
+            backup_name(&cdb, "2023-09-26T21:15:42Z"),
+            Some("test-20230926211542".to_string())
+        );
+        assert_eq!(
+            backup_name(&cdb, "2023-09-26T23:15:42+02:00"),
+            Some("test-20230926211542".to_string())
+        );
+        assert_eq!(backup_name(&cdb, "now"), None);
+    }
+
+    #[test]
+    fn test_backup_status_from_cnpg() {
+        let backup = requested_backup("test-20230926211542");
+        let mut cnpg_backup = Backup::new("test-20230926211542", BackupSpec::default());
+
+        // Not started yet
+        assert_eq!(backup_status_from_cnpg(&backup, &cnpg_backup), backup);
+        assert!(!is_finished(&backup));
+
+        cnpg_backup.status = Some(BackupStatus {
+            phase: Some("completed".to_string()),
+            begin_wal: Some("000000010000000000000002".to_string()),
+            end_wal: Some("000000010000000000000003".to_string()),
+            started_at: Some("2023-09-26T21:15:45Z".to_string()),
+            stopped_at: Some("2023-09-26T21:16:02Z".to_string()),
+            ..BackupStatus::default()
+        });
+        let updated = backup_status_from_cnpg(&backup, &cnpg_backup);
+        assert!(is_finished(&updated));
+        assert_eq!(updated.requested_at, backup.requested_at);
+        assert_eq!(updated.begin_wal.as_deref(), Some("000000010000000000000002"));
+        assert_eq!(updated.end_wal.as_deref(), Some("000000010000000000000003"));
+        assert_eq!(
+            updated.started_at,
+            Some("2023-09-26T21:15:45Z".parse::<DateTime<Utc>>().unwrap())
+        );
+        assert_eq!(
+            updated.stopped_at,
+            Some("2023-09-26T21:16:02Z".parse::<DateTime<Utc>>().unwrap())
+        );
+    }
+}
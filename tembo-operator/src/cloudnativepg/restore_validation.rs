@@ -0,0 +1,691 @@
+use crate::{
+    apis::coredb_types::{CoreDB, Restore, S3Credentials},
+    cloudnativepg::cnpg::{cnpg_cluster_bootstrap_from_cdb, parse_target_time},
+    conditions::{requeue_with_condition, set_condition, RESTORE_READY},
+    secret::fetch_decoded_data_key_from_secret,
+    Context, Error,
+};
+use aws_sdk_s3::{
+    config::{Credentials, Region},
+    types::Object,
+    Client,
+};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use k8s_openapi::{api::core::v1::Secret, apimachinery::pkg::apis::meta::v1::Condition};
+use kube::{runtime::controller::Action, Api, ResourceExt};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tracing::{debug, info, instrument, warn};
+
+// Used when the restore credentials don't have a region, S3 compatible stores ignore it
+const DEFAULT_REGION: &str = "us-east-1";
+
+// Barman status of a base backup that completed
+const BACKUP_DONE: &str = "DONE";
+
+/// Where barman stores the backups of the instance to restore from
+#[derive(Debug, PartialEq)]
+struct RestoreSource {
+    bucket: String,
+    prefix: String,
+}
+
+/// A base backup found in the object store, read from its backup.info file
+#[derive(Debug, PartialEq)]
+pub(crate) struct BaseBackup {
+    pub(crate) backup_id: Option<String>,
+    pub(crate) status: Option<String>,
+    pub(crate) end_time: Option<DateTime<Utc>>,
+    // Size of the base backup in bytes
+    pub(crate) size: Option<i64>,
+}
+
+/// What the object store holds for the instance to restore from
+#[derive(Debug, Default, PartialEq)]
+struct RestoreCatalog {
+    base_backups: Vec<BaseBackup>,
+    last_wal_archived_at: Option<DateTime<Utc>>,
+}
+
+// The restore reads from the same object store as the bootstrap of the Cluster
+fn restore_source(cdb: &CoreDB) -> Option<RestoreSource> {
+    let (_, external_clusters, _) = cnpg_cluster_bootstrap_from_cdb(cdb);
+    let object_store = external_clusters?
+        .into_iter()
+        .find(|cluster| cluster.name == "tembo-recovery")?
+        .barman_object_store?;
+    let path = object_store.destination_path.strip_prefix("s3://")?;
+    let (bucket, path) = path.split_once('/').unwrap_or((path, ""));
+    if bucket.is_empty() {
+        return None;
+    }
+    // Barman stores the backups of every server in a directory named after it
+    let server_name = object_store.server_name?;
+    let prefix = match path.trim_matches('/') {
+        "" => server_name,
+        path => format!("{}/{}", path, server_name),
+    };
+    Some(RestoreSource {
+        bucket: bucket.to_string(),
+        prefix,
+    })
+}
+
+// Barman writes times like `2023-09-26 21:16:02.123456+00:00`, and `None` when there is no value
+fn parse_barman_time(time: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S%.f%:z")
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+pub(crate) fn parse_backup_info(content: &str) -> BaseBackup {
+    let values: HashMap<&str, &str> = content
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .collect();
+    BaseBackup {
+        backup_id: values.get("backup_id").map(|id| id.to_string()),
+        status: values.get("status").map(|status| status.to_string()),
+        end_time: values.get("end_time").and_then(|time| parse_barman_time(time)),
+        size: values.get("size").and_then(|size| size.parse().ok()),
+    }
+}
+
+// The recovery target time, in the same formats accepted for the bootstrap of the Cluster. Times
+// without a time zone are in UTC, like the Postgres of the Cluster.
+fn recovery_target_time(restore: &Restore) -> Result<Option<DateTime<Utc>>, String> {
+    let parsed = parse_target_time(restore.recovery_target_time.as_deref()).map_err(|err| {
+        format!(
+            "Invalid recovery target time {}: {}",
+            restore.recovery_target_time.clone().unwrap_or_default(),
+            err
+        )
+    })?;
+    Ok(parsed.and_then(|time| {
+        DateTime::parse_from_str(&time, "%Y-%m-%d %H:%M:%S%.f%#z")
+            .map(|time| time.with_timezone(&Utc))
+            .ok()
+            .or_else(|| {
+                NaiveDateTime::parse_from_str(&time, "%Y-%m-%d %H:%M:%S")
+                    .ok()
+                    .map(|time| Utc.from_utc_datetime(&time))
+            })
+    }))
+}
+
+/// Check that there is a completed base backup to restore from, and that the base backup and the
+/// archived WAL cover the recovery target time
+fn check_restore(catalog: &RestoreCatalog, target_time: Option<DateTime<Utc>>) -> Result<(), String> {
+    let completed: Vec<&BaseBackup> = catalog
+        .base_backups
+        .iter()
+        .filter(|backup| backup.status.as_deref() == Some(BACKUP_DONE))
+        .collect();
+    if completed.is_empty() {
+        return Err("No completed base backup found to restore from".to_string());
+    }
+    // Without a target time, everything that was archived is replayed
+    let Some(target_time) = target_time else {
+        return Ok(());
+    };
+
+    let covered = completed
+        .iter()
+        .any(|backup| backup.end_time.is_some_and(|end_time| end_time <= target_time));
+    if !covered {
+        let oldest = completed.iter().filter_map(|backup| backup.end_time).min();
+        return Err(match oldest {
+            Some(oldest) => format!(
+                "The recovery target time {} is before the end of the oldest completed base backup at {}",
+                target_time, oldest
+            ),
+            None => format!(
+                "No completed base backup with an end time found to recover to {}",
+                target_time
+            ),
+        });
+    }
+
+    match catalog.last_wal_archived_at {
+        None => Err(format!("No archived WAL found to recover to {}", target_time)),
+        Some(last_wal_archived_at) if last_wal_archived_at < target_time => Err(format!(
+            "The recovery target time {} is after the last archived WAL at {}",
+            target_time, last_wal_archived_at
+        )),
+        Some(_) => Ok(()),
+    }
+}
+
+// Backups the operator can't read are restored without validation
+fn validation_skipped_reason(restore: &Restore) -> Option<&'static str> {
+    if restore.google_credentials.is_some() || restore.azure_credentials.is_some() {
+        return Some("Only backups in S3 are validated");
+    }
+    // The IAM role of the instance can't be assumed by the operator
+    let s3_credentials = restore.s3_credentials.clone().unwrap_or_default();
+    if s3_credentials.inherit_from_iam_role == Some(true)
+        || s3_credentials.access_key_id.is_none()
+        || s3_credentials.secret_access_key.is_none()
+    {
+        return Some("Backups read with the IAM role of the instance are not validated");
+    }
+    None
+}
+
+async fn s3_client(region: String, credentials: Option<Credentials>, endpoint_url: Option<&str>) -> Client {
+    let mut loader = aws_config::from_env().region(Region::new(region));
+    if let Some(credentials) = credentials {
+        loader = loader.credentials_provider(credentials);
+    }
+    let sdk_config = loader.load().await;
+    let mut config = aws_sdk_s3::config::Builder::from(&sdk_config);
+    if let Some(endpoint_url) = endpoint_url {
+        // S3 compatible stores like MinIO address buckets by path instead of by subdomain
+        config = config.endpoint_url(endpoint_url).force_path_style(true);
+    }
+    Client::from_conf(config.build())
+}
+
+// Credentials are read from the secrets referenced in the CoreDB, without access keys the client
+// falls back to the credentials of the operator
+pub(crate) async fn object_store_client(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    s3_credentials: &S3Credentials,
+    endpoint_url: Option<&str>,
+) -> Result<Client, Error> {
+    let secrets: Api<Secret> = Api::namespaced(ctx.client.clone(), &cdb.namespace().unwrap());
+    let region = match &s3_credentials.region {
+        Some(region) => {
+            fetch_decoded_data_key_from_secret(secrets.clone(), region.name.clone(), &region.key).await?
+        }
+        None => DEFAULT_REGION.to_string(),
+    };
+    let credentials = match (&s3_credentials.access_key_id, &s3_credentials.secret_access_key) {
+        (Some(access_key_id), Some(secret_access_key)) => {
+            let session_token = match &s3_credentials.session_token {
+                Some(token) => Some(
+                    fetch_decoded_data_key_from_secret(secrets.clone(), token.name.clone(), &token.key)
+                        .await?,
+                ),
+                None => None,
+            };
+            Some(Credentials::new(
+                fetch_decoded_data_key_from_secret(
+                    secrets.clone(),
+                    access_key_id.name.clone(),
+                    &access_key_id.key,
+                )
+                .await?,
+                fetch_decoded_data_key_from_secret(
+                    secrets.clone(),
+                    secret_access_key.name.clone(),
+                    &secret_access_key.key,
+                )
+                .await?,
+                session_token,
+                None,
+                "coredb",
+            ))
+        }
+        _ => None,
+    };
+    Ok(s3_client(region, credentials, endpoint_url).await)
+}
+
+// List the objects and the common prefixes under a prefix, following every page
+async fn list_objects(
+    client: &Client,
+    bucket: &str,
+    prefix: &str,
+    delimiter: Option<&str>,
+) -> Result<(Vec<Object>, Vec<String>), Error> {
+    let mut objects = vec![];
+    let mut prefixes = vec![];
+    let mut continuation_token = None;
+    loop {
+        let output = client
+            .list_objects_v2()
+            .bucket(bucket)
+            .prefix(prefix)
+            .set_delimiter(delimiter.map(str::to_string))
+            .set_continuation_token(continuation_token)
+            .send()
+            .await
+            .map_err(|e| {
+                Error::ObjectStoreError(format!("Failed to list s3://{}/{}: {}", bucket, prefix, e))
+            })?;
+        objects.extend(output.contents().unwrap_or_default().iter().cloned());
+        prefixes.extend(
+            output
+                .common_prefixes()
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|common_prefix| common_prefix.prefix().map(str::to_string)),
+        );
+        continuation_token = output.next_continuation_token().map(str::to_string);
+        if !output.is_truncated() || continuation_token.is_none() {
+            return Ok((objects, prefixes));
+        }
+    }
+}
+
+pub(crate) async fn get_object(client: &Client, bucket: &str, key: &str) -> Result<String, Error> {
+    let output = client
+        .get_object()
+        .bucket(bucket)
+        .key(key)
+        .send()
+        .await
+        .map_err(|e| Error::ObjectStoreError(format!("Failed to get s3://{}/{}: {}", bucket, key, e)))?;
+    let body = output
+        .body
+        .collect()
+        .await
+        .map_err(|e| Error::ObjectStoreError(format!("Failed to read s3://{}/{}: {}", bucket, key, e)))?;
+    Ok(String::from_utf8_lossy(&body.into_bytes()).into_owned())
+}
+
+// Read the base backups and the time of the last archived WAL of the server
+async fn fetch_restore_catalog(client: &Client, source: &RestoreSource) -> Result<RestoreCatalog, Error> {
+    let (objects, _) =
+        list_objects(client, &source.bucket, &format!("{}/base/", source.prefix), None).await?;
+    let mut base_backups = vec![];
+    for key in objects
+        .iter()
+        .filter_map(|object| object.key())
+        .filter(|key| key.ends_with("/backup.info"))
+    {
+        base_backups.push(parse_backup_info(&get_object(client, &source.bucket, key).await?));
+    }
+
+    // WAL is stored in one directory per timeline and log, the latest one sorts last
+    let (_, wal_directories) = list_objects(
+        client,
+        &source.bucket,
+        &format!("{}/wals/", source.prefix),
+        Some("/"),
+    )
+    .await?;
+    let last_wal_archived_at = match wal_directories.iter().max() {
+        None => None,
+        Some(wal_directory) => {
+            let (wals, _) = list_objects(client, &source.bucket, wal_directory, None).await?;
+            wals.iter()
+                .filter_map(|wal| wal.last_modified())
+                .filter_map(|last_modified| {
+                    Utc.timestamp_opt(last_modified.secs(), last_modified.subsec_nanos())
+                        .single()
+                })
+                .max()
+        }
+    };
+    Ok(RestoreCatalog {
+        base_backups,
+        last_wal_archived_at,
+    })
+}
+
+/// Before the Cluster is bootstrapped from a backup, check that the object store has a completed
+/// base backup and archived WAL that cover the recovery target time. A restore from a missing
+/// backup produces a Cluster that never comes up, so the Cluster is not created until the checks
+/// pass, and the problem is reported in the RestoreReady condition.
+#[instrument(skip(cdb, ctx, conditions), fields(trace_id, instance_name = %cdb.name_any()))]
+pub async fn validate_restore(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    conditions: &mut Vec<Condition>,
+) -> Result<(), Action> {
+    let Some(restore) = &cdb.spec.restore else {
+        return Ok(());
+    };
+    if let Some(reason) = validation_skipped_reason(restore) {
+        info!(
+            "Not validating the backups to restore {} from: {}",
+            cdb.name_any(),
+            reason
+        );
+        set_condition(
+            conditions,
+            RESTORE_READY,
+            true,
+            "RestoreValidationSkipped",
+            reason,
+            cdb.metadata.generation,
+        );
+        return Ok(());
+    }
+    let result = match (recovery_target_time(restore), restore_source(cdb)) {
+        (Err(message), _) => Err(("InvalidRecoveryTargetTime", message)),
+        (Ok(_), None) => Err((
+            "InvalidRestoreSource",
+            "The backups to restore from are not in an S3 bucket".to_string(),
+        )),
+        (Ok(target_time), Some(source)) => {
+            debug!(
+                "Checking the backups in s3://{}/{} for {}",
+                source.bucket,
+                source.prefix,
+                cdb.name_any()
+            );
+            let s3_credentials = restore.s3_credentials.clone().unwrap_or_default();
+            let client =
+                object_store_client(cdb, ctx.clone(), &s3_credentials, restore.endpoint_url.as_deref()).await;
+            let catalog = match client {
+                Ok(client) => fetch_restore_catalog(&client, &source).await,
+                Err(e) => Err(e),
+            };
+            match catalog {
+                Ok(catalog) => check_restore(&catalog, target_time)
+                    .map_err(|message| ("RestoreValidationFailed", message)),
+                Err(e) => Err((
+                    "RestoreSourceUnavailable",
+                    format!("Failed to read the backups to restore from: {}", e),
+                )),
+            }
+        }
+    };
+
+    match result {
+        Ok(()) => {
+            info!("Validated the backups to restore {} from", cdb.name_any());
+            set_condition(
+                conditions,
+                RESTORE_READY,
+                true,
+                "RestoreValidated",
+                "",
+                cdb.metadata.generation,
+            );
+            Ok(())
+        }
+        Err((reason, message)) => {
+            warn!("Not restoring {}: {}", cdb.name_any(), message);
+            Err(requeue_with_condition(
+                cdb,
+                ctx,
+                conditions,
+                RESTORE_READY,
+                reason,
+                &message,
+                Action::requeue(Duration::from_secs(300)),
+            )
+            .await)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::coredb_types::{
+        CoreDBSpec, GoogleCredentials, S3Credentials, S3CredentialsAccessKeyId, S3CredentialsSecretAccessKey,
+    };
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    fn time(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    fn restore(recovery_target_time: Option<&str>) -> Restore {
+        Restore {
+            server_name: "org-test-inst-source".to_string(),
+            recovery_target_time: recovery_target_time.map(str::to_string),
+            endpoint_url: None,
+            s3_credentials: None,
+            google_credentials: None,
+            azure_credentials: None,
+        }
+    }
+
+    fn completed_backup(end_time: &str) -> BaseBackup {
+        BaseBackup {
+            backup_id: Some("20230926T211545".to_string()),
+            status: Some(BACKUP_DONE.to_string()),
+            end_time: Some(time(end_time)),
+            size: None,
+        }
+    }
+
+    #[test]
+    fn test_restore_source() {
+        let mut cdb = CoreDB::new("org-test-inst-restored", CoreDBSpec {
+            restore: Some(restore(None)),
+            ..CoreDBSpec::default()
+        });
+        cdb.spec.backup.destinationPath =
+            Some("s3://tembo-backups/coredb/org-test-inst-restored".to_string());
+        assert_eq!(
+            restore_source(&cdb),
+            Some(RestoreSource {
+                bucket: "tembo-backups".to_string(),
+                prefix: "coredb/org-test-inst-source/org-test-inst-source".to_string(),
+            })
+        );
+
+        cdb.spec.backup.destinationPath = Some("/backups/org-test-inst-restored".to_string());
+        assert_eq!(restore_source(&cdb), None);
+    }
+
+    #[test]
+    fn test_validation_skipped_reason() {
+        let mut restore = restore(None);
+        restore.s3_credentials = Some(S3Credentials {
+            access_key_id: Some(S3CredentialsAccessKeyId {
+                name: "restore-s3".to_string(),
+                key: "ACCESS_KEY_ID".to_string(),
+            }),
+            secret_access_key: Some(S3CredentialsSecretAccessKey {
+                name: "restore-s3".to_string(),
+                key: "ACCESS_SECRET_KEY".to_string(),
+            }),
+            ..S3Credentials::default()
+        });
+        assert_eq!(validation_skipped_reason(&restore), None);
+
+        // The IAM role of the instance is inherited
+        restore.s3_credentials.as_mut().unwrap().inherit_from_iam_role = Some(true);
+        assert!(validation_skipped_reason(&restore).is_some());
+        restore.s3_credentials = None;
+        assert!(validation_skipped_reason(&restore).is_some());
+
+        restore.google_credentials = Some(GoogleCredentials::default());
+        assert_eq!(
+            validation_skipped_reason(&restore),
+            Some("Only backups in S3 are validated")
+        );
+    }
+
+    #[test]
+    fn test_parse_backup_info() {
+        let content = "backup_id=20230926T211545\n\
+                       begin_time=2023-09-26 21:15:45.123456+00:00\n\
+                       end_time=2023-09-26 21:16:02+00:00\n\
+                       size=31962013\n\
+                       status=DONE\n";
+        assert_eq!(parse_backup_info(content), BaseBackup {
+            backup_id: Some("20230926T211545".to_string()),
+            status: Some("DONE".to_string()),
+            end_time: Some(time("2023-09-26T21:16:02Z")),
+            size: Some(31962013),
+        });
+
+        let content = "backup_id=20230927T211545\nend_time=None\nstatus=STARTED\n";
+        let backup = parse_backup_info(content);
+        assert_eq!(backup.status.as_deref(), Some("STARTED"));
+        assert_eq!(backup.end_time, None);
+        assert_eq!(backup.size, None);
+    }
+
+    #[test]
+    fn test_recovery_target_time() {
+        assert_eq!(recovery_target_time(&restore(None)), Ok(None));
+        assert_eq!(
+            recovery_target_time(&restore(Some("2023-09-26T21:16:02Z"))),
+            Ok(Some(time("2023-09-26T21:16:02Z")))
+        );
+        assert_eq!(
+            recovery_target_time(&restore(Some("2023-09-26 23:16:02.000000+02:00"))),
+            Ok(Some(time("2023-09-26T21:16:02Z")))
+        );
+        assert_eq!(
+            recovery_target_time(&restore(Some("2023-09-26 21:16:02"))),
+            Ok(Some(time("2023-09-26T21:16:02Z")))
+        );
+        assert!(recovery_target_time(&restore(Some("yesterday"))).is_err());
+    }
+
+    #[test]
+    fn test_check_restore() {
+        let catalog = RestoreCatalog {
+            base_backups: vec![
+                BaseBackup {
+                    backup_id: Some("20230925T211545".to_string()),
+                    status: Some("FAILED".to_string()),
+                    end_time: None,
+                    size: None,
+                },
+                completed_backup("2023-09-26T21:16:02Z"),
+            ],
+            last_wal_archived_at: Some(time("2023-09-27T10:00:00Z")),
+        };
+        assert_eq!(check_restore(&catalog, None), Ok(()));
+        assert_eq!(
+            check_restore(&catalog, Some(time("2023-09-27T09:00:00Z"))),
+            Ok(())
+        );
+        assert!(check_restore(&catalog, Some(time("2023-09-26T12:00:00Z")))
+            .unwrap_err()
+            .contains("is before the end of the oldest completed base backup"));
+        assert!(check_restore(&catalog, Some(time("2023-09-27T11:00:00Z")))
+            .unwrap_err()
+            .contains("is after the last archived WAL"));
+
+        let no_wal = RestoreCatalog {
+            last_wal_archived_at: None,
+            ..catalog
+        };
+        assert_eq!(check_restore(&no_wal, None), Ok(()));
+        assert!(check_restore(&no_wal, Some(time("2023-09-27T09:00:00Z")))
+            .unwrap_err()
+            .contains("No archived WAL"));
+
+        assert_eq!(
+            check_restore(&RestoreCatalog::default(), None),
+            Err("No completed base backup found to restore from".to_string())
+        );
+    }
+
+    fn list_bucket_result(keys: &[(&str, &str)], prefixes: &[&str]) -> String {
+        let contents: String = keys
+            .iter()
+            .map(|(key, last_modified)| {
+                format!(
+                    "<Contents><Key>{}</Key><LastModified>{}</LastModified><Size>16</Size></Contents>",
+                    key, last_modified
+                )
+            })
+            .collect();
+        let common_prefixes: String = prefixes
+            .iter()
+            .map(|prefix| format!("<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>", prefix))
+            .collect();
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+             <ListBucketResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">\
+             <Name>tembo-backups</Name><IsTruncated>false</IsTruncated>{}{}</ListBucketResult>",
+            contents, common_prefixes
+        )
+    }
+
+    // An S3 compatible store, like MinIO, addressed by path
+    #[tokio::test]
+    async fn test_fetch_restore_catalog() {
+        let server = MockServer::start().await;
+        let prefix = "coredb/org-test-inst-source/org-test-inst-source";
+        Mock::given(method("GET"))
+            .and(path("/tembo-backups/"))
+            .and(query_param("prefix", format!("{}/base/", prefix)))
+            .respond_with(ResponseTemplate::new(200).set_body_string(list_bucket_result(
+                &[
+                    (
+                        &format!("{}/base/20230926T211545/backup.info", prefix),
+                        "2023-09-26T21:16:03.000Z",
+                    ),
+                    (
+                        &format!("{}/base/20230926T211545/data.tar.snappy", prefix),
+                        "2023-09-26T21:16:01.000Z",
+                    ),
+                ],
+                &[],
+            )))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/tembo-backups/{}/base/20230926T211545/backup.info",
+                prefix
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "backup_id=20230926T211545\nend_time=2023-09-26 21:16:02+00:00\nstatus=DONE\n",
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/tembo-backups/"))
+            .and(query_param("prefix", format!("{}/wals/", prefix)))
+            .and(query_param("delimiter", "/"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(list_bucket_result(&[], &[
+                    &format!("{}/wals/0000000100000000/", prefix),
+                    &format!("{}/wals/0000000200000000/", prefix),
+                ])),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/tembo-backups/"))
+            .and(query_param(
+                "prefix",
+                format!("{}/wals/0000000200000000/", prefix),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_string(list_bucket_result(
+                &[
+                    (
+                        &format!("{}/wals/0000000200000000/000000020000000000000004.snappy", prefix),
+                        "2023-09-27T09:55:00.000Z",
+                    ),
+                    (
+                        &format!("{}/wals/0000000200000000/000000020000000000000005.snappy", prefix),
+                        "2023-09-27T10:00:00.000Z",
+                    ),
+                ],
+                &[],
+            )))
+            .mount(&server)
+            .await;
+
+        let client = s3_client(
+            DEFAULT_REGION.to_string(),
+            Some(Credentials::new("minio", "minio123", None, None, "test")),
+            Some(&server.uri()),
+        )
+        .await;
+        let source = RestoreSource {
+            bucket: "tembo-backups".to_string(),
+            prefix: prefix.to_string(),
+        };
+        let catalog = fetch_restore_catalog(&client, &source).await.unwrap();
+        assert_eq!(catalog, RestoreCatalog {
+            base_backups: vec![completed_backup("2023-09-26T21:16:02Z")],
+            last_wal_archived_at: Some(time("2023-09-27T10:00:00Z")),
+        });
+        assert_eq!(
+            check_restore(&catalog, Some(time("2023-09-27T09:00:00Z"))),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,265 @@
+use crate::{
+    apis::coredb_types::CoreDB,
+    controller::{patch_cdb_status_merge, publish_event},
+    Context,
+};
+use chrono::Utc;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use kube::{
+    api::{Api, ResourceExt},
+    runtime::{controller::Action, events::EventType},
+};
+use serde_json::json;
+use std::{fmt::Display, sync::Arc, time::Duration};
+use tracing::*;
+
+// Condition types reported in the CoreDB status, one for each stage of the reconciler
+pub const READY: &str = "Ready";
+pub const NETWORK_POLICIES_READY: &str = "NetworkPoliciesReady";
+pub const CERTIFICATES_READY: &str = "CertificatesReady";
+pub const INGRESS_READY: &str = "IngressReady";
+pub const SECRETS_READY: &str = "SecretsReady";
+pub const CLUSTER_READY: &str = "ClusterReady";
+pub const STORAGE_READY: &str = "StorageReady";
+pub const POOLER_READY: &str = "PoolerReady";
+pub const DATABASES_READY: &str = "DatabasesReady";
+pub const EXTENSIONS_READY: &str = "ExtensionsReady";
+pub const TRUNK_INSTALLS_READY: &str = "TrunkInstallsReady";
+pub const BACKUPS_READY: &str = "BackupsReady";
+pub const RESTORE_READY: &str = "RestoreReady";
+pub const METRICS_READY: &str = "MetricsReady";
+
+// Current conditions of the CoreDB, used as the starting point of a reconcile
+pub fn current_conditions(cdb: &CoreDB) -> Vec<Condition> {
+    cdb.status
+        .as_ref()
+        .and_then(|s| s.conditions.clone())
+        .unwrap_or_default()
+}
+
+pub fn get_condition<'a>(conditions: &'a [Condition], type_: &str) -> Option<&'a Condition> {
+    conditions.iter().find(|c| c.type_ == type_)
+}
+
+// Adds or updates a condition. The lastTransitionTime only changes when the status changes.
+pub fn set_condition(
+    conditions: &mut Vec<Condition>,
+    type_: &str,
+    status: bool,
+    reason: &str,
+    message: &str,
+    observed_generation: Option<i64>,
+) {
+    let status = if status { "True" } else { "False" }.to_string();
+    match conditions.iter_mut().find(|c| c.type_ == type_) {
+        Some(condition) => {
+            if condition.status != status {
+                condition.last_transition_time = Time(Utc::now());
+            }
+            condition.status = status;
+            condition.reason = reason.to_string();
+            condition.message = message.to_string();
+            condition.observed_generation = observed_generation;
+        }
+        None => conditions.push(Condition {
+            type_: type_.to_string(),
+            status,
+            reason: reason.to_string(),
+            message: message.to_string(),
+            last_transition_time: Time(Utc::now()),
+            observed_generation,
+        }),
+    }
+    // Keep the order deterministic to avoid needless status updates
+    conditions.sort_by(|a, b| a.type_.cmp(&b.type_));
+}
+
+// Sets the condition and Ready to False with the error message, then patches the status. A Warning
+// event is published when the condition changes, a requeue that keeps failing the same way, like a
+// wait for Postgres to restart, does not publish an event on every reconcile. Returns the requeue
+// action to propagate.
+pub async fn requeue_with_condition(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    conditions: &mut Vec<Condition>,
+    type_: &str,
+    reason: &str,
+    message: &str,
+    action: Action,
+) -> Action {
+    let changed = condition_changed(conditions, type_, reason, message);
+    let generation = cdb.metadata.generation;
+    set_condition(conditions, type_, false, reason, message, generation);
+    set_condition(conditions, READY, false, reason, message, generation);
+    let coredbs: Api<CoreDB> = Api::namespaced(ctx.client.clone(), &cdb.namespace().unwrap());
+    let patch_status = json!({
+        "apiVersion": "coredb.io/v1alpha1",
+        "kind": "CoreDB",
+        "status": {
+            "conditions": conditions
+        }
+    });
+    // Failing to report the condition should not change how we requeue
+    if patch_cdb_status_merge(&coredbs, &cdb.name_any(), patch_status)
+        .await
+        .is_err()
+    {
+        warn!(
+            "Failed to set condition {} to False for {}",
+            type_,
+            cdb.name_any()
+        );
+    }
+    if changed {
+        publish_event(cdb, ctx, EventType::Warning, reason, message.to_string()).await;
+    }
+    action
+}
+
+// Whether setting the condition to False with this reason and message changes it
+fn condition_changed(conditions: &[Condition], type_: &str, reason: &str, message: &str) -> bool {
+    get_condition(conditions, type_)
+        .is_none_or(|c| c.status != "False" || c.reason != reason || c.message != message)
+}
+
+/// Requeues a failed stage of the reconcile after a few minutes, with its condition set to False
+/// and the error appended to the message, see requeue_with_condition
+pub(crate) trait RequeueOnError<T> {
+    async fn or_requeue(
+        self,
+        cdb: &CoreDB,
+        ctx: Arc<Context>,
+        conditions: &mut Vec<Condition>,
+        type_: &str,
+        reason: &str,
+        message: &str,
+    ) -> Result<T, Action>;
+}
+
+impl<T, E: Display> RequeueOnError<T> for Result<T, E> {
+    async fn or_requeue(
+        self,
+        cdb: &CoreDB,
+        ctx: Arc<Context>,
+        conditions: &mut Vec<Condition>,
+        type_: &str,
+        reason: &str,
+        message: &str,
+    ) -> Result<T, Action> {
+        match self {
+            Ok(value) => Ok(value),
+            Err(e) => {
+                let message = format!("{}: {}", message, e);
+                error!("{} for {}", message, cdb.name_any());
+                Err(requeue_with_condition(
+                    cdb,
+                    ctx,
+                    conditions,
+                    type_,
+                    reason,
+                    &message,
+                    Action::requeue(Duration::from_secs(300)),
+                )
+                .await)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_condition() {
+        let mut conditions = vec![];
+        set_condition(
+            &mut conditions,
+            READY,
+            false,
+            "ClusterNotReady",
+            "waiting",
+            Some(1),
+        );
+        set_condition(
+            &mut conditions,
+            CLUSTER_READY,
+            false,
+            "ClusterNotReady",
+            "waiting",
+            Some(1),
+        );
+        assert_eq!(conditions.len(), 2);
+        // Sorted by type
+        assert_eq!(conditions[0].type_, CLUSTER_READY);
+        assert_eq!(conditions[1].type_, READY);
+
+        let transition_time = conditions[0].last_transition_time.clone();
+        // Same status keeps the transition time, but updates the message
+        set_condition(
+            &mut conditions,
+            CLUSTER_READY,
+            false,
+            "ClusterNotReady",
+            "still waiting",
+            Some(2),
+        );
+        let condition = get_condition(&conditions, CLUSTER_READY).unwrap();
+        assert_eq!(condition.last_transition_time, transition_time);
+        assert_eq!(condition.message, "still waiting");
+        assert_eq!(condition.observed_generation, Some(2));
+
+        set_condition(&mut conditions, CLUSTER_READY, true, "ClusterReady", "", Some(2));
+        let condition = get_condition(&conditions, CLUSTER_READY).unwrap();
+        assert_eq!(condition.status, "True");
+        assert_eq!(condition.reason, "ClusterReady");
+        assert!(condition.last_transition_time >= transition_time);
+        assert_eq!(conditions.len(), 2);
+    }
+
+    #[test]
+    fn test_condition_changed() {
+        let mut conditions = vec![];
+        assert!(condition_changed(
+            &conditions,
+            CLUSTER_READY,
+            "PostgresRestarting",
+            "waiting"
+        ));
+
+        set_condition(
+            &mut conditions,
+            CLUSTER_READY,
+            false,
+            "PostgresRestarting",
+            "waiting",
+            Some(1),
+        );
+        assert!(!condition_changed(
+            &conditions,
+            CLUSTER_READY,
+            "PostgresRestarting",
+            "waiting"
+        ));
+        assert!(condition_changed(
+            &conditions,
+            CLUSTER_READY,
+            "ClusterError",
+            "waiting"
+        ));
+        assert!(condition_changed(
+            &conditions,
+            CLUSTER_READY,
+            "PostgresRestarting",
+            "still waiting"
+        ));
+
+        set_condition(&mut conditions, CLUSTER_READY, true, "ClusterReady", "", Some(1));
+        assert!(condition_changed(
+            &conditions,
+            CLUSTER_READY,
+            "PostgresRestarting",
+            "waiting"
+        ));
+    }
+}
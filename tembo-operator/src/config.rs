@@ -3,12 +3,57 @@ use std::env;
 #[derive(Clone, Debug)]
 pub struct Config {
     pub enable_backup: bool,
+    pub enable_native_postgres_client: bool,
+    // Check the object store for the backups to restore from before bootstrapping a Cluster from them
+    pub enable_restore_validation: bool,
+    pub trunk_registry_scheme: String,
+    pub trunk_registry_domain: String,
+    // PEM file with the certificates of a self-hosted registry, in addition to the system ones
+    pub trunk_registry_ca_bundle: Option<String>,
+    // Headers the operator sends to the registry, one `Name: value` per line, like an Authorization
+    // header. They are not passed to trunk install in the pods.
+    pub trunk_registry_headers: Option<String>,
+    // Where to load the list of libraries that require load when the registry can't be reached.
+    // The ConfigMap is given as namespace/name, and the list is read from its `libraries` key.
+    pub trunk_libraries_file: Option<String>,
+    pub trunk_libraries_configmap: Option<String>,
+    // Failed trunk installs are retried after the backoff, doubled on every attempt
+    pub trunk_install_max_attempts: i32,
+    pub trunk_install_backoff_seconds: i64,
+    // The admission and conversion webhooks are served over TLS on their own port
+    pub enable_webhook: bool,
+    pub webhook_port: u16,
+    pub webhook_tls_cert: String,
+    pub webhook_tls_key: String,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             enable_backup: from_env_default("ENABLE_BACKUP", "true").parse().unwrap(),
+            // Query instances over a Postgres connection instead of running psql in the primary pod
+            enable_native_postgres_client: from_env_default("ENABLE_NATIVE_POSTGRES_CLIENT", "false")
+                .parse()
+                .unwrap(),
+            enable_restore_validation: from_env_default("ENABLE_RESTORE_VALIDATION", "true")
+                .parse()
+                .unwrap(),
+            trunk_registry_scheme: from_env_default("TRUNK_REGISTRY_SCHEME", "https"),
+            trunk_registry_domain: from_env_default("TRUNK_REGISTRY_DOMAIN", "registry.pgtrunk.io"),
+            trunk_registry_ca_bundle: from_env_optional("TRUNK_REGISTRY_CA_BUNDLE"),
+            trunk_registry_headers: from_env_optional("TRUNK_REGISTRY_HEADERS"),
+            trunk_libraries_file: from_env_optional("TRUNK_LIBRARIES_FILE"),
+            trunk_libraries_configmap: from_env_optional("TRUNK_LIBRARIES_CONFIGMAP"),
+            trunk_install_max_attempts: from_env_default("TRUNK_INSTALL_MAX_ATTEMPTS", "5")
+                .parse()
+                .unwrap(),
+            trunk_install_backoff_seconds: from_env_default("TRUNK_INSTALL_BACKOFF_SECONDS", "30")
+                .parse()
+                .unwrap(),
+            enable_webhook: from_env_default("ENABLE_WEBHOOK", "false").parse().unwrap(),
+            webhook_port: from_env_default("WEBHOOK_PORT", "8443").parse().unwrap(),
+            webhook_tls_cert: from_env_default("WEBHOOK_TLS_CERT", "/certs/tls.crt"),
+            webhook_tls_key: from_env_default("WEBHOOK_TLS_KEY", "/certs/tls.key"),
         }
     }
 }
@@ -17,3 +62,8 @@ impl Default for Config {
 fn from_env_default(var: &str, default: &str) -> String {
     env::var(var).unwrap_or_else(|_| default.to_owned())
 }
+
+// Source the variable from the env - None if not set or empty
+fn from_env_optional(var: &str) -> Option<String> {
+    env::var(var).ok().filter(|value| !value.is_empty())
+}
@@ -5,16 +5,28 @@ use crate::{
     apis::coredb_types::{CoreDB, CoreDBStatus},
     app_service::manager::reconcile_app_services,
     cloudnativepg::{
+        backup_catalog::{backup_catalog, backup_sizes, get_last_archived_wal, recoverability_window},
         backups::Backup,
-        cnpg::{cnpg_cluster_from_cdb, reconcile_cnpg, reconcile_cnpg_scheduled_backup, reconcile_pooler},
+        cnpg::{
+            cnpg_cluster_from_cdb, pooler_ready_replicas, reconcile_cnpg, reconcile_cnpg_scheduled_backup,
+            reconcile_pooler,
+        },
+        on_demand_backups::reconcile_on_demand_backups,
+    },
+    conditions::{
+        current_conditions, requeue_with_condition, set_condition, RequeueOnError, BACKUPS_READY,
+        CERTIFICATES_READY, CLUSTER_READY, EXTENSIONS_READY, INGRESS_READY, METRICS_READY,
+        NETWORK_POLICIES_READY, POOLER_READY, READY, SECRETS_READY,
     },
     config::Config,
+    databases::reconcile_databases,
     deployment_postgres_exporter::reconcile_prometheus_exporter_deployment,
     exec::{ExecCommand, ExecOutput},
     extensions::database_queries::is_not_restarting,
     heartbeat::reconcile_heartbeat,
     ingress::reconcile_postgres_ing_route_tcp,
     postgres_certificates::reconcile_certificates,
+    postgres_client::{self, PostgresPools},
     psql::{PsqlCommand, PsqlOutput},
     secret::{reconcile_postgres_role_secret, reconcile_secret},
     service::reconcile_prometheus_exporter_service,
@@ -43,7 +55,7 @@ use crate::{
     ingress::{reconcile_extra_postgres_ing_route_tcp, reconcile_ip_allowlist_middleware},
     network_policies::reconcile_network_policies,
     postgres_exporter::reconcile_prom_configmap,
-    trunk::{extensions_that_require_load, reconcile_trunk_configmap},
+    trunk::{extension_configs, extensions_that_require_load, reconcile_trunk_configmap},
 };
 use rand::Rng;
 use serde::Serialize;
@@ -64,6 +76,8 @@ pub struct Context {
     pub diagnostics: Arc<RwLock<Diagnostics>>,
     /// Prometheus metrics
     pub metrics: Metrics,
+    /// Connection pools of the native Postgres client
+    pub postgres_pools: PostgresPools,
 }
 
 #[instrument(skip(ctx, cdb), fields(trace_id))]
@@ -118,17 +132,66 @@ impl CoreDB {
     #[instrument(skip(self, ctx, cfg))]
     async fn reconcile(&self, ctx: Arc<Context>, cfg: &Config) -> Result<Action, Action> {
         let client = ctx.client.clone();
-        let _recorder = ctx.diagnostics.read().await.recorder(client.clone(), self);
         let ns = self.namespace().unwrap();
         let name = self.name_any();
         let coredbs: Api<CoreDB> = Api::namespaced(client.clone(), &ns);
 
-        reconcile_network_policies(ctx.client.clone(), &ns).await?;
+        // Conditions are carried through the reconcile, each stage sets its own condition
+        let generation = self.metadata.generation;
+        let mut conditions = current_conditions(self);
+
+        reconcile_network_policies(ctx.client.clone(), &ns)
+            .await
+            .or_requeue(
+                self,
+                ctx.clone(),
+                &mut conditions,
+                NETWORK_POLICIES_READY,
+                "NetworkPoliciesError",
+                "Error reconciling network policies",
+            )
+            .await?;
+        set_condition(
+            &mut conditions,
+            NETWORK_POLICIES_READY,
+            true,
+            "NetworkPoliciesReady",
+            "",
+            generation,
+        );
 
         // Fetch any metadata we need from Trunk
-        reconcile_trunk_configmap(ctx.client.clone(), &ns).await?;
+        reconcile_trunk_configmap(ctx.client.clone(), &ns, cfg)
+            .await
+            .or_requeue(
+                self,
+                ctx.clone(),
+                &mut conditions,
+                EXTENSIONS_READY,
+                "TrunkMetadataError",
+                "Error reconciling the trunk metadata",
+            )
+            .await?;
 
-        reconcile_certificates(ctx.client.clone(), self, &ns).await?;
+        reconcile_certificates(ctx.client.clone(), self, &ns)
+            .await
+            .or_requeue(
+                self,
+                ctx.clone(),
+                &mut conditions,
+                CERTIFICATES_READY,
+                "CertificatesNotReady",
+                "Error reconciling certificates",
+            )
+            .await?;
+        set_condition(
+            &mut conditions,
+            CERTIFICATES_READY,
+            true,
+            "CertificatesReady",
+            "",
+            generation,
+        );
 
         // Ingress
         match std::env::var("DATA_PLANE_BASEDOMAIN") {
@@ -140,10 +203,15 @@ impl CoreDB {
 
                 let middleware_name = reconcile_ip_allowlist_middleware(self, ctx.clone())
                     .await
-                    .map_err(|e| {
-                        error!("Error reconciling MiddlewareTCP for {}: {:?}", name, e);
-                        Action::requeue(Duration::from_secs(300))
-                    })?;
+                    .or_requeue(
+                        self,
+                        ctx.clone(),
+                        &mut conditions,
+                        INGRESS_READY,
+                        "IngressError",
+                        "Error reconciling MiddlewareTCP",
+                    )
+                    .await?;
 
                 let service_name_read_write = format!("{}-rw", self.name_any().as_str());
                 let prefix_read_write = format!("{}-rw-", self.name_any().as_str());
@@ -159,13 +227,42 @@ impl CoreDB {
                     vec![middleware_name.clone()],
                 )
                 .await
-                .map_err(|e| {
-                    error!("Error reconciling postgres ingress route: {:?}", e);
-                    // For unexpected errors, we should requeue for several minutes at least,
-                    // for expected, "waiting" type of requeuing, those should be shorter, just a few seconds.
-                    // IngressRouteTCP does not have expected errors during reconciliation.
-                    Action::requeue(Duration::from_secs(300))
-                })?;
+                // For unexpected errors, we should requeue for several minutes at least,
+                // for expected, "waiting" type of requeuing, those should be shorter, just a few seconds.
+                // IngressRouteTCP does not have expected errors during reconciliation.
+                .or_requeue(
+                    self,
+                    ctx.clone(),
+                    &mut conditions,
+                    INGRESS_READY,
+                    "IngressError",
+                    "Error reconciling postgres ingress route",
+                )
+                .await?;
+
+                let service_name_read_only = format!("{}-ro", self.name_any().as_str());
+                let prefix_read_only = format!("{}-ro-", self.name_any().as_str());
+                reconcile_postgres_ing_route_tcp(
+                    self,
+                    ctx.clone(),
+                    service_name_read_only.as_str(),
+                    basedomain.as_str(),
+                    ns.as_str(),
+                    prefix_read_only.as_str(),
+                    service_name_read_only.as_str(),
+                    IntOrString::Int(5432),
+                    vec![middleware_name.clone()],
+                )
+                .await
+                .or_requeue(
+                    self,
+                    ctx.clone(),
+                    &mut conditions,
+                    INGRESS_READY,
+                    "IngressError",
+                    "Error reconciling read-only postgres ingress route",
+                )
+                .await?;
 
                 reconcile_extra_postgres_ing_route_tcp(
                     self,
@@ -176,13 +273,18 @@ impl CoreDB {
                     vec![middleware_name.clone()],
                 )
                 .await
-                .map_err(|e| {
-                    error!("Error reconciling extra postgres ingress route: {:?}", e);
-                    // For unexpected errors, we should requeue for several minutes at least,
-                    // for expected, "waiting" type of requeuing, those should be shorter, just a few seconds.
-                    // IngressRouteTCP does not have expected errors during reconciliation.
-                    Action::requeue(Duration::from_secs(300))
-                })?;
+                // For unexpected errors, we should requeue for several minutes at least,
+                // for expected, "waiting" type of requeuing, those should be shorter, just a few seconds.
+                // IngressRouteTCP does not have expected errors during reconciliation.
+                .or_requeue(
+                    self,
+                    ctx.clone(),
+                    &mut conditions,
+                    INGRESS_READY,
+                    "IngressError",
+                    "Error reconciling extra postgres ingress route",
+                )
+                .await?;
                 // If pooler is enabled, reconcile ingress route tcp for pooler
                 if self.spec.connectionPooler.enabled {
                     let name_pooler = format!("{}-pooler", self.name_any().as_str());
@@ -199,14 +301,53 @@ impl CoreDB {
                         vec![middleware_name.clone()],
                     )
                     .await
-                    .map_err(|e| {
-                        error!("Error reconciling pooler ingress route: {:?}", e);
-                        // For unexpected errors, we should requeue for several minutes at least,
-                        // for expected, "waiting" type of requeuing, those should be shorter, just a few seconds.
-                        // IngressRouteTCP does not have expected errors during reconciliation.
-                        Action::requeue(Duration::from_secs(300))
-                    })?;
+                    // For unexpected errors, we should requeue for several minutes at least,
+                    // for expected, "waiting" type of requeuing, those should be shorter, just a few seconds.
+                    // IngressRouteTCP does not have expected errors during reconciliation.
+                    .or_requeue(
+                        self,
+                        ctx.clone(),
+                        &mut conditions,
+                        INGRESS_READY,
+                        "IngressError",
+                        "Error reconciling pooler ingress route",
+                    )
+                    .await?;
+                }
+                // If the read-only pooler is enabled, reconcile ingress route tcp for it as well
+                if self.spec.connectionPooler.enabled && self.spec.connectionPooler.readOnly {
+                    let name_pooler_read_only = format!("{}-pooler-ro", self.name_any().as_str());
+                    let prefix_pooler_read_only = format!("{}-pooler-ro-", self.name_any().as_str());
+                    reconcile_postgres_ing_route_tcp(
+                        self,
+                        ctx.clone(),
+                        name_pooler_read_only.as_str(),
+                        basedomain.as_str(),
+                        ns.as_str(),
+                        prefix_pooler_read_only.as_str(),
+                        name_pooler_read_only.as_str(),
+                        IntOrString::Int(5432),
+                        vec![middleware_name.clone()],
+                    )
+                    .await
+                    .or_requeue(
+                        self,
+                        ctx.clone(),
+                        &mut conditions,
+                        INGRESS_READY,
+                        "IngressError",
+                        "Error reconciling read-only pooler ingress route",
+                    )
+                    .await?;
                 }
+                set_condition(
+                    &mut conditions,
+                    INGRESS_READY,
+                    true,
+                    "IngressReady",
+                    "",
+                    generation,
+                );
             }
             Err(_e) => {
                 warn!("DATA_PLANE_BASEDOMAIN is not set, skipping reconciliation of IngressRouteTCP");
@@ -226,75 +367,159 @@ impl CoreDB {
             debug!("Reconciling prometheus configmap");
             reconcile_prom_configmap(self, client.clone(), &ns)
                 .await
-                .map_err(|e| {
-                    error!("Error reconciling prometheus configmap: {:?}", e);
-                    Action::requeue(Duration::from_secs(300))
-                })?;
+                .or_requeue(
+                    self,
+                    ctx.clone(),
+                    &mut conditions,
+                    METRICS_READY,
+                    "PrometheusConfigMapError",
+                    "Error reconciling prometheus configmap",
+                )
+                .await?;
         }
 
         debug!("Reconciling secret");
         // Superuser connection info
-        reconcile_secret(self, ctx.clone()).await.map_err(|e| {
-            error!("Error reconciling secret: {:?}", e);
-            Action::requeue(Duration::from_secs(300))
-        })?;
+        reconcile_secret(self, ctx.clone())
+            .await
+            .or_requeue(
+                self,
+                ctx.clone(),
+                &mut conditions,
+                SECRETS_READY,
+                "SecretError",
+                "Error reconciling secret",
+            )
+            .await?;
 
         // Postgres exporter connection info
         if self.spec.postgresExporterEnabled {
-            let _ = reconcile_postgres_role_secret(
+            reconcile_postgres_role_secret(
                 self,
                 ctx.clone(),
                 "postgres_exporter",
                 &format!("{}-exporter", name.clone()),
             )
             .await
-            .map_err(|e| {
-                error!("Error reconciling postgres exporter secret: {:?}", e);
-                Action::requeue(Duration::from_secs(300))
-            })?;
+            .or_requeue(
+                self,
+                ctx.clone(),
+                &mut conditions,
+                SECRETS_READY,
+                "SecretError",
+                "Error reconciling postgres exporter secret",
+            )
+            .await?;
         }
 
-        let _ =
-            reconcile_postgres_role_secret(self, ctx.clone(), "readonly", &format!("{}-ro", name.clone()))
-                .await
-                .map_err(|e| {
-                    error!("Error reconciling postgres exporter secret: {:?}", e);
-                    Action::requeue(Duration::from_secs(300))
-                })?;
+        reconcile_postgres_role_secret(self, ctx.clone(), "readonly", &format!("{}-ro", name.clone()))
+            .await
+            .or_requeue(
+                self,
+                ctx.clone(),
+                &mut conditions,
+                SECRETS_READY,
+                "SecretError",
+                "Error reconciling postgres readonly secret",
+            )
+            .await?;
+        set_condition(
+            &mut conditions,
+            SECRETS_READY,
+            true,
+            "SecretsReady",
+            "",
+            generation,
+        );
 
         // Deploy cluster
-        reconcile_cnpg(self, ctx.clone()).await?;
+        // Failures of the cluster are reported in the ClusterReady condition by reconcile_cnpg
+        reconcile_cnpg(self, ctx.clone(), cfg, &mut conditions).await?;
         if cfg.enable_backup {
-            reconcile_cnpg_scheduled_backup(self, ctx.clone()).await?;
+            reconcile_cnpg_scheduled_backup(self, ctx.clone())
+                .await
+                .or_requeue(
+                    self,
+                    ctx.clone(),
+                    &mut conditions,
+                    BACKUPS_READY,
+                    "ScheduledBackupError",
+                    "Error reconciling the CNPG scheduled backup",
+                )
+                .await?;
         }
 
         if self.spec.postgresExporterEnabled {
             debug!("Reconciling prometheus exporter deployment");
             reconcile_prometheus_exporter_deployment(self, ctx.clone())
                 .await
-                .map_err(|e| {
-                    error!("Error reconciling prometheus exporter deployment: {:?}", e);
-                    Action::requeue(Duration::from_secs(300))
-                })?;
+                .or_requeue(
+                    self,
+                    ctx.clone(),
+                    &mut conditions,
+                    METRICS_READY,
+                    "PrometheusExporterError",
+                    "Error reconciling prometheus exporter deployment",
+                )
+                .await?;
         };
 
         // reconcile service
         debug!("Reconciling prometheus exporter service");
         reconcile_prometheus_exporter_service(self, ctx.clone())
             .await
-            .map_err(|e| {
-                error!("Error reconciling service: {:?}", e);
-                Action::requeue(Duration::from_secs(300))
-            })?;
+            .or_requeue(
+                self,
+                ctx.clone(),
+                &mut conditions,
+                METRICS_READY,
+                "PrometheusExporterError",
+                "Error reconciling prometheus exporter service",
+            )
+            .await?;
+        set_condition(
+            &mut conditions,
+            METRICS_READY,
+            true,
+            "MetricsReady",
+            "",
+            generation,
+        );
 
-        // Reconcile Pooler resource
-        reconcile_pooler(self, ctx.clone()).await?;
+        // Reconcile Pooler resource, failures are reported in the PoolerReady condition
+        reconcile_pooler(self, ctx.clone(), cfg, &mut conditions).await?;
+        set_condition(&mut conditions, POOLER_READY, true, "PoolerReady", "", generation);
 
-        // Check if Postgres is already running
-        let pg_postmaster_start_time = is_not_restarting(self, ctx.clone(), "postgres").await?;
+        let pooler_ready_replicas = pooler_ready_replicas(self, ctx.clone()).await;
 
         let mut new_status = match self.spec.stop {
             false => {
+                // Check if Postgres is already running
+                let pg_postmaster_start_time =
+                    match is_not_restarting(self, ctx.clone(), cfg, "postgres").await {
+                        Ok(pg_postmaster_start_time) => pg_postmaster_start_time,
+                        Err(action) => {
+                            return Err(requeue_with_condition(
+                                self,
+                                ctx.clone(),
+                                &mut conditions,
+                                CLUSTER_READY,
+                                "PostgresRestarting",
+                                "Waiting for Postgres to finish restarting",
+                                action,
+                            )
+                            .await);
+                        }
+                    };
+                set_condition(
+                    &mut conditions,
+                    CLUSTER_READY,
+                    true,
+                    "ClusterReady",
+                    "",
+                    generation,
+                );
+
                 let patch_status = json!({
                     "apiVersion": "coredb.io/v1alpha1",
                     "kind": "CoreDB",
@@ -303,13 +528,103 @@ impl CoreDB {
                         "pg_postmaster_start_time": pg_postmaster_start_time,
                     }
                 });
-                patch_cdb_status_merge(&coredbs, &name, patch_status).await?;
+                if let Err(e) = try_patch_cdb_status_merge(&coredbs, &name, patch_status).await {
+                    return Err(requeue_with_condition(
+                        self,
+                        ctx.clone(),
+                        &mut conditions,
+                        READY,
+                        "StatusUpdateError",
+                        &format!("Error updating the CoreDB status: {}", e),
+                        Action::requeue(Duration::from_secs(10)),
+                    )
+                    .await);
+                }
+                // Databases are created before extensions, which can be installed into them
+                reconcile_databases(self, ctx.clone(), cfg, &mut conditions).await?;
                 let (trunk_installs, extensions) =
-                    reconcile_extensions(self, ctx.clone(), &coredbs, &name).await?;
-
-                let recovery_time = self.get_recovery_time(ctx.clone()).await?;
+                    reconcile_extensions(self, ctx.clone(), cfg, &mut conditions).await?;
+
+                let backup_list = match self.list_backups(ctx.clone()).await {
+                    Ok(backup_list) => backup_list,
+                    Err(action) => {
+                        return Err(requeue_with_condition(
+                            self,
+                            ctx.clone(),
+                            &mut conditions,
+                            BACKUPS_READY,
+                            "RecoveryTimeUnknown",
+                            "Error determining the first recoverability time",
+                            action,
+                        )
+                        .await);
+                    }
+                };
+                let mut backup_catalog = backup_catalog(&backup_list);
+                backup_sizes(self, ctx.clone(), &backup_list, &mut backup_catalog).await;
+                let recovery_time = self.process_backups(backup_list);
+                let recoverability_window = match recovery_time {
+                    // Nothing can be restored before the first backup completed
+                    None => None,
+                    Some(_) => match get_last_archived_wal(self, ctx.clone(), cfg).await {
+                        Ok((last_archived_wal, last_archived_time)) => {
+                            recoverability_window(recovery_time, last_archived_wal, last_archived_time)
+                        }
+                        // The window is only informational, it is determined again on the next reconcile
+                        Err(_) => {
+                            warn!(
+                                "Could not determine the last archived WAL of {}, leaving the recoverability window unset",
+                                self.name_any()
+                            );
+                            None
+                        }
+                    },
+                };
+                let backups = if cfg.enable_backup {
+                    match reconcile_on_demand_backups(self, ctx.clone()).await {
+                        Ok(backups) => backups,
+                        Err(action) => {
+                            return Err(requeue_with_condition(
+                                self,
+                                ctx.clone(),
+                                &mut conditions,
+                                BACKUPS_READY,
+                                "OnDemandBackupError",
+                                "Error reconciling on-demand backups",
+                                action,
+                            )
+                            .await);
+                        }
+                    }
+                } else {
+                    self.status.as_ref().and_then(|f| f.backups.clone())
+                };
+                if cfg.enable_backup {
+                    set_condition(
+                        &mut conditions,
+                        BACKUPS_READY,
+                        true,
+                        "BackupsReady",
+                        "",
+                        generation,
+                    );
+                }
 
-                let current_config_values = get_current_config_values(self, ctx.clone()).await?;
+                let current_config_values = match get_current_config_values(self, ctx.clone(), cfg).await {
+                    Ok(current_config_values) => current_config_values,
+                    Err(action) => {
+                        return Err(requeue_with_condition(
+                            self,
+                            ctx.clone(),
+                            &mut conditions,
+                            CLUSTER_READY,
+                            "ConfigurationUnknown",
+                            "Error reading the current Postgres configuration",
+                            action,
+                        )
+                        .await);
+                    }
+                };
                 CoreDBStatus {
                     running: true,
                     extensionsUpdating: false,
@@ -321,10 +636,25 @@ impl CoreDB {
                     first_recoverability_time: recovery_time,
                     pg_postmaster_start_time,
                     last_fully_reconciled_at: None,
+                    conditions: None,
+                    observed_generation: generation,
+                    pooler_ready_replicas,
+                    backups,
+                    backup_catalog: Some(backup_catalog),
+                    recoverability_window,
                 }
             }
             true => {
-                let current_config_values = get_current_config_values(self, ctx.clone()).await?;
+                // The cluster is hibernated, so there is no Postgres to query. Keep the last known
+                // values in the status until the instance is started again.
+                set_condition(
+                    &mut conditions,
+                    CLUSTER_READY,
+                    false,
+                    "Hibernated",
+                    "Instance is stopped",
+                    generation,
+                );
                 CoreDBStatus {
                     running: false,
                     extensionsUpdating: false,
@@ -332,14 +662,23 @@ impl CoreDB {
                     extensions: self.status.as_ref().and_then(|f| f.extensions.clone()),
                     trunk_installs: self.status.as_ref().and_then(|f| f.trunk_installs.clone()),
                     resources: Some(self.spec.resources.clone()),
-                    runtime_config: Some(current_config_values),
+                    runtime_config: self.status.as_ref().and_then(|f| f.runtime_config.clone()),
                     first_recoverability_time: self.status.as_ref().and_then(|f| f.first_recoverability_time),
                     pg_postmaster_start_time: None,
                     last_fully_reconciled_at: None,
+                    conditions: None,
+                    observed_generation: generation,
+                    pooler_ready_replicas,
+                    backups: self.status.as_ref().and_then(|f| f.backups.clone()),
+                    backup_catalog: self.status.as_ref().and_then(|f| f.backup_catalog.clone()),
+                    recoverability_window: self.status.as_ref().and_then(|f| f.recoverability_window.clone()),
                 }
             }
         };
 
+        set_condition(&mut conditions, READY, true, "Reconciled", "", generation);
+        new_status.conditions = Some(conditions.clone());
+
         let current_time = Utc::now();
         new_status.last_fully_reconciled_at = {
             let current_fully_reconciled_at = match self.status.as_ref() {
@@ -364,9 +703,22 @@ impl CoreDB {
             "status": new_status
         });
 
-        patch_cdb_status_merge(&coredbs, &name, patch_status).await?;
+        if let Err(e) = try_patch_cdb_status_merge(&coredbs, &name, patch_status).await {
+            return Err(requeue_with_condition(
+                self,
+                ctx.clone(),
+                &mut conditions,
+                READY,
+                "StatusUpdateError",
+                &format!("Error updating the CoreDB status: {}", e),
+                Action::requeue(Duration::from_secs(10)),
+            )
+            .await);
+        }
 
-        reconcile_heartbeat(self, ctx.clone()).await?;
+        if !self.spec.stop {
+            reconcile_heartbeat(self, ctx.clone(), cfg).await?;
+        }
         info!("Fully reconciled {}", self.name_any());
         // Check back every 90-150 seconds
         let jitter = rand::thread_rng().gen_range(0..60);
@@ -385,6 +737,7 @@ impl CoreDB {
             .await
             .map_err(Error::KubeError);
         let phase = ns_status.unwrap().status.unwrap().phase;
+        ctx.postgres_pools.remove_instance(self);
         if phase == Some("Terminating".to_string()) {
             return Ok(Action::await_change());
         }
@@ -411,7 +764,9 @@ impl CoreDB {
     ) -> Result<Pod, Action> {
         let requires_load =
             extensions_that_require_load(client.clone(), &self.metadata.namespace.clone().unwrap()).await?;
-        let cluster = cnpg_cluster_from_cdb(self, None, requires_load);
+        let extension_configs =
+            extension_configs(client.clone(), &self.metadata.namespace.clone().unwrap()).await?;
+        let cluster = cnpg_cluster_from_cdb(self, None, requires_load, extension_configs);
         let cluster_name = cluster
             .metadata
             .name
@@ -468,7 +823,9 @@ impl CoreDB {
     ) -> Result<Vec<Pod>, Action> {
         let requires_load =
             extensions_that_require_load(client.clone(), &self.metadata.namespace.clone().unwrap()).await?;
-        let cluster = cnpg_cluster_from_cdb(self, None, requires_load);
+        let extension_configs =
+            extension_configs(client.clone(), &self.metadata.namespace.clone().unwrap()).await?;
+        let cluster = cnpg_cluster_from_cdb(self, None, requires_load, extension_configs);
         let cluster_name = cluster
             .metadata
             .name
@@ -633,6 +990,24 @@ impl CoreDB {
         cnpg_psql_command.execute().await
     }
 
+    // Runs statements that don't return rows. When the native Postgres client is enabled, they are
+    // sent over a connection to the -rw service, psql is only used when the connection fails.
+    #[instrument(skip(self, context, cfg))]
+    pub async fn execute_sql(
+        &self,
+        command: String,
+        database: String,
+        context: Arc<Context>,
+        cfg: &Config,
+    ) -> Result<PsqlOutput, Action> {
+        if let Some(output) =
+            postgres_client::try_execute(self, context.clone(), cfg, &database, &command).await
+        {
+            return Ok(output);
+        }
+        self.psql(command, database, context).await
+    }
+
     pub async fn exec(
         &self,
         pod_name: String,
@@ -661,6 +1036,16 @@ impl CoreDB {
     // oldest completed Backup object in the namespace.
     #[instrument(skip(self, context))]
     pub async fn get_recovery_time(&self, context: Arc<Context>) -> Result<Option<DateTime<Utc>>, Action> {
+        let backup_list = self.list_backups(context).await?;
+
+        let oldest_backup_time = self.process_backups(backup_list);
+
+        Ok(oldest_backup_time)
+    }
+
+    // list_backups returns the Backup objects of the cluster in the namespace.
+    #[instrument(skip(self, context))]
+    pub async fn list_backups(&self, context: Arc<Context>) -> Result<Vec<Backup>, Action> {
         let client = context.client.clone();
         let namespace = self
             .metadata
@@ -675,9 +1060,7 @@ impl CoreDB {
             Action::requeue(Duration::from_secs(300))
         })?;
 
-        let oldest_backup_time = self.process_backups(backup_list.items);
-
-        Ok(oldest_backup_time)
+        Ok(backup_list.items)
     }
 }
 
@@ -731,8 +1114,12 @@ pub async fn get_current_coredb_resource(cdb: &CoreDB, ctx: Arc<Context>) -> Res
 }
 
 // Get current config values
-pub async fn get_current_config_values(cdb: &CoreDB, ctx: Arc<Context>) -> Result<Vec<PgConfig>, Action> {
-    let cfg = list_config_params(cdb, ctx.clone()).await?;
+pub async fn get_current_config_values(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    cfg: &Config,
+) -> Result<Vec<PgConfig>, Action> {
+    let cfg = list_config_params(cdb, ctx.clone(), cfg).await?;
     Ok(cfg)
 }
 
@@ -741,6 +1128,17 @@ pub async fn patch_cdb_status_merge(
     name: &str,
     patch: serde_json::Value,
 ) -> Result<(), Action> {
+    try_patch_cdb_status_merge(cdb, name, patch)
+        .await
+        .map_err(|_| Action::requeue(Duration::from_secs(10)))
+}
+
+// Same as patch_cdb_status_merge, but returns the error so that it can be reported in a condition
+pub async fn try_patch_cdb_status_merge(
+    cdb: &Api<CoreDB>,
+    name: &str,
+    patch: serde_json::Value,
+) -> Result<(), kube::Error> {
     let pp = PatchParams {
         field_manager: Some("cntrlr".to_string()),
         ..PatchParams::default()
@@ -753,11 +1151,34 @@ pub async fn patch_cdb_status_merge(
         }
         Err(e) => {
             error!("Error updating CoreDB status for {}: {:?}", name, e);
-            Err(Action::requeue(Duration::from_secs(10)))
+            Err(e)
         }
     }
 }
 
+// Publish a Kubernetes Event on the CoreDB so it shows up in `kubectl describe`.
+// Events are best effort, a failure to publish is logged and otherwise ignored.
+pub async fn publish_event(cdb: &CoreDB, ctx: Arc<Context>, type_: EventType, reason: &str, note: String) {
+    let recorder = ctx.diagnostics.read().await.recorder(ctx.client.clone(), cdb);
+    if let Err(e) = recorder
+        .publish(Event {
+            type_,
+            reason: reason.into(),
+            note: Some(note),
+            action: "Reconciling".into(),
+            secondary: None,
+        })
+        .await
+    {
+        warn!(
+            "Failed to publish {} event for {}: {:?}",
+            reason,
+            cdb.name_any(),
+            e
+        );
+    }
+}
+
 /// Diagnostics to be exposed by the web server
 #[derive(Clone, Serialize)]
 pub struct Diagnostics {
@@ -809,6 +1230,7 @@ impl State {
             client,
             metrics: Metrics::default().register(&self.registry).unwrap(),
             diagnostics: self.diagnostics.clone(),
+            postgres_pools: PostgresPools::default(),
         })
     }
 }
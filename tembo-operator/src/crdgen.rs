@@ -1,7 +1,53 @@
-use controller::apis::coredb_types::CoreDB;
-use kube::CustomResourceExt;
+use controller::apis::{coredb_types::CoreDB, v1beta1};
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceConversion;
+use kube::{core::crd::merge_crds, CustomResourceExt};
+
+// With --helm, the CRD is written for the chart: v1beta1 is converted by the webhook when the
+// webhook is enabled, in the namespace of the release
+const HELM_CONVERSION: &str = r#"  conversion:
+{{- if .Values.webhook.enabled }}
+    strategy: Webhook
+    webhook:
+      clientConfig:
+        service:
+          name: coredb-controller-webhook
+          namespace: {{ .Release.Namespace }}
+          path: /convert
+          port: 443
+      conversionReviewVersions:
+      - v1
+{{- else }}
+    strategy: None
+{{- end }}
+"#;
+
+// Without the webhook, v1beta1 is not served, its fields are named differently and writes through
+// it would lose them
+const HELM_V1BETA1_SERVED: &str = r#"    served: {{ .Values.webhook.enabled }}
+    storage: false
+"#;
+
+// cert-manager injects the CA bundle of the webhook certificate for the conversion webhook
+const HELM_CA_INJECTION: &str = r#"  annotations:
+{{- if .Values.webhook.enabled }}
+    cert-manager.io/inject-ca-from: {{ .Release.Namespace }}/coredb-controller-webhook-certificate
+{{- end }}
+"#;
+
 fn main() {
-    let mut crd = CoreDB::crd();
+    let helm = std::env::args().any(|arg| arg == "--helm");
+
+    // v1alpha1 remains the storage version. v1beta1 is only served when the webhook converts it,
+    // without conversion the API server would prune the fields that are named differently.
+    let mut crd = merge_crds(vec![CoreDB::crd(), v1beta1::CoreDB::crd()], "v1alpha1").unwrap();
+    for version in crd.spec.versions.iter_mut() {
+        version.served = version.name == "v1alpha1";
+    }
+
+    crd.spec.conversion = Some(CustomResourceConversion {
+        strategy: "None".to_string(),
+        webhook: None,
+    });
 
     // Ensure metadata exists
     if crd.metadata.annotations.is_none() {
@@ -14,6 +60,12 @@ fn main() {
     }
 
     let crd_str = serde_yaml::to_string(&crd).unwrap();
-    let st = crd_str.replace("required:\n                - queries", "");
+    let mut st = crd_str.replace("required:\n                - queries", "");
+    if helm {
+        st = st
+            .replacen("  conversion:\n    strategy: None\n", HELM_CONVERSION, 1)
+            .replacen("  annotations:\n", HELM_CA_INJECTION, 1)
+            .replacen("    served: false\n    storage: false\n", HELM_V1BETA1_SERVED, 1);
+    }
     print!("{}", st)
 }
@@ -0,0 +1,360 @@
+use crate::{
+    apis::coredb_types::{CoreDB, Database},
+    cloudnativepg::clusters::Cluster,
+    conditions::{requeue_with_condition, set_condition, DATABASES_READY},
+    config::Config,
+    extensions::database_queries::check_input,
+    postgres_client, Context,
+};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Condition as StatusCondition;
+use kube::{runtime::controller::Action, Api, ResourceExt};
+use lazy_static::lazy_static;
+use regex::Regex;
+use sqlx::Row;
+use std::{sync::Arc, time::Duration};
+use tracing::{debug, error, info, instrument, warn};
+
+lazy_static! {
+    // Encodings and locales are passed as literals, like UTF8 or en_US.UTF-8
+    static ref VALID_SETTING: Regex = Regex::new(r"^[a-zA-Z0-9][a-zA-Z0-9_.@-]*$").unwrap();
+}
+
+pub fn check_setting(setting: &str) -> bool {
+    VALID_SETTING.is_match(setting)
+}
+
+pub const LIST_DATABASE_SETTINGS_QUERY: &str = r#"SELECT d.datname, r.rolname, pg_encoding_to_char(d.encoding), d.datcollate, d.datctype
+FROM pg_database d JOIN pg_roles r ON d.datdba = r.oid
+WHERE d.datistemplate = false;"#;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatabaseRow {
+    pub name: String,
+    pub owner: String,
+    pub encoding: String,
+    pub collate: String,
+    pub ctype: String,
+}
+
+/// Parse the output of LIST_DATABASE_SETTINGS_QUERY
+pub fn parse_database_rows(psql_str: &str) -> Vec<DatabaseRow> {
+    let mut results = vec![];
+    for line in psql_str.lines().skip(2) {
+        let fields: Vec<&str> = line.split('|').map(|s| s.trim()).collect();
+        if fields.len() < 5 {
+            debug!("Skipping line: {:?}", fields);
+            continue;
+        }
+        results.push(DatabaseRow {
+            name: fields[0].to_owned(),
+            owner: fields[1].to_owned(),
+            encoding: fields[2].to_owned(),
+            collate: fields[3].to_owned(),
+            ctype: fields[4].to_owned(),
+        });
+    }
+    results
+}
+
+async fn list_database_settings(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    cfg: &Config,
+) -> Result<Vec<DatabaseRow>, Action> {
+    if let Some(rows) = postgres_client::try_fetch(
+        cdb,
+        ctx.clone(),
+        cfg,
+        "postgres",
+        LIST_DATABASE_SETTINGS_QUERY,
+        |row| {
+            Ok(DatabaseRow {
+                name: row.try_get(0)?,
+                owner: row.try_get(1)?,
+                encoding: row.try_get(2)?,
+                collate: row.try_get(3)?,
+                ctype: row.try_get(4)?,
+            })
+        },
+    )
+    .await
+    {
+        return Ok(rows);
+    }
+    let psql_out = cdb
+        .psql(
+            LIST_DATABASE_SETTINGS_QUERY.to_owned(),
+            "postgres".to_owned(),
+            ctx,
+        )
+        .await?;
+    Ok(parse_database_rows(&psql_out.stdout.unwrap_or_default()))
+}
+
+// CREATE DATABASE can't run inside a transaction block, so it is run on its own. Databases created
+// with another encoding or locale than template1 have to be copied from template0.
+pub fn create_database_command(database: &Database) -> String {
+    let mut command = format!(
+        "CREATE DATABASE \"{}\" OWNER \"{}\"",
+        database.name, database.owner
+    );
+    if database.encoding.is_some() || database.locale.is_some() {
+        command.push_str(" TEMPLATE template0");
+    }
+    if let Some(encoding) = &database.encoding {
+        command.push_str(&format!(" ENCODING '{}'", encoding));
+    }
+    if let Some(locale) = &database.locale {
+        command.push_str(&format!(" LC_COLLATE '{}' LC_CTYPE '{}'", locale, locale));
+    }
+    command.push(';');
+    command
+}
+
+// The encoding and locale can't be changed once the database exists, so differences are only reported
+pub fn database_drift(database: &Database, row: &DatabaseRow) -> Vec<String> {
+    let mut drift = vec![];
+    if let Some(encoding) = &database.encoding {
+        if !encoding.eq_ignore_ascii_case(&row.encoding)
+            && !encoding.replace('-', "").eq_ignore_ascii_case(&row.encoding)
+        {
+            drift.push(format!(
+                "database {} has encoding {}, expected {}",
+                database.name, row.encoding, encoding
+            ));
+        }
+    }
+    if let Some(locale) = &database.locale {
+        if locale != &row.collate || locale != &row.ctype {
+            drift.push(format!(
+                "database {} has locale {}/{}, expected {}",
+                database.name, row.collate, row.ctype, locale
+            ));
+        }
+    }
+    drift
+}
+
+// Roles are reconciled by CloudNativePG, which reports the roles it could not reconcile in the
+// status of the Cluster
+fn role_drift(cluster: &Cluster) -> Vec<String> {
+    cluster
+        .status
+        .as_ref()
+        .and_then(|s| s.managed_roles_status.as_ref())
+        .and_then(|s| s.cannot_reconcile.as_ref())
+        .map(|cannot_reconcile| {
+            cannot_reconcile
+                .iter()
+                .map(|(role, errors)| format!("role {}: {}", role, errors.join(", ")))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Create the databases of the spec that do not exist yet, and report drift between the spec and the
+/// databases and roles in the DatabasesReady condition
+#[instrument(skip(cdb, ctx, conditions, cfg), fields(cdb_name = %cdb.name_any()))]
+pub async fn reconcile_databases(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    cfg: &Config,
+    conditions: &mut Vec<StatusCondition>,
+) -> Result<(), Action> {
+    let generation = cdb.metadata.generation;
+    let name = cdb.name_any();
+    let mut drift = vec![];
+
+    if !cdb.spec.databases.is_empty() {
+        let current = list_database_settings(cdb, ctx.clone(), cfg).await?;
+
+        for database in &cdb.spec.databases {
+            if !check_input(&database.name) || !check_input(&database.owner) {
+                warn!("Skipping database with invalid name or owner {}", database.name);
+                continue;
+            }
+            if !database
+                .encoding
+                .iter()
+                .chain(database.locale.iter())
+                .all(|s| check_setting(s))
+            {
+                warn!(
+                    "Skipping database with invalid encoding or locale {}",
+                    database.name
+                );
+                continue;
+            }
+            let command = match current.iter().find(|row| row.name == database.name) {
+                Some(row) => {
+                    drift.extend(database_drift(database, row));
+                    if row.owner == database.owner {
+                        continue;
+                    }
+                    format!(
+                        "ALTER DATABASE \"{}\" OWNER TO \"{}\";",
+                        database.name, database.owner
+                    )
+                }
+                None => create_database_command(database),
+            };
+            info!("Running {} for {}", command, name);
+            let result = cdb
+                .execute_sql(command, "postgres".to_owned(), ctx.clone(), cfg)
+                .await;
+            let stderr = match result {
+                Ok(psql_out) if psql_out.success => continue,
+                Ok(psql_out) => psql_out.stderr.unwrap_or_default(),
+                Err(_) => String::new(),
+            };
+            error!(
+                "Failed to reconcile database {} for {}: {}",
+                database.name, name, stderr
+            );
+            return Err(requeue_with_condition(
+                cdb,
+                ctx.clone(),
+                conditions,
+                DATABASES_READY,
+                "DatabaseNotReconciled",
+                &format!("Failed to reconcile database {}", database.name),
+                Action::requeue(Duration::from_secs(30)),
+            )
+            .await);
+        }
+    }
+
+    if !cdb.spec.roles.is_empty() {
+        let clusters: Api<Cluster> = Api::namespaced(ctx.client.clone(), &cdb.namespace().unwrap());
+        match clusters.get_opt(&name).await {
+            Ok(Some(cluster)) => drift.extend(role_drift(&cluster)),
+            Ok(None) => {}
+            Err(e) => {
+                error!("Error getting the cluster of {}: {}", name, e);
+                return Err(Action::requeue(Duration::from_secs(30)));
+            }
+        }
+    }
+
+    if drift.is_empty() {
+        set_condition(
+            conditions,
+            DATABASES_READY,
+            true,
+            "DatabasesReady",
+            "",
+            generation,
+        );
+    } else {
+        let message = drift.join("; ");
+        warn!("Drift in databases or roles of {}: {}", name, message);
+        set_condition(
+            conditions,
+            DATABASES_READY,
+            false,
+            "DatabaseDrift",
+            &message,
+            generation,
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cloudnativepg::clusters::{ClusterSpec, ClusterStatus, ClusterStatusManagedRolesStatus};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_parse_database_rows() {
+        let psql_str = " datname  | rolname  | pg_encoding_to_char | datcollate  |   datctype
+----------+----------+---------------------+-------------+-------------
+ postgres | postgres | UTF8                | en_US.UTF-8 | en_US.UTF-8
+ app      | app      | LATIN1              | C           | C
+(2 rows)
+";
+        let rows = parse_database_rows(psql_str);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1], DatabaseRow {
+            name: "app".to_owned(),
+            owner: "app".to_owned(),
+            encoding: "LATIN1".to_owned(),
+            collate: "C".to_owned(),
+            ctype: "C".to_owned(),
+        });
+    }
+
+    #[test]
+    fn test_create_database_command() {
+        let mut database = Database {
+            name: "app".to_owned(),
+            owner: "app-owner".to_owned(),
+            encoding: None,
+            locale: None,
+        };
+        assert_eq!(
+            create_database_command(&database),
+            "CREATE DATABASE \"app\" OWNER \"app-owner\";"
+        );
+        database.encoding = Some("UTF8".to_owned());
+        database.locale = Some("C".to_owned());
+        assert_eq!(
+            create_database_command(&database),
+            "CREATE DATABASE \"app\" OWNER \"app-owner\" TEMPLATE template0 ENCODING 'UTF8' LC_COLLATE 'C' LC_CTYPE 'C';"
+        );
+    }
+
+    #[test]
+    fn test_database_drift() {
+        let row = DatabaseRow {
+            name: "app".to_owned(),
+            owner: "postgres".to_owned(),
+            encoding: "UTF8".to_owned(),
+            collate: "en_US.UTF-8".to_owned(),
+            ctype: "en_US.UTF-8".to_owned(),
+        };
+        let mut database = Database {
+            name: "app".to_owned(),
+            owner: "postgres".to_owned(),
+            encoding: Some("utf-8".to_owned()),
+            locale: None,
+        };
+        assert!(database_drift(&database, &row).is_empty());
+
+        database.encoding = Some("LATIN1".to_owned());
+        database.locale = Some("C".to_owned());
+        let drift = database_drift(&database, &row);
+        assert_eq!(drift, vec![
+            "database app has encoding UTF8, expected LATIN1".to_owned(),
+            "database app has locale en_US.UTF-8/en_US.UTF-8, expected C".to_owned(),
+        ]);
+    }
+
+    #[test]
+    fn test_role_drift() {
+        let mut cluster = Cluster::new("test", ClusterSpec::default());
+        assert!(role_drift(&cluster).is_empty());
+        cluster.status = Some(ClusterStatus {
+            managed_roles_status: Some(ClusterStatusManagedRolesStatus {
+                cannot_reconcile: Some(BTreeMap::from([("app".to_owned(), vec![
+                    "role \"missing\" does not exist".to_owned(),
+                ])])),
+                ..ClusterStatusManagedRolesStatus::default()
+            }),
+            ..ClusterStatus::default()
+        });
+        assert_eq!(role_drift(&cluster), vec![
+            "role app: role \"missing\" does not exist".to_owned()
+        ]);
+    }
+
+    #[test]
+    fn test_check_setting() {
+        assert!(check_setting("UTF8"));
+        assert!(check_setting("en_US.UTF-8"));
+        assert!(check_setting("de_DE@euro"));
+        assert!(!check_setting("C' TEMPLATE template1"));
+        assert!(!check_setting(""));
+    }
+}
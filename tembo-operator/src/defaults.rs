@@ -61,6 +61,23 @@ pub fn default_pkglibdir_storage() -> Quantity {
     Quantity("1Gi".to_string())
 }
 
+pub fn default_topology_key() -> String {
+    "topology.kubernetes.io/zone".to_owned()
+}
+
+pub fn default_database_owner() -> String {
+    "postgres".to_owned()
+}
+
+pub fn default_role_login() -> bool {
+    true
+}
+
+pub fn default_role_connection_limit() -> i64 {
+    // No limit
+    -1
+}
+
 pub fn default_postgres_exporter_image() -> String {
     "quay.io/prometheuscommunity/postgres-exporter:v0.12.0".to_owned()
 }
@@ -129,9 +146,21 @@ pub fn default_conn_pooler() -> ConnectionPooler {
     ConnectionPooler {
         enabled: default_conn_pooler_enabled(),
         pooler: default_pgbouncer(),
+        readOnly: false,
+        instances: default_pooler_instances(),
+        minAvailable: None,
+        autoscaling: None,
     }
 }
 
+pub fn default_pooler_instances() -> i32 {
+    1
+}
+
+pub fn default_pooler_target_cpu_utilization() -> i32 {
+    80
+}
+
 pub fn default_conn_pooler_enabled() -> bool {
     false
 }
@@ -3,17 +3,19 @@ use crate::{
         coredb_types::CoreDB,
         postgres_parameters::{ConfigValue, PgConfig},
     },
+    config::Config,
     extensions::{
         types,
         types::{ExtensionInstallLocation, ExtensionInstallLocationStatus, ExtensionStatus},
     },
-    Context, RESTARTED_AT,
+    postgres_client, Context, RESTARTED_AT,
 };
 use chrono::{DateTime, Utc};
 use k8s_openapi::api::core::v1::Pod;
 use kube::{api::DeleteParams, runtime::controller::Action, Api, ResourceExt};
 use lazy_static::lazy_static;
 use regex::Regex;
+use sqlx::Row;
 use std::{
     collections::{BTreeSet, HashMap},
     sync::Arc,
@@ -106,7 +108,23 @@ pub struct ExtRow {
 }
 
 #[instrument(skip(cdb, ctx), fields(cdb_name = %cdb.name_any()))]
-pub async fn list_shared_preload_libraries(cdb: &CoreDB, ctx: Arc<Context>) -> Result<Vec<String>, Action> {
+pub async fn list_shared_preload_libraries(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    cfg: &Config,
+) -> Result<Vec<String>, Action> {
+    if let Some(rows) = postgres_client::try_fetch(
+        cdb,
+        ctx.clone(),
+        cfg,
+        "postgres",
+        LIST_SHARED_PRELOAD_LIBRARIES_QUERY,
+        |row| row.try_get::<String, _>(0),
+    )
+    .await
+    {
+        return Ok(split_libraries(&rows));
+    }
     let psql_out = cdb
         .psql(
             LIST_SHARED_PRELOAD_LIBRARIES_QUERY.to_owned(),
@@ -125,10 +143,7 @@ pub async fn list_shared_preload_libraries(cdb: &CoreDB, ctx: Arc<Context>) -> R
         Some(out) => out,
     };
     let result = parse_sql_output(&result_string);
-    let mut libraries: Vec<String> = vec![];
-    if result.len() == 1 {
-        libraries = result[0].split(',').map(|s| s.trim().to_string()).collect();
-    }
+    let libraries = split_libraries(&result);
     debug!(
         "{}: Found shared_preload_libraries: {:?}",
         cdb.metadata.name.clone().unwrap(),
@@ -137,9 +152,39 @@ pub async fn list_shared_preload_libraries(cdb: &CoreDB, ctx: Arc<Context>) -> R
     Ok(libraries)
 }
 
+fn split_libraries(result: &[String]) -> Vec<String> {
+    let mut libraries: Vec<String> = vec![];
+    if result.len() == 1 {
+        libraries = result[0].split(',').map(|s| s.trim().to_string()).collect();
+    }
+    libraries
+}
+
 /// lists all extensions in a single database
 #[instrument(skip(cdb, ctx), fields(cdb_name = %cdb.name_any()))]
-pub async fn list_extensions(cdb: &CoreDB, ctx: Arc<Context>, database: &str) -> Result<Vec<ExtRow>, Action> {
+pub async fn list_extensions(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    cfg: &Config,
+    database: &str,
+) -> Result<Vec<ExtRow>, Action> {
+    if let Some(extensions) =
+        postgres_client::try_fetch(cdb, ctx.clone(), cfg, database, LIST_EXTENSIONS_QUERY, |row| {
+            Ok(ExtRow {
+                name: row.try_get("name")?,
+                // Extensions don't need to have a comment
+                description: row
+                    .try_get::<Option<String>, _>("description")?
+                    .unwrap_or_default(),
+                version: row.try_get("version")?,
+                enabled: row.try_get("enabled")?,
+                schema: row.try_get("schema")?,
+            })
+        })
+        .await
+    {
+        return Ok(extensions);
+    }
     let psql_out = cdb
         .psql(LIST_EXTENSIONS_QUERY.to_owned(), database.to_owned(), ctx)
         .await?;
@@ -149,7 +194,18 @@ pub async fn list_extensions(cdb: &CoreDB, ctx: Arc<Context>, database: &str) ->
 
 /// List all configuration parameters
 #[instrument(skip(cdb, ctx), fields(cdb_name = %cdb.name_any()))]
-pub async fn list_config_params(cdb: &CoreDB, ctx: Arc<Context>) -> Result<Vec<PgConfig>, Action> {
+pub async fn list_config_params(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    cfg: &Config,
+) -> Result<Vec<PgConfig>, Action> {
+    if let Some(configs) = postgres_client::try_fetch(cdb, ctx.clone(), cfg, "postgres", "SHOW ALL;", |row| {
+        Ok(config_param(row.try_get(0)?, row.try_get(1)?))
+    })
+    .await
+    {
+        return Ok(configs);
+    }
     let psql_out = cdb
         .psql("SHOW ALL;".to_owned(), "postgres".to_owned(), ctx)
         .await?;
@@ -171,6 +227,7 @@ pub async fn list_config_params(cdb: &CoreDB, ctx: Arc<Context>) -> Result<Vec<P
 pub async fn is_not_restarting(
     cdb: &CoreDB,
     ctx: Arc<Context>,
+    cfg: &Config,
     database: &str,
 ) -> Result<Option<DateTime<Utc>>, Action> {
     // chrono strftime declaration to parse Postgres timestamps
@@ -182,6 +239,42 @@ pub async fn is_not_restarting(
 
     let cdb_name = cdb.name_any();
 
+    let restarted_requested_at: Option<DateTime<Utc>> = match cdb.annotations().get(RESTARTED_AT) {
+        None => None,
+        Some(restarted_at) => Some(
+            DateTime::parse_from_rfc3339(restarted_at)
+                .map_err(|err| {
+                    error!("{cdb_name}: Failed to deserialize DateTime from `restartedAt`: {err}");
+
+                    Action::requeue(Duration::from_secs(300))
+                })?
+                .into(),
+        ),
+    };
+
+    if let Some(rows) = postgres_client::try_fetch(
+        cdb,
+        ctx.clone(),
+        cfg,
+        database,
+        "select pg_postmaster_start_time();",
+        |row| row.try_get::<DateTime<Utc>, _>(0),
+    )
+    .await
+    {
+        let server_started_at = rows.first().copied();
+        return match restarted_requested_at {
+            None => Ok(server_started_at),
+            Some(restarted_requested_at) => {
+                let server_started_at = server_started_at.ok_or_else(|| {
+                    error!("{cdb_name}: select pg_postmaster_start_time() returned no rows");
+                    Action::requeue(Duration::from_secs(300))
+                })?;
+                restart_complete(&cdb_name, restarted_requested_at, server_started_at)
+            }
+        };
+    }
+
     let pg_postmaster_result = cdb
         .psql(
             "select pg_postmaster_start_time();".to_owned(),
@@ -190,7 +283,7 @@ pub async fn is_not_restarting(
         )
         .await;
 
-    let Some(restarted_at) = cdb.annotations().get(RESTARTED_AT) else {
+    let Some(restarted_requested_at) = restarted_requested_at else {
         // We don't have the annotation, so we are not restarting
         // return pg_postmaster_start_time if we have it.
         let result = pg_postmaster_result
@@ -207,15 +300,6 @@ pub async fn is_not_restarting(
         return Ok(result);
     };
 
-    let restarted_requested_at: DateTime<Utc> = DateTime::parse_from_rfc3339(restarted_at)
-        .map_err(|err| {
-            error!("{cdb_name}: Failed to deserialize DateTime from `restartedAt`: {err}");
-
-            Action::requeue(Duration::from_secs(300))
-        })?
-        .into();
-
-
     let pg_postmaster = match pg_postmaster_result {
         Ok(result) => result.stdout.ok_or_else(|| {
             error!("{cdb_name}: select pg_postmaster_start_time() had no stdout");
@@ -281,6 +365,14 @@ pub async fn is_not_restarting(
         })?
         .into();
 
+    restart_complete(&cdb_name, restarted_requested_at, server_started_at)
+}
+
+fn restart_complete(
+    cdb_name: &str,
+    restarted_requested_at: DateTime<Utc>,
+    server_started_at: DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>, Action> {
     if server_started_at >= restarted_requested_at {
         // Server started after the moment we requested it to restart,
         // meaning the restart is done
@@ -348,7 +440,15 @@ pub fn parse_extensions(psql_str: &str) -> Vec<ExtRow> {
 
 /// returns all the databases in an instance
 #[instrument(skip(cdb, ctx), fields(cdb_name = %cdb.name_any()))]
-pub async fn list_databases(cdb: &CoreDB, ctx: Arc<Context>) -> Result<Vec<String>, Action> {
+pub async fn list_databases(cdb: &CoreDB, ctx: Arc<Context>, cfg: &Config) -> Result<Vec<String>, Action> {
+    if let Some(databases) =
+        postgres_client::try_fetch(cdb, ctx.clone(), cfg, "postgres", LIST_DATABASES_QUERY, |row| {
+            row.try_get::<String, _>(0)
+        })
+        .await
+    {
+        return Ok(databases);
+    }
     let _client = ctx.client.clone();
     let psql_out = cdb
         .psql(LIST_DATABASES_QUERY.to_owned(), "postgres".to_owned(), ctx)
@@ -377,6 +477,21 @@ pub fn parse_sql_output(psql_str: &str) -> Vec<String> {
     results
 }
 
+fn config_param(name: String, value: &str) -> PgConfig {
+    // If value is multiple, Set as ConfigValue::Multiple
+    if value.contains(',') {
+        let values: BTreeSet<String> = value.split(',').map(|s| s.trim().to_owned()).collect();
+        return PgConfig {
+            name,
+            value: ConfigValue::Multiple(values),
+        };
+    }
+    PgConfig {
+        name,
+        value: ConfigValue::Single(value.to_owned()),
+    }
+}
+
 /// Parse the output of `SHOW ALL` to get the parameter and its value. Return Vec<PgConfig>
 #[instrument(skip(psql_str))]
 pub fn parse_config_params(psql_str: &str) -> Vec<PgConfig> {
@@ -387,21 +502,7 @@ pub fn parse_config_params(psql_str: &str) -> Vec<PgConfig> {
             debug!("Skipping last line:{:?}", fields);
             continue;
         }
-        // If value is multiple, Set as ConfigValue::Multiple
-        if fields[1].contains(',') {
-            let values: BTreeSet<String> = fields[1].split(',').map(|s| s.trim().to_owned()).collect();
-            let config = PgConfig {
-                name: fields[0].to_owned(),
-                value: ConfigValue::Multiple(values),
-            };
-            results.push(config);
-            continue;
-        }
-        let config = PgConfig {
-            name: fields[0].to_owned(),
-            value: ConfigValue::Single(fields[1].to_owned()),
-        };
-        results.push(config);
+        results.push(config_param(fields[0].to_owned(), fields[1]));
     }
     let num_results = results.len();
     debug!("Found {} config values", num_results);
@@ -413,24 +514,32 @@ pub fn parse_config_params(psql_str: &str) -> Vec<PgConfig> {
 }
 
 /// list databases then get all extensions from each database
-#[instrument(skip(cdb, ctx), fields(cdb_name = %cdb.name_any()))]
-pub async fn get_all_extensions(cdb: &CoreDB, ctx: Arc<Context>) -> Result<Vec<ExtensionStatus>, Action> {
-    let databases = list_databases(cdb, ctx.clone()).await?;
+#[instrument(skip(cdb, ctx, cfg), fields(cdb_name = %cdb.name_any()))]
+pub async fn get_all_extensions(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    cfg: &Config,
+) -> Result<Vec<ExtensionStatus>, Action> {
+    let databases = list_databases(cdb, ctx.clone(), cfg).await?;
     debug!("databases: {:?}", databases);
 
     let mut ext_hashmap: HashMap<(String, String), Vec<ExtensionInstallLocationStatus>> = HashMap::new();
     // query every database for extensions
     // transform results by extension name, rather than by database
     for db in databases {
-        let extensions = list_extensions(cdb, ctx.clone(), &db).await?;
+        let extensions = list_extensions(cdb, ctx.clone(), cfg, &db).await?;
         for ext in extensions {
             let extlocation = ExtensionInstallLocationStatus {
                 database: db.clone(),
                 version: Some(ext.version),
                 enabled: Some(ext.enabled),
-                schema: Some(ext.schema),
+                // Extensions that are not enabled are not in any schema
+                schema: ext.enabled.then_some(ext.schema),
                 error: None,
                 error_message: None,
+                target_version: None,
+                update_path: None,
+                target_schema: None,
             };
             ext_hashmap
                 .entry((ext.name, ext.description))
@@ -454,14 +563,397 @@ pub async fn get_all_extensions(cdb: &CoreDB, ctx: Arc<Context>) -> Result<Vec<E
     Ok(ext_spec)
 }
 
+// Prefix of the error message of a location whose target version is not available yet, which is
+// checked again on every reconcile since a trunk install can make it available
+pub const NO_UPDATE_PATH_MESSAGE: &str = "No update path";
+
+/// returns the versions an extension goes through to update from one version to another, like
+/// 1.0--1.1--1.2, or None if the target version is not available
+#[instrument(skip(cdb, ctx), fields(cdb_name = %cdb.name_any()))]
+pub async fn get_extension_update_path(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    cfg: &Config,
+    ext_name: &str,
+    database: &str,
+    from_version: &str,
+    to_version: &str,
+) -> Result<Option<String>, Action> {
+    if !check_input(ext_name)
+        || !check_input(database)
+        || !types::check_version(from_version)
+        || !types::check_version(to_version)
+    {
+        warn!(
+            "Extension {} update from {} to {} is not formatted properly. Skipping operation.",
+            ext_name, from_version, to_version
+        );
+        return Ok(None);
+    }
+    let query = format!(
+        "SELECT path FROM pg_extension_update_paths('{}') WHERE source = '{}' AND target = '{}' AND path IS NOT NULL;",
+        ext_name, from_version, to_version
+    );
+    if let Some(paths) = postgres_client::try_fetch(cdb, ctx.clone(), cfg, database, &query, |row| {
+        row.try_get::<String, _>(0)
+    })
+    .await
+    {
+        return Ok(paths.into_iter().next());
+    }
+    let psql_out = cdb.psql(query, database.to_owned(), ctx).await?;
+    let result_string = psql_out.stdout.unwrap_or_default();
+    Ok(parse_sql_output(&result_string).into_iter().next())
+}
+
+/// Look up the update path of every location with a target version. Locations without a path to
+/// the target version are marked as failed, until the version becomes available.
+#[instrument(skip(cdb, ctx, ext_status_updates, cfg), fields(cdb_name = %cdb.name_any()))]
+pub async fn determine_update_paths(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    cfg: &Config,
+    ext_status_updates: Vec<ExtensionStatus>,
+) -> Result<Vec<ExtensionStatus>, Action> {
+    let mut ext_status_updates = ext_status_updates;
+    for extension_status in ext_status_updates.iter_mut() {
+        for location_status in extension_status.locations.iter_mut() {
+            let (Some(version), Some(target_version)) = (
+                location_status.version.clone(),
+                location_status.target_version.clone(),
+            ) else {
+                continue;
+            };
+            let missing_path = location_status
+                .error_message
+                .as_deref()
+                .is_some_and(|message| message.starts_with(NO_UPDATE_PATH_MESSAGE));
+            if location_status.error == Some(true) && !missing_path {
+                continue;
+            }
+            location_status.update_path = get_extension_update_path(
+                cdb,
+                ctx.clone(),
+                cfg,
+                &extension_status.name,
+                &location_status.database,
+                &version,
+                &target_version,
+            )
+            .await?;
+            if location_status.update_path.is_some() {
+                location_status.error = Some(false);
+                location_status.error_message = None;
+            } else {
+                location_status.error = Some(true);
+                location_status.error_message = Some(format!(
+                    "{} from version {} to {}, the target version may not be installed",
+                    NO_UPDATE_PATH_MESSAGE, version, target_version
+                ));
+            }
+        }
+    }
+    Ok(ext_status_updates)
+}
+
+// Prefix of the error message of a location that can not be moved to the schema in the spec, which
+// is checked again on every reconcile since updating the extension can make it relocatable
+const NOT_RELOCATABLE_MESSAGE: &str = "Not relocatable";
+
+/// returns whether an enabled extension can be moved to another schema with ALTER EXTENSION, or
+/// None if the extension is not enabled in the database
+#[instrument(skip(cdb, ctx), fields(cdb_name = %cdb.name_any()))]
+pub async fn is_extension_relocatable(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    cfg: &Config,
+    ext_name: &str,
+    database: &str,
+) -> Result<Option<bool>, Action> {
+    if !check_input(ext_name) || !check_input(database) {
+        warn!(
+            "Extension {} in database {} is not formatted properly. Skipping operation.",
+            ext_name, database
+        );
+        return Ok(None);
+    }
+    let query = format!(
+        "SELECT extrelocatable FROM pg_extension WHERE extname = '{}';",
+        ext_name
+    );
+    if let Some(relocatable) = postgres_client::try_fetch(cdb, ctx.clone(), cfg, database, &query, |row| {
+        row.try_get::<bool, _>(0)
+    })
+    .await
+    {
+        return Ok(relocatable.into_iter().next());
+    }
+    let psql_out = cdb.psql(query, database.to_owned(), ctx).await?;
+    let result_string = psql_out.stdout.unwrap_or_default();
+    Ok(parse_sql_output(&result_string)
+        .into_iter()
+        .next()
+        .map(|relocatable| relocatable == "t"))
+}
+
+/// Check that every location with a target schema can be moved there. Locations of extensions
+/// that are not relocatable are marked as failed, until the extension becomes relocatable.
+#[instrument(skip(cdb, ctx, ext_status_updates, cfg), fields(cdb_name = %cdb.name_any()))]
+pub async fn determine_relocations(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    cfg: &Config,
+    ext_status_updates: Vec<ExtensionStatus>,
+) -> Result<Vec<ExtensionStatus>, Action> {
+    let mut ext_status_updates = ext_status_updates;
+    for extension_status in ext_status_updates.iter_mut() {
+        for location_status in extension_status.locations.iter_mut() {
+            let Some(target_schema) = location_status.target_schema.clone() else {
+                continue;
+            };
+            let not_relocatable = location_status
+                .error_message
+                .as_deref()
+                .is_some_and(|message| message.starts_with(NOT_RELOCATABLE_MESSAGE));
+            if location_status.error == Some(true) && !not_relocatable {
+                continue;
+            }
+            match is_extension_relocatable(
+                cdb,
+                ctx.clone(),
+                cfg,
+                &extension_status.name,
+                &location_status.database,
+            )
+            .await?
+            {
+                Some(false) => {
+                    location_status.error = Some(true);
+                    location_status.error_message = Some(format!(
+                        "{}: extension {} does not support moving from schema {} to {}",
+                        NOT_RELOCATABLE_MESSAGE,
+                        extension_status.name,
+                        location_status.schema.clone().unwrap_or_default(),
+                        target_schema
+                    ));
+                }
+                _ if not_relocatable => {
+                    location_status.error = Some(false);
+                    location_status.error_message = None;
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(ext_status_updates)
+}
+
+/// Handles moving an extension location to the schema in the spec
+/// On failure, returns an error message
+#[instrument(skip(cdb, ctx, cfg), fields(cdb_name = %cdb.name_any(), ext_name, ext_loc))]
+pub async fn relocate_extension(
+    cdb: &CoreDB,
+    ext_name: &str,
+    ext_loc: ExtensionInstallLocation,
+    ctx: Arc<Context>,
+    cfg: &Config,
+) -> Result<(), String> {
+    let coredb_name = cdb.metadata.name.clone().expect("CoreDB should have a name");
+    if !check_input(ext_name) || !check_input(&ext_loc.database) {
+        warn!(
+            "Extension or database name is not formatted properly. Skipping operation. {}",
+            &coredb_name
+        );
+        return Err("Extension or database name is not formatted properly".to_string());
+    }
+    let schema = ext_loc
+        .schema
+        .clone()
+        .ok_or_else(|| "Missing schema".to_string())?;
+    let command = types::generate_extension_relocate_cmd(ext_name, &schema)?;
+
+    match cdb
+        .execute_sql(command, ext_loc.database.clone(), ctx.clone(), cfg)
+        .await
+    {
+        Ok(psql_output) if psql_output.success => {
+            info!(
+                "Successfully moved extension {} to schema {} in database {}, instance {}",
+                ext_name, schema, ext_loc.database, &coredb_name
+            );
+            Ok(())
+        }
+        Ok(psql_output) => {
+            warn!(
+                "Failed to move extension {} in database {}, instance {}",
+                ext_name, ext_loc.database, &coredb_name
+            );
+            Err(psql_output.stderr.unwrap_or_else(|| {
+                "Failed to move extension, and found no output. Please try again. If this issue persists, contact support.".to_string()
+            }))
+        }
+        Err(e) => {
+            error!("Failed to move extension because of kube exec error: {:?}", e);
+            Err(
+                "Could not connect to database, try again. If problem persists, please contact support."
+                    .to_string(),
+            )
+        }
+    }
+}
+
+/// Handles updating an extension location to the version in the spec
+/// On failure, returns an error message
+#[instrument(skip(cdb, ctx, cfg), fields(cdb_name = %cdb.name_any(), ext_name, ext_loc))]
+pub async fn update_extension(
+    cdb: &CoreDB,
+    ext_name: &str,
+    ext_loc: ExtensionInstallLocation,
+    ctx: Arc<Context>,
+    cfg: &Config,
+) -> Result<(), String> {
+    let coredb_name = cdb.metadata.name.clone().expect("CoreDB should have a name");
+    if !check_input(ext_name) || !check_input(&ext_loc.database) {
+        warn!(
+            "Extension or database name is not formatted properly. Skipping operation. {}",
+            &coredb_name
+        );
+        return Err("Extension or database name is not formatted properly".to_string());
+    }
+    let version = ext_loc
+        .version
+        .clone()
+        .ok_or_else(|| "Missing version".to_string())?;
+    let command = types::generate_extension_update_cmd(ext_name, &version)?;
+
+    match cdb
+        .execute_sql(command, ext_loc.database.clone(), ctx.clone(), cfg)
+        .await
+    {
+        Ok(psql_output) if psql_output.success => {
+            info!(
+                "Successfully updated extension {} to version {} in database {}, instance {}",
+                ext_name, version, ext_loc.database, &coredb_name
+            );
+            Ok(())
+        }
+        Ok(psql_output) => {
+            warn!(
+                "Failed to update extension {} in database {}, instance {}",
+                ext_name, ext_loc.database, &coredb_name
+            );
+            Err(psql_output.stderr.unwrap_or_else(|| {
+                "Failed to update extension, and found no output. Please try again. If this issue persists, contact support.".to_string()
+            }))
+        }
+        Err(e) => {
+            error!("Failed to update extension because of kube exec error: {:?}", e);
+            Err(
+                "Could not connect to database, try again. If problem persists, please contact support."
+                    .to_string(),
+            )
+        }
+    }
+}
+
+/// A batch of toggles that failed and was rolled back
+#[derive(Debug, PartialEq)]
+pub struct ToggleBatchError {
+    /// The extension whose statement failed, None when the batch could not run
+    pub extension_name: Option<String>,
+    pub error_message: String,
+}
+
+/// Handles create/drop of several extensions in one database, in a single transaction
+/// On failure, nothing is toggled and the extension that failed is returned with the error message
+#[instrument(skip(cdb, ctx, toggles, cfg), fields(cdb_name = %cdb.name_any()))]
+pub async fn toggle_extensions_in_database(
+    cdb: &CoreDB,
+    database: &str,
+    toggles: &[(String, ExtensionInstallLocation)],
+    ctx: Arc<Context>,
+    cfg: &Config,
+) -> Result<(), ToggleBatchError> {
+    let coredb_name = cdb.metadata.name.clone().expect("CoreDB should have a name");
+    if !check_input(database) {
+        warn!(
+            "Database name is not formatted properly. Skipping operation. {}",
+            &coredb_name
+        );
+        return Err(ToggleBatchError {
+            extension_name: None,
+            error_message: "Database name is not formatted properly".to_string(),
+        });
+    }
+    if let Some((ext_name, _)) = toggles.iter().find(|(ext_name, _)| !check_input(ext_name)) {
+        warn!(
+            "Extension is not formatted properly. Skipping operation. {}",
+            &coredb_name
+        );
+        return Err(ToggleBatchError {
+            extension_name: Some(ext_name.clone()),
+            error_message: "Extension name is not formatted properly".to_string(),
+        });
+    }
+    let command =
+        types::generate_extension_toggle_batch_cmd(toggles).map_err(|(ext_name, error_message)| {
+            ToggleBatchError {
+                extension_name: Some(ext_name),
+                error_message,
+            }
+        })?;
+
+    match cdb
+        .execute_sql(command, database.to_owned(), ctx.clone(), cfg)
+        .await
+    {
+        Ok(psql_output) if psql_output.success => {
+            info!(
+                "Successfully toggled {} extensions in database {}, instance {}",
+                toggles.len(),
+                database,
+                &coredb_name
+            );
+            Ok(())
+        }
+        Ok(psql_output) => {
+            let error_message = psql_output.stderr.unwrap_or_else(|| {
+                "Failed to toggle extensions, and found no output. Please try again. If this issue persists, contact support.".to_string()
+            });
+            let extension_name = types::parse_toggle_batch_error(&error_message);
+            warn!(
+                "Failed to toggle extensions in database {}, instance {}, rolled back: {}",
+                database, &coredb_name, error_message
+            );
+            Err(ToggleBatchError {
+                extension_name,
+                error_message,
+            })
+        }
+        Err(e) => {
+            error!(
+                "Failed to reconcile extensions because of kube exec error: {:?}",
+                e
+            );
+            Err(ToggleBatchError {
+                extension_name: None,
+                error_message:
+                    "Could not connect to database, try again. If problem persists, please contact support."
+                        .to_string(),
+            })
+        }
+    }
+}
+
 /// Handles create/drop an extension location
 /// On failure, returns an error message
-#[instrument(skip(cdb, ctx), fields(cdb_name = %cdb.name_any(), ext_name, ext_loc))]
+#[instrument(skip(cdb, ctx, cfg), fields(cdb_name = %cdb.name_any(), ext_name, ext_loc))]
 pub async fn toggle_extension(
     cdb: &CoreDB,
     ext_name: &str,
     ext_loc: ExtensionInstallLocation,
     ctx: Arc<Context>,
+    cfg: &Config,
 ) -> Result<(), String> {
     let coredb_name = cdb.metadata.name.clone().expect("CoreDB should have a name");
     if !check_input(ext_name) {
@@ -483,7 +975,7 @@ pub async fn toggle_extension(
     let command = types::generate_extension_enable_cmd(ext_name, &ext_loc)?;
 
     let result = cdb
-        .psql(command.clone(), database_name.clone(), ctx.clone())
+        .execute_sql(command.clone(), database_name.clone(), ctx.clone(), cfg)
         .await;
 
     match result {
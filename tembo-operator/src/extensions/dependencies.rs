@@ -0,0 +1,236 @@
+use crate::extensions::types::{Extension, ExtensionStatus, TrunkInstall};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Prefix of the error message of a location that can not be enabled because of missing dependencies
+const UNRESOLVED_DEPENDENCIES_MESSAGE: &str = "Missing dependencies";
+
+/// All dependencies of an extension, including the dependencies of its dependencies, in the order
+/// they are found. The extension itself is not included, even in a dependency cycle.
+pub fn all_dependencies(name: &str, dependencies: &BTreeMap<String, Vec<String>>) -> Vec<String> {
+    let mut found: Vec<String> = vec![];
+    let mut to_visit: Vec<&str> = vec![name];
+    while let Some(current) = to_visit.pop() {
+        for dependency in dependencies.get(current).into_iter().flatten() {
+            if dependency != name && !found.contains(dependency) {
+                found.push(dependency.clone());
+                to_visit.push(dependency);
+            }
+        }
+    }
+    found
+}
+
+/// Sort items so that each one comes after the items it depends on. Items that are part of a
+/// dependency cycle keep their original order.
+pub fn dependency_order<T>(
+    items: Vec<T>,
+    name: impl Fn(&T) -> &str,
+    dependencies: &BTreeMap<String, Vec<String>>,
+) -> Vec<T> {
+    let mut remaining: Vec<(T, Vec<String>)> = items
+        .into_iter()
+        .map(|item| {
+            let item_dependencies = all_dependencies(name(&item), dependencies);
+            (item, item_dependencies)
+        })
+        .collect();
+    let mut ordered: Vec<T> = Vec::with_capacity(remaining.len());
+    while !remaining.is_empty() {
+        // The first item that does not depend on any other remaining item, or the first item if
+        // every remaining item is waiting on another one
+        let next = remaining
+            .iter()
+            .position(|(_, item_dependencies)| {
+                !remaining
+                    .iter()
+                    .any(|(other, _)| item_dependencies.iter().any(|d| d == name(other)))
+            })
+            .unwrap_or(0);
+        ordered.push(remaining.remove(next).0);
+    }
+    ordered
+}
+
+pub fn order_trunk_installs<'a>(
+    trunk_installs: Vec<&'a TrunkInstall>,
+    dependencies: &BTreeMap<String, Vec<String>>,
+) -> Vec<&'a TrunkInstall> {
+    dependency_order(trunk_installs, |t| t.name.as_str(), dependencies)
+}
+
+pub fn order_extensions(
+    extensions: Vec<Extension>,
+    dependencies: &BTreeMap<String, Vec<String>>,
+) -> Vec<Extension> {
+    dependency_order(extensions, |e| e.name.as_str(), dependencies)
+}
+
+/// Dependencies of an extension that are not available in the instance
+pub fn unresolved_dependencies(
+    name: &str,
+    dependencies: &BTreeMap<String, Vec<String>>,
+    available: &BTreeSet<String>,
+) -> Vec<String> {
+    all_dependencies(name, dependencies)
+        .into_iter()
+        .filter(|dependency| !available.contains(dependency))
+        .collect()
+}
+
+/// Mark the desired locations of extensions that depend on unavailable extensions as failed, so
+/// they are not toggled. Locations that were marked before and whose dependencies are now
+/// available are cleared, so they are toggled again.
+pub fn report_unresolved_dependencies(
+    desired_extensions: &[Extension],
+    ext_status_updates: Vec<ExtensionStatus>,
+    dependencies: &BTreeMap<String, Vec<String>>,
+) -> Vec<ExtensionStatus> {
+    // An extension is available when Postgres lists it in any database
+    let available: BTreeSet<String> = ext_status_updates
+        .iter()
+        .filter(|ext| ext.locations.iter().any(|loc| loc.enabled.is_some()))
+        .map(|ext| ext.name.clone())
+        .collect();
+    let mut ext_status_updates = ext_status_updates;
+    for extension_status in ext_status_updates.iter_mut() {
+        let Some(desired_extension) = desired_extensions
+            .iter()
+            .find(|ext| ext.name == extension_status.name)
+        else {
+            continue;
+        };
+        let unresolved = unresolved_dependencies(&extension_status.name, dependencies, &available);
+        for location_status in extension_status.locations.iter_mut() {
+            let wants_enabled = desired_extension
+                .locations
+                .iter()
+                .any(|loc| loc.database == location_status.database && loc.enabled);
+            if wants_enabled && !unresolved.is_empty() && location_status.enabled != Some(true) {
+                location_status.error = Some(true);
+                location_status.error_message = Some(format!(
+                    "{}: {}",
+                    UNRESOLVED_DEPENDENCIES_MESSAGE,
+                    unresolved.join(", ")
+                ));
+            } else if location_status
+                .error_message
+                .as_deref()
+                .is_some_and(|message| message.starts_with(UNRESOLVED_DEPENDENCIES_MESSAGE))
+            {
+                location_status.error = Some(false);
+                location_status.error_message = None;
+            }
+        }
+    }
+    ext_status_updates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extensions::types::{ExtensionInstallLocation, ExtensionInstallLocationStatus};
+
+    fn dependencies() -> BTreeMap<String, Vec<String>> {
+        BTreeMap::from([
+            ("vectorize".to_string(), vec![
+                "pgmq".to_string(),
+                "pg_cron".to_string(),
+            ]),
+            ("pgmq".to_string(), vec!["pg_partman".to_string()]),
+            // A cycle
+            ("a".to_string(), vec!["b".to_string()]),
+            ("b".to_string(), vec!["a".to_string()]),
+        ])
+    }
+
+    fn trunk_install(name: &str) -> TrunkInstall {
+        TrunkInstall {
+            name: name.to_string(),
+            version: Some("1.0.0".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_all_dependencies() {
+        let dependencies = dependencies();
+        assert_eq!(all_dependencies("vectorize", &dependencies), vec![
+            "pgmq",
+            "pg_cron",
+            "pg_partman"
+        ]);
+        assert_eq!(all_dependencies("a", &dependencies), vec!["b"]);
+        assert!(all_dependencies("pg_cron", &dependencies).is_empty());
+    }
+
+    #[test]
+    fn test_order_trunk_installs() {
+        let dependencies = dependencies();
+        let installs = [
+            trunk_install("vectorize"),
+            trunk_install("pg_cron"),
+            trunk_install("b"),
+            trunk_install("pg_partman"),
+            trunk_install("a"),
+            trunk_install("pgmq"),
+        ];
+        let ordered = order_trunk_installs(installs.iter().collect(), &dependencies);
+        let names: Vec<&str> = ordered.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec![
+            "pg_cron",
+            "pg_partman",
+            "pgmq",
+            "vectorize",
+            "b",
+            "a"
+        ]);
+    }
+
+    #[test]
+    fn test_report_unresolved_dependencies() {
+        let dependencies = dependencies();
+        let desired = vec![Extension {
+            name: "vectorize".to_string(),
+            description: None,
+            locations: vec![ExtensionInstallLocation {
+                enabled: true,
+                database: "postgres".to_string(),
+                schema: None,
+                version: None,
+            }],
+        }];
+        let status = |name: &str, enabled: Option<bool>| ExtensionStatus {
+            name: name.to_string(),
+            description: None,
+            locations: vec![ExtensionInstallLocationStatus {
+                database: "postgres".to_string(),
+                schema: Some("public".to_string()),
+                version: None,
+                enabled,
+                error: Some(false),
+                error_message: None,
+                target_version: None,
+                update_path: None,
+                target_schema: None,
+            }],
+        };
+
+        // pgmq and pg_partman are not available
+        let statuses = vec![status("pg_cron", Some(false)), status("vectorize", Some(false))];
+        let statuses = report_unresolved_dependencies(&desired, statuses, &dependencies);
+        let location = &statuses[1].locations[0];
+        assert_eq!(location.error, Some(true));
+        assert_eq!(
+            location.error_message.as_deref(),
+            Some("Missing dependencies: pgmq, pg_partman")
+        );
+
+        // Once they are installed, the error is cleared
+        let mut statuses = statuses;
+        statuses.push(status("pgmq", Some(false)));
+        statuses.push(status("pg_partman", Some(false)));
+        let statuses = report_unresolved_dependencies(&desired, statuses, &dependencies);
+        let location = &statuses[1].locations[0];
+        assert_eq!(location.error, Some(false));
+        assert!(location.error_message.is_none());
+    }
+}
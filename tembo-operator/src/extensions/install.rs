@@ -1,15 +1,35 @@
 use crate::{
     apis::coredb_types::CoreDB,
     cloudnativepg::cnpg::{get_fenced_pods, unfence_pod},
+    config::Config,
     extensions::{
+        database_queries::NO_UPDATE_PATH_MESSAGE,
+        dependencies::{all_dependencies, order_trunk_installs},
         kubernetes_queries::{add_trunk_install_to_status, remove_trunk_installs_from_status},
-        types::{TrunkInstall, TrunkInstallStatus},
+        types::{TrunkInstall, TrunkInstallPodAttempt, TrunkInstallStatus},
+        uninstall::{trunk_installs_to_uninstall, uninstall_trunk_install},
     },
-    Context,
+    publish_event,
+    trunk::{
+        compatible_versions, extension_dependencies, extensions_that_require_load, registry_url,
+        TrunkRegistry,
+    },
+    Context, RETRY_TRUNK_INSTALLS,
 };
+use chrono::{DateTime, Utc};
 use k8s_openapi::{api::core::v1::Pod, apimachinery::pkg::apis::meta::v1::ObjectMeta};
-use kube::{runtime::controller::Action, Api};
-use std::{collections::HashSet, sync::Arc, time::Duration};
+use kube::{
+    api::{Patch, PatchParams},
+    runtime::{controller::Action, events::EventType},
+    Api,
+};
+use serde_json::json;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    sync::Arc,
+    time::Duration,
+};
 use tracing::{debug, error, info, instrument, warn};
 
 use crate::apis::coredb_types::CoreDBStatus;
@@ -93,10 +113,9 @@ fn find_trunk_installs_to_remove_from_status(cdb: &CoreDB) -> Vec<String> {
                 return trunk_installs_to_remove_from_status;
             }
             Some(trunk_installs) => {
+                let desired_trunk_installs = desired_trunk_installs(cdb);
                 for ext_status in trunk_installs {
-                    if !cdb
-                        .spec
-                        .trunk_installs
+                    if !desired_trunk_installs
                         .iter()
                         .any(|ext| ext.name == ext_status.name)
                     {
@@ -110,11 +129,141 @@ fn find_trunk_installs_to_remove_from_status(cdb: &CoreDB) -> Vec<String> {
     trunk_installs_to_remove_from_status
 }
 
+// Longest wait between two attempts of a failed trunk install
+const MAX_TRUNK_INSTALL_BACKOFF_SECONDS: i64 = 3600;
+
+/// When failed trunk installs are attempted again
+struct TrunkInstallRetry {
+    max_attempts: i32,
+    backoff_seconds: i64,
+    // Installs to retry right away, requested with an annotation
+    forced: Vec<String>,
+}
+
+impl TrunkInstallRetry {
+    fn new(cdb: &CoreDB, cfg: &Config) -> Self {
+        let forced = cdb
+            .metadata
+            .annotations
+            .as_ref()
+            .and_then(|annotations| annotations.get(RETRY_TRUNK_INSTALLS))
+            .map(|names| {
+                names
+                    .split(',')
+                    .map(|name| name.trim().to_string())
+                    .filter(|name| !name.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        TrunkInstallRetry {
+            max_attempts: cfg.trunk_install_max_attempts,
+            backoff_seconds: cfg.trunk_install_backoff_seconds,
+            forced,
+        }
+    }
+
+    // The backoff doubles after every attempt
+    fn backoff(&self, attempts: i32) -> chrono::Duration {
+        let factor = 2i64.saturating_pow((attempts - 1).max(0) as u32);
+        chrono::Duration::seconds(
+            self.backoff_seconds
+                .saturating_mul(factor)
+                .min(MAX_TRUNK_INSTALL_BACKOFF_SECONDS),
+        )
+    }
+
+    fn is_due(&self, name: &str, attempt: &TrunkInstallPodAttempt, now: DateTime<Utc>) -> bool {
+        if self.forced.iter().any(|forced| forced == name) {
+            return true;
+        }
+        if attempt.attempts >= self.max_attempts {
+            return false;
+        }
+        match attempt.last_attempt_at {
+            None => true,
+            Some(last_attempt_at) => now >= last_attempt_at + self.backoff(attempt.attempts),
+        }
+    }
+}
+
+// Versions that extensions are updated to but whose update script is not installed, by extension
+fn missing_update_versions(cdb: &CoreDB) -> BTreeMap<String, String> {
+    cdb.status
+        .as_ref()
+        .and_then(|status| status.extensions.as_ref())
+        .into_iter()
+        .flatten()
+        .flat_map(|ext| ext.locations.iter().map(move |loc| (ext, loc)))
+        .filter(|(_, loc)| {
+            loc.error_message
+                .as_deref()
+                .is_some_and(|message| message.starts_with(NO_UPDATE_PATH_MESSAGE))
+        })
+        .filter_map(|(ext, loc)| Some((ext.name.clone(), loc.target_version.clone()?)))
+        .collect()
+}
+
+/// The trunk installs of the spec, along with the versions extensions are updated to when their
+/// update script is missing, which are installed from trunk to provide it. Once the extension is
+/// updated, a trunk install of the spec stays at that version instead of going back to the older one.
+fn desired_trunk_installs(cdb: &CoreDB) -> Vec<TrunkInstall> {
+    let mut missing_update_versions = missing_update_versions(cdb);
+    let trunk_install_statuses = cdb
+        .status
+        .as_ref()
+        .and_then(|status| status.trunk_installs.as_ref())
+        .cloned()
+        .unwrap_or_default();
+    let updated_to = |name: &str| {
+        trunk_install_statuses
+            .iter()
+            .filter(|ext_status| ext_status.name == name && !ext_status.error)
+            .filter_map(|ext_status| ext_status.version.clone())
+            .find(|version| {
+                cdb.spec.extensions.iter().any(|ext| {
+                    ext.name == name
+                        && ext
+                            .locations
+                            .iter()
+                            .any(|loc| loc.version.as_ref() == Some(version))
+                })
+            })
+    };
+
+    let mut trunk_installs: Vec<TrunkInstall> = cdb
+        .spec
+        .trunk_installs
+        .iter()
+        .map(|ext| TrunkInstall {
+            name: ext.name.clone(),
+            version: missing_update_versions
+                .remove(&ext.name)
+                .or_else(|| updated_to(&ext.name))
+                .or_else(|| ext.version.clone()),
+        })
+        .collect();
+    trunk_installs.extend(
+        missing_update_versions
+            .into_iter()
+            .map(|(name, version)| TrunkInstall {
+                name,
+                version: Some(version),
+            }),
+    );
+    trunk_installs
+}
+
 /// Find all trunk installs to install on a pod and return a Vec of TrunkInstall
 /// This function also needs to define a lifetime, since we are only returning a reference to
-/// TrunkInstall, which is owned by CoreDB we only need to define a lifetime for CoreDB
-#[instrument(skip(cdb, pod_name) fields(trace_id))]
-fn find_trunk_installs_to_pod<'a>(cdb: &'a CoreDB, pod_name: &str) -> Vec<&'a TrunkInstall> {
+/// TrunkInstall, which is owned by the desired trunk installs we only need to define a lifetime for them
+#[instrument(skip(cdb, trunk_installs, pod_name, retry) fields(trace_id))]
+fn find_trunk_installs_to_pod<'a>(
+    cdb: &CoreDB,
+    trunk_installs: &'a [TrunkInstall],
+    pod_name: &str,
+    retry: &TrunkInstallRetry,
+    now: DateTime<Utc>,
+) -> Vec<&'a TrunkInstall> {
     debug!(
         "Checking for trunk installs to install on pod {} for {}",
         pod_name,
@@ -122,25 +271,47 @@ fn find_trunk_installs_to_pod<'a>(cdb: &'a CoreDB, pod_name: &str) -> Vec<&'a Tr
     );
 
     let mut trunk_installs_to_install = Vec::new();
+    let trunk_install_statuses = cdb
+        .status
+        .clone()
+        .unwrap_or_default()
+        .trunk_installs
+        .unwrap_or_default();
 
-    // Get extensions in spec.trunk_install that are not in status.trunk_install
-    for ext in cdb.spec.trunk_installs.iter() {
-        if !cdb
-            .status
-            .clone()
-            .unwrap_or_default()
-            .trunk_installs
-            .unwrap_or_default()
+    // Get extensions in spec.trunk_install that are not in status.trunk_install, or that were
+    // installed with another version
+    for ext in trunk_installs {
+        let ext_status = trunk_install_statuses
             .iter()
-            .any(|ext_status| {
-                ext.name == ext_status.name
-                    && ext_status
-                        .installed_to_pods
-                        .clone()
-                        .unwrap_or_default()
-                        .contains(&pod_name.to_string())
-            })
-        {
+            .find(|ext_status| ext.name == ext_status.name && ext.version == ext_status.version);
+        let Some(ext_status) = ext_status.filter(|ext_status| {
+            ext_status
+                .installed_to_pods
+                .clone()
+                .unwrap_or_default()
+                .contains(&pod_name.to_string())
+        }) else {
+            trunk_installs_to_install.push(ext);
+            continue;
+        };
+        // Retry installs that failed on this pod once their backoff is over
+        let attempt = ext_status
+            .attempts
+            .iter()
+            .flatten()
+            .find(|attempt| attempt.pod_name == pod_name);
+        let retry_due = match attempt {
+            Some(attempt) => attempt.last_error.is_some() && retry.is_due(&ext.name, attempt, now),
+            // Installs that failed before attempts were recorded are retried once
+            None => ext_status.error,
+        };
+        if retry_due {
+            info!(
+                "Retrying trunk install {} on pod {} for {}",
+                ext.name,
+                pod_name,
+                cdb.metadata.name.clone().expect("CoreDB should have a name")
+            );
             trunk_installs_to_install.push(ext);
         }
     }
@@ -148,6 +319,40 @@ fn find_trunk_installs_to_pod<'a>(cdb: &'a CoreDB, pod_name: &str) -> Vec<&'a Tr
     trunk_installs_to_install
 }
 
+// Record this attempt on the pod in the status, counting the previous attempts of the same version
+fn record_attempt(
+    cdb: &CoreDB,
+    trunk_install_status: TrunkInstallStatus,
+    pod_name: &str,
+    now: DateTime<Utc>,
+) -> TrunkInstallStatus {
+    let previous_attempts = cdb
+        .status
+        .as_ref()
+        .and_then(|status| status.trunk_installs.as_ref())
+        .into_iter()
+        .flatten()
+        .filter(|ext_status| {
+            ext_status.name == trunk_install_status.name && ext_status.version == trunk_install_status.version
+        })
+        .flat_map(|ext_status| ext_status.attempts.iter().flatten())
+        .find(|attempt| attempt.pod_name == pod_name)
+        .map_or(0, |attempt| attempt.attempts);
+    let last_error = match trunk_install_status.error {
+        true => trunk_install_status.error_message.clone(),
+        false => None,
+    };
+    TrunkInstallStatus {
+        attempts: Some(vec![TrunkInstallPodAttempt {
+            pod_name: pod_name.to_string(),
+            attempts: previous_attempts + 1,
+            last_attempt_at: Some(now),
+            last_error,
+        }]),
+        ..trunk_install_status
+    }
+}
+
 // is_pod_fenced function checks if a pod is fenced and returns a bool or requeue action
 #[instrument(skip(cdb, ctx, pod_name) fields(trace_id, pod_name))]
 async fn is_pod_fenced(cdb: &CoreDB, ctx: Arc<Context>, pod_name: &str) -> Result<bool, Action> {
@@ -171,11 +376,14 @@ async fn is_pod_fenced(cdb: &CoreDB, ctx: Arc<Context>, pod_name: &str) -> Resul
     Ok(false)
 }
 
-#[instrument(skip(ctx, cdb))]
+/// Install the trunk installs of the spec and uninstall the removed ones. Returns the trunk install
+/// statuses, and the reasons why removed trunk installs could not be uninstalled.
+#[instrument(skip(ctx, cdb, cfg))]
 pub async fn reconcile_trunk_installs(
     cdb: &CoreDB,
     ctx: Arc<Context>,
-) -> Result<Vec<TrunkInstallStatus>, Action> {
+    cfg: &Config,
+) -> Result<(Vec<TrunkInstallStatus>, Vec<String>), Action> {
     let instance_name = cdb.metadata.name.clone().expect("CoreDB should have a name");
 
     debug!("Starting to reconcile trunk installs for {}", instance_name);
@@ -188,11 +396,54 @@ pub async fn reconcile_trunk_installs(
             .expect("CoreDB should have a namespace"),
     );
 
+    // Get all fenced and non-fenced pods for this instance
+    let all_pods = all_fenced_and_non_fenced_pods(cdb, ctx.clone()).await?;
+
     // Get extensions in status.trunk_install that are not in spec
     // Deleting them from status allows for retrying installation
     // by first removing the extension from the spec, then adding it back
-    // Get trunk installs to remove from status
-    let trunk_installs_to_remove_from_status = find_trunk_installs_to_remove_from_status(cdb);
+    // They are uninstalled first, unless they are still enabled
+    let requires_load = extensions_that_require_load(
+        ctx.client.clone(),
+        &cdb.metadata
+            .namespace
+            .clone()
+            .expect("CoreDB should have a namespace"),
+    )
+    .await?;
+    // The registry is shared by the installs and uninstalls of this reconcile
+    let registry = TrunkRegistry::from_config(cfg).map_err(|e| {
+        error!(
+            "Failed to configure the trunk registry for {}: {}",
+            instance_name, e
+        );
+        Action::requeue(Duration::from_secs(300))
+    })?;
+    let trunk_installs_to_uninstall = trunk_installs_to_uninstall(cdb);
+    let mut trunk_installs_to_remove_from_status = Vec::new();
+    let mut uninstalls_blocked = Vec::new();
+    for name in find_trunk_installs_to_remove_from_status(cdb) {
+        if !trunk_installs_to_uninstall.contains(&name) {
+            warn!(
+                "Trunk install {} is still enabled on {}, removing it from status without uninstalling",
+                name, instance_name
+            );
+            trunk_installs_to_remove_from_status.push(name);
+        } else if uninstall_trunk_install(
+            cdb,
+            ctx.clone(),
+            cfg,
+            &registry,
+            &name,
+            &requires_load,
+            &all_pods,
+            &mut uninstalls_blocked,
+        )
+        .await?
+        {
+            trunk_installs_to_remove_from_status.push(name);
+        }
+    }
 
     // Remove extensions from status
     remove_trunk_installs_from_status(
@@ -205,15 +456,31 @@ pub async fn reconcile_trunk_installs(
     // Get extensions in spec.trunk_install that are not in status.trunk_install
     let mut all_results = Vec::new();
 
-    // Get all fenced and non-fenced pods for this instance
-    let all_pods = all_fenced_and_non_fenced_pods(cdb, ctx.clone()).await?;
+    // Dependencies are installed before the extensions that require them
+    let dependencies = extension_dependencies(
+        ctx.client.clone(),
+        &cdb.metadata
+            .namespace
+            .clone()
+            .expect("CoreDB should have a namespace"),
+    )
+    .await?;
+
+    // Failed installs are retried with backoff, or right away when requested with an annotation
+    let retry = TrunkInstallRetry::new(cdb, cfg);
+
+    // Versions that extensions are updated to are installed when their update script is missing
+    let desired_trunk_installs = desired_trunk_installs(cdb);
 
     // Loop through all pods and install missing trunk installs
     for pod in all_pods {
         let pod_name = pod.metadata.name.expect("Pod should always have a name");
 
         // Filter trunk installs that are not yet installed on this instance
-        let trunk_installs_to_pod = find_trunk_installs_to_pod(cdb, &pod_name);
+        let trunk_installs_to_pod = order_trunk_installs(
+            find_trunk_installs_to_pod(cdb, &desired_trunk_installs, &pod_name, &retry, Utc::now()),
+            &dependencies,
+        );
 
         if trunk_installs_to_pod.is_empty() {
             debug!(
@@ -229,7 +496,17 @@ pub async fn reconcile_trunk_installs(
         }
 
         // Install missing trunk installs
-        match install_extensions_to_pod(cdb, trunk_installs_to_pod, &ctx, pod_name.clone()).await {
+        match install_extensions_to_pod(
+            cdb,
+            trunk_installs_to_pod,
+            &dependencies,
+            &ctx,
+            cfg,
+            &registry,
+            pod_name.clone(),
+        )
+        .await
+        {
             Ok(result) => {
                 all_results = result;
             }
@@ -237,6 +514,24 @@ pub async fn reconcile_trunk_installs(
         };
     }
 
+    // The requested retries were attempted on every pod
+    if !retry.forced.is_empty() {
+        let patch = json!({
+            "metadata": {
+                "annotations": {
+                    RETRY_TRUNK_INSTALLS: null
+                }
+            }
+        });
+        coredb_api
+            .patch(&instance_name, &PatchParams::default(), &Patch::Merge(&patch))
+            .await
+            .map_err(|e| {
+                error!("Error removing {} annotation: {:?}", RETRY_TRUNK_INSTALLS, e);
+                Action::requeue(Duration::from_secs(10))
+            })?;
+    }
+
     info!(
         "Completed trunk install reconciliation for instance {}",
         instance_name
@@ -254,7 +549,7 @@ pub async fn reconcile_trunk_installs(
             .clone()
             .unwrap_or_default();
     }
-    Ok(all_results)
+    Ok((all_results, uninstalls_blocked))
 }
 
 // initializing current_trunk_install_statuses from CoreDB status and return a Vec of TrunkInstallStatus
@@ -276,12 +571,51 @@ fn initialize_trunk_install_statuses(cdb: &CoreDB, coredb_name: &str) -> Vec<Tru
         })
 }
 
+// Where the CA bundle of the registry is written in the pod for trunk install
+const TRUNK_REGISTRY_CA_BUNDLE_PATH: &str = "/tmp/trunk-registry-ca.pem";
+
+/// The trunk install command run in a pod, with the registry of the operator. The CA bundle of the
+/// registry is written in the pod and trusted through SSL_CERT_FILE, it is given as an argument of
+/// the shell so it is not parsed by it. The headers of the registry are only sent by the operator,
+/// the trunk CLI has no way to send them and the command is logged when it fails.
+fn trunk_install_command(cfg: &Config, ca_bundle: Option<&str>, name: &str, version: &str) -> Vec<String> {
+    let trunk_install = vec![
+        "trunk".to_owned(),
+        "install".to_owned(),
+        "-r".to_owned(),
+        registry_url(cfg),
+        name.to_owned(),
+        "--version".to_owned(),
+        version.to_owned(),
+    ];
+
+    match ca_bundle {
+        Some(ca_bundle) => [
+            "sh".to_owned(),
+            "-c".to_owned(),
+            format!(
+                "printf '%s' \"$1\" > {} && shift && exec env \"$@\"",
+                TRUNK_REGISTRY_CA_BUNDLE_PATH
+            ),
+            "sh".to_owned(),
+            ca_bundle.to_owned(),
+            format!("SSL_CERT_FILE={}", TRUNK_REGISTRY_CA_BUNDLE_PATH),
+        ]
+        .into_iter()
+        .chain(trunk_install)
+        .collect(),
+        None => trunk_install,
+    }
+}
+
 /// execute_extension_install_command function executes the trunk install command and returns a
 /// TrunkInstallStatus or bool
-#[instrument(skip(cdb, ctx, coredb_name, ext, pod_name) fields(trace_id))]
+#[instrument(skip(cdb, ctx, cfg, ca_bundle, coredb_name, ext, pod_name) fields(trace_id))]
 async fn execute_extension_install_command(
     cdb: &CoreDB,
     ctx: Arc<Context>,
+    cfg: &Config,
+    ca_bundle: Option<&str>,
     coredb_name: &str,
     ext: &TrunkInstall,
     pod_name: &str,
@@ -302,19 +636,13 @@ async fn execute_extension_install_command(
                 loading: false,
                 error_message: Some("Missing version".to_string()),
                 installed_to_pods: Some(vec![pod_name.to_string()]),
+                attempts: None,
             });
         }
         Some(version) => version.clone(),
     };
 
-    let cmd = vec![
-        "trunk".to_owned(),
-        "install".to_owned(),
-        "-r https://registry.pgtrunk.io".to_owned(),
-        ext.name.clone(),
-        "--version".to_owned(),
-        version,
-    ];
+    let cmd = trunk_install_command(cfg, ca_bundle, &ext.name, &version);
 
     // If the pod is not up yet, do not try and install the extension
     if let Err(e) = cdb.log_pod_status(client.clone(), pod_name).await {
@@ -350,6 +678,7 @@ async fn execute_extension_install_command(
                     loading: false,
                     error_message: None,
                     installed_to_pods: Some(vec![pod_name.to_string()]),
+                    attempts: None,
                 }
             } else {
                 error!(
@@ -363,6 +692,7 @@ async fn execute_extension_install_command(
                     error_message: Some(output),
                     loading: false,
                     installed_to_pods: Some(vec![pod_name.to_string()]),
+                    attempts: None,
                 }
             };
 
@@ -377,12 +707,123 @@ async fn execute_extension_install_command(
         }
     }
 }
-/// handles installing extensions
-#[instrument(skip(ctx, cdb) fields(trace_id))]
+// Returns the status of a trunk install that is skipped because one of its dependencies failed to install
+fn dependency_failed_status(
+    ext: &TrunkInstall,
+    dependencies: &BTreeMap<String, Vec<String>>,
+    failed: &[String],
+    pod_name: &str,
+) -> Option<TrunkInstallStatus> {
+    let failed_dependencies: Vec<String> = all_dependencies(&ext.name, dependencies)
+        .into_iter()
+        .filter(|dependency| failed.contains(dependency))
+        .collect();
+    if failed_dependencies.is_empty() {
+        return None;
+    }
+    Some(TrunkInstallStatus {
+        name: ext.name.clone(),
+        version: ext.version.clone(),
+        error: true,
+        loading: false,
+        error_message: Some(format!(
+            "Dependencies failed to install: {}",
+            failed_dependencies.join(", ")
+        )),
+        installed_to_pods: Some(vec![pod_name.to_string()]),
+        attempts: None,
+    })
+}
+
+// Parse the major version from the output of pg_config --version, like PostgreSQL 15.4
+fn parse_postgres_major_version(output: &str) -> Option<u32> {
+    output
+        .split_whitespace()
+        .nth(1)?
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// The major version of Postgres in the image of a pod
+async fn postgres_major_version(cdb: &CoreDB, ctx: Arc<Context>, pod_name: &str) -> Result<u32, Action> {
+    let coredb_name = cdb.metadata.name.clone().expect("CoreDB should have a name");
+    let cmd = vec!["pg_config".to_owned(), "--version".to_owned()];
+    let output = match cdb.exec(pod_name.to_string(), ctx.client.clone(), &cmd).await {
+        Ok(output) if output.success => output.stdout.unwrap_or_default(),
+        Ok(output) => {
+            warn!(
+                "Failed to get the Postgres version of {} for {}: {}",
+                pod_name,
+                coredb_name,
+                output.stderr.unwrap_or_default()
+            );
+            return Err(Action::requeue(Duration::from_secs(10)));
+        }
+        Err(e) => {
+            warn!(
+                "Kube exec error getting the Postgres version of {} for {}: {}",
+                pod_name, coredb_name, e
+            );
+            return Err(Action::requeue(Duration::from_secs(10)));
+        }
+    };
+    parse_postgres_major_version(&output).ok_or_else(|| {
+        error!(
+            "Could not parse the Postgres version of {} for {}: {}",
+            pod_name, coredb_name, output
+        );
+        Action::requeue(Duration::from_secs(300))
+    })
+}
+
+// Refuse to install a version of an extension that was not built for the Postgres of the image
+fn incompatible_version_status(
+    ext: &TrunkInstall,
+    compatible_versions: &[String],
+    postgres_major_version: u32,
+    pod_name: &str,
+) -> Option<TrunkInstallStatus> {
+    // A missing version is reported by the install
+    let version = ext.version.as_ref()?;
+    if compatible_versions.contains(version) {
+        return None;
+    }
+    let error_message = if compatible_versions.is_empty() {
+        format!(
+            "Version {} of {} is not compatible with Postgres {}, no version is available for Postgres {}",
+            version, ext.name, postgres_major_version, postgres_major_version
+        )
+    } else {
+        format!(
+            "Version {} of {} is not compatible with Postgres {}, compatible versions: {}",
+            version,
+            ext.name,
+            postgres_major_version,
+            compatible_versions.join(", ")
+        )
+    };
+    Some(TrunkInstallStatus {
+        name: ext.name.clone(),
+        version: ext.version.clone(),
+        error: true,
+        loading: false,
+        error_message: Some(error_message),
+        installed_to_pods: Some(vec![pod_name.to_string()]),
+        attempts: None,
+    })
+}
+
+/// handles installing extensions, trunk_installs should be in dependency order
+#[instrument(skip(ctx, cdb, dependencies, cfg, registry) fields(trace_id))]
 pub async fn install_extensions_to_pod(
     cdb: &CoreDB,
     trunk_installs: Vec<&TrunkInstall>,
+    dependencies: &BTreeMap<String, Vec<String>>,
     ctx: &Arc<Context>,
+    cfg: &Config,
+    registry: &TrunkRegistry,
     pod_name: String,
 ) -> Result<Vec<TrunkInstallStatus>, Action> {
     let coredb_name = cdb.metadata.name.clone().expect("CoreDB should have a name");
@@ -403,36 +844,151 @@ pub async fn install_extensions_to_pod(
     }
     info!("Installing extensions into {}: {:?}", coredb_name, trunk_installs);
 
+    // The pods trust the same CA as the operator to reach the registry
+    let ca_bundle = match &cfg.trunk_registry_ca_bundle {
+        Some(path) => Some(fs::read_to_string(path).map_err(|e| {
+            error!("Failed to read the trunk registry CA bundle {}: {}", path, e);
+            Action::requeue(Duration::from_secs(300))
+        })?),
+        None => None,
+    };
+
+    // Only versions built for the Postgres of the image are installed
+    let postgres_major_version = postgres_major_version(cdb, ctx.clone(), &pod_name).await?;
+
     let mut requeue = false;
+    let mut failed: Vec<String> = vec![];
+    let mut installed: Vec<&str> = vec![];
     for ext in trunk_installs.iter() {
+        // Don't install extensions whose dependencies could not be installed
+        if let Some(trunk_install_status) = dependency_failed_status(ext, dependencies, &failed, &pod_name) {
+            let trunk_install_status = record_attempt(cdb, trunk_install_status, &pod_name, Utc::now());
+            warn!(
+                "Skipping install of {} on {}: {:?}",
+                ext.name, coredb_name, trunk_install_status.error_message
+            );
+            failed.push(ext.name.clone());
+            current_trunk_install_statuses =
+                add_trunk_install_to_status(&coredb_api, &coredb_name, &trunk_install_status).await?;
+            continue;
+        }
+
+        match compatible_versions(registry, &ext.name, postgres_major_version).await {
+            Ok(versions) => {
+                if let Some(trunk_install_status) =
+                    incompatible_version_status(ext, &versions, postgres_major_version, &pod_name)
+                {
+                    let trunk_install_status =
+                        record_attempt(cdb, trunk_install_status, &pod_name, Utc::now());
+                    let error_message = trunk_install_status.error_message.clone().unwrap_or_default();
+                    warn!(
+                        "Skipping install of {} on {}: {}",
+                        ext.name, coredb_name, error_message
+                    );
+                    publish_event(
+                        cdb,
+                        ctx.clone(),
+                        EventType::Warning,
+                        "TrunkInstallIncompatible",
+                        error_message,
+                    )
+                    .await;
+                    failed.push(ext.name.clone());
+                    current_trunk_install_statuses =
+                        add_trunk_install_to_status(&coredb_api, &coredb_name, &trunk_install_status).await?;
+                    continue;
+                }
+            }
+            // The install itself reports a version that does not exist
+            Err(e) => warn!(
+                "Could not check the compatibility of {} with Postgres {} on {}: {}",
+                ext.name, postgres_major_version, coredb_name, e
+            ),
+        }
+
         info!("Attempting to install extension: {} on {}", ext.name, coredb_name);
 
         // Execute trunk install command
-        match execute_extension_install_command(cdb, ctx.clone(), &coredb_name, ext, &pod_name).await {
+        match execute_extension_install_command(
+            cdb,
+            ctx.clone(),
+            cfg,
+            ca_bundle.as_deref(),
+            &coredb_name,
+            ext,
+            &pod_name,
+        )
+        .await
+        {
             Ok(trunk_install_status) => {
+                let trunk_install_status = record_attempt(cdb, trunk_install_status, &pod_name, Utc::now());
                 if trunk_install_status.error {
                     // Log and continue to the next iteration
                     warn!(
                         "Error occurred during installation: {:?}",
                         trunk_install_status.error_message
                     );
+                    publish_event(
+                        cdb,
+                        ctx.clone(),
+                        EventType::Warning,
+                        "TrunkInstallFailed",
+                        format!(
+                            "Failed to install {} into {}: {}",
+                            ext.name,
+                            pod_name,
+                            trunk_install_status.error_message.clone().unwrap_or_default()
+                        ),
+                    )
+                    .await;
+                    failed.push(ext.name.clone());
                     current_trunk_install_statuses =
                         add_trunk_install_to_status(&coredb_api, &coredb_name, &trunk_install_status).await?;
                     continue;
                 }
                 current_trunk_install_statuses =
                     add_trunk_install_to_status(&coredb_api, &coredb_name, &trunk_install_status).await?;
+                installed.push(&ext.name);
             }
             Err(should_requeue) => {
+                failed.push(ext.name.clone());
                 requeue = should_requeue;
             }
         }
     }
     if requeue {
         warn!("Requeueing due to errors for instance {}", coredb_name);
+        publish_event(
+            cdb,
+            ctx.clone(),
+            EventType::Warning,
+            "TrunkInstallError",
+            format!("Could not run trunk installs on {}, requeuing", pod_name),
+        )
+        .await;
         return Err(Action::requeue(Duration::from_secs(10)));
     }
-    info!("Successfully installed all extensions to {}", pod_name);
+    if failed.is_empty() {
+        info!("Successfully installed all extensions to {}", pod_name);
+    } else {
+        warn!(
+            "Installed {} of {} extensions to {}, failed: {}",
+            installed.len(),
+            trunk_installs.len(),
+            pod_name,
+            failed.join(", ")
+        );
+    }
+    if !installed.is_empty() {
+        publish_event(
+            cdb,
+            ctx.clone(),
+            EventType::Normal,
+            "TrunkInstallsComplete",
+            format!("Installed {} into {}", installed.join(", "), pod_name),
+        )
+        .await;
+    }
 
     // Check for fenced pods and unfence it
     let fenced_pods = get_fenced_pods(cdb, ctx.clone()).await?;
@@ -450,7 +1006,84 @@ pub async fn install_extensions_to_pod(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::apis::coredb_types::CoreDBSpec;
+    use crate::{
+        apis::coredb_types::CoreDBSpec,
+        extensions::types::{
+            Extension, ExtensionInstallLocation, ExtensionInstallLocationStatus, ExtensionStatus,
+        },
+    };
+
+    #[test]
+    fn test_dependency_failed_status() {
+        let dependencies = BTreeMap::from([("vectorize".to_string(), vec![
+            "pgmq".to_string(),
+            "pg_cron".to_string(),
+        ])]);
+        let vectorize = TrunkInstall {
+            name: "vectorize".to_string(),
+            version: Some("0.9.0".to_string()),
+        };
+        assert!(dependency_failed_status(&vectorize, &dependencies, &[], "pod1").is_none());
+        assert!(
+            dependency_failed_status(&vectorize, &dependencies, &["pg_partman".to_string()], "pod1")
+                .is_none()
+        );
+
+        let status =
+            dependency_failed_status(&vectorize, &dependencies, &["pgmq".to_string()], "pod1").unwrap();
+        assert!(status.error);
+        assert_eq!(
+            status.error_message,
+            Some("Dependencies failed to install: pgmq".to_string())
+        );
+        assert_eq!(status.installed_to_pods, Some(vec!["pod1".to_string()]));
+    }
+
+    #[test]
+    fn test_parse_postgres_major_version() {
+        assert_eq!(parse_postgres_major_version("PostgreSQL 15.4\n"), Some(15));
+        assert_eq!(parse_postgres_major_version("PostgreSQL 16beta1"), Some(16));
+        assert_eq!(
+            parse_postgres_major_version("PostgreSQL 14.9 (Ubuntu 14.9-1.pgdg22.04+1)"),
+            Some(14)
+        );
+        assert_eq!(parse_postgres_major_version(""), None);
+        assert_eq!(parse_postgres_major_version("PostgreSQL"), None);
+    }
+
+    #[test]
+    fn test_incompatible_version_status() {
+        let pgmq = TrunkInstall {
+            name: "pgmq".to_string(),
+            version: Some("0.33.3".to_string()),
+        };
+        let compatible = vec!["1.0.0".to_string(), "1.1.1".to_string()];
+        let status = incompatible_version_status(&pgmq, &compatible, 16, "pod1").unwrap();
+        assert!(status.error);
+        assert_eq!(
+            status.error_message,
+            Some("Version 0.33.3 of pgmq is not compatible with Postgres 16, compatible versions: 1.0.0, 1.1.1".to_string())
+        );
+        let status = incompatible_version_status(&pgmq, &[], 16, "pod1").unwrap();
+        assert_eq!(
+            status.error_message,
+            Some(
+                "Version 0.33.3 of pgmq is not compatible with Postgres 16, no version is available for Postgres 16"
+                    .to_string()
+            )
+        );
+
+        let pgmq = TrunkInstall {
+            name: "pgmq".to_string(),
+            version: Some("1.1.1".to_string()),
+        };
+        assert!(incompatible_version_status(&pgmq, &compatible, 16, "pod1").is_none());
+        let pgmq = TrunkInstall {
+            name: "pgmq".to_string(),
+            version: None,
+        };
+        assert!(incompatible_version_status(&pgmq, &compatible, 16, "pod1").is_none());
+    }
 
     #[test]
     fn test_merge_and_deduplicate_pods() {
@@ -506,6 +1139,7 @@ mod tests {
             error_message: None,
             loading: false,
             installed_to_pods: Some(vec!["test-coredb-24631-1".to_string()]),
+            attempts: None,
         };
 
         let trunk_install_status2 = TrunkInstallStatus {
@@ -515,6 +1149,7 @@ mod tests {
             error_message: None,
             loading: false,
             installed_to_pods: Some(vec!["test-coredb-24631-1".to_string()]),
+            attempts: None,
         };
 
         let trunk_install_status3 = TrunkInstallStatus {
@@ -524,6 +1159,7 @@ mod tests {
             loading: false,
             error_message: None,
             installed_to_pods: Some(vec!["test-coredb-24631-1".to_string()]),
+            attempts: None,
         };
 
         let cdb = CoreDB {
@@ -553,6 +1189,77 @@ mod tests {
         assert_eq!(result[0], "install3");
     }
 
+    fn trunk_install_retry(forced: Vec<String>) -> TrunkInstallRetry {
+        TrunkInstallRetry {
+            max_attempts: 3,
+            backoff_seconds: 30,
+            forced,
+        }
+    }
+
+    #[test]
+    fn test_trunk_install_retry() {
+        let now = Utc::now();
+        let attempt = |attempts: i32, seconds_ago: i64| TrunkInstallPodAttempt {
+            pod_name: "test-coredb-24631-1".to_string(),
+            attempts,
+            last_attempt_at: Some(now - chrono::Duration::seconds(seconds_ago)),
+            last_error: Some("Failed".to_string()),
+        };
+        let retry = trunk_install_retry(vec![]);
+        // 30s after the first attempt, 60s after the second
+        assert!(!retry.is_due("pgml", &attempt(1, 29), now));
+        assert!(retry.is_due("pgml", &attempt(1, 30), now));
+        assert!(!retry.is_due("pgml", &attempt(2, 59), now));
+        assert!(retry.is_due("pgml", &attempt(2, 60), now));
+        // No more attempts after the maximum
+        assert!(!retry.is_due("pgml", &attempt(3, 3600), now));
+        // Unless retried with the annotation
+        let forced = trunk_install_retry(vec!["pgml".to_string()]);
+        assert!(forced.is_due("pgml", &attempt(3, 0), now));
+        assert!(!forced.is_due("pgmq", &attempt(3, 0), now));
+        // The backoff is capped
+        assert_eq!(retry.backoff(30).num_seconds(), MAX_TRUNK_INSTALL_BACKOFF_SECONDS);
+    }
+
+    #[test]
+    fn test_record_attempt() {
+        let now = Utc::now();
+        let status = |attempts: Option<Vec<TrunkInstallPodAttempt>>| TrunkInstallStatus {
+            name: "pgml".to_string(),
+            version: Some("2.7.1".to_string()),
+            error: true,
+            loading: false,
+            error_message: Some("Failed".to_string()),
+            installed_to_pods: Some(vec!["test-coredb-24631-1".to_string()]),
+            attempts,
+        };
+        let cdb = CoreDB {
+            metadata: ObjectMeta::default(),
+            spec: CoreDBSpec::default(),
+            status: Some(CoreDBStatus {
+                trunk_installs: Some(vec![status(Some(vec![TrunkInstallPodAttempt {
+                    pod_name: "test-coredb-24631-1".to_string(),
+                    attempts: 2,
+                    last_attempt_at: None,
+                    last_error: Some("Failed".to_string()),
+                }]))]),
+                ..Default::default()
+            }),
+        };
+
+        let recorded = record_attempt(&cdb, status(None), "test-coredb-24631-1", now);
+        assert_eq!(
+            recorded.attempts,
+            Some(vec![TrunkInstallPodAttempt {
+                pod_name: "test-coredb-24631-1".to_string(),
+                attempts: 3,
+                last_attempt_at: Some(now),
+                last_error: Some("Failed".to_string()),
+            }])
+        );
+    }
+
     #[test]
     fn test_find_trunk_installs_to_pod() {
         // Arrange
@@ -576,6 +1283,7 @@ mod tests {
             error_message: None,
             loading: false,
             installed_to_pods: Some(vec!["test-coredb-24631-1".to_string()]),
+            attempts: None,
         };
 
         let cdb = CoreDB {
@@ -599,12 +1307,197 @@ mod tests {
         let pod_name = "test-coredb-24631-1";
 
         // Act
-        let result = find_trunk_installs_to_pod(&cdb, pod_name);
+        let result = find_trunk_installs_to_pod(
+            &cdb,
+            &cdb.spec.trunk_installs,
+            pod_name,
+            &trunk_install_retry(vec![]),
+            Utc::now(),
+        );
 
         // Assert
         assert_eq!(result.len(), 2);
         assert_eq!(result[0].name, "install2");
         assert_eq!(result[1].name, "install3");
+
+        // A new version is installed again
+        let mut cdb = cdb;
+        cdb.spec.trunk_installs[0].version = Some("1.1".to_string());
+        let result = find_trunk_installs_to_pod(
+            &cdb,
+            &cdb.spec.trunk_installs,
+            pod_name,
+            &trunk_install_retry(vec![]),
+            Utc::now(),
+        );
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].name, "install1");
+
+        // A failed install is retried once the backoff is over
+        cdb.spec.trunk_installs[0].version = Some("1.0".to_string());
+        let mut failed_status = trunk_install_status1;
+        failed_status.error = true;
+        failed_status.attempts = Some(vec![TrunkInstallPodAttempt {
+            pod_name: pod_name.to_string(),
+            attempts: 1,
+            last_attempt_at: Some(Utc::now() - chrono::Duration::seconds(60)),
+            last_error: Some("Failed".to_string()),
+        }]);
+        cdb.status = Some(CoreDBStatus {
+            trunk_installs: Some(vec![failed_status]),
+            ..Default::default()
+        });
+        let result = find_trunk_installs_to_pod(
+            &cdb,
+            &cdb.spec.trunk_installs,
+            pod_name,
+            &trunk_install_retry(vec![]),
+            Utc::now(),
+        );
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].name, "install1");
+    }
+
+    #[test]
+    fn test_trunk_install_command() {
+        let mut cfg = Config {
+            trunk_registry_scheme: "https".to_string(),
+            trunk_registry_domain: "registry.example.com".to_string(),
+            trunk_registry_ca_bundle: None,
+            trunk_registry_headers: None,
+            ..Config::default()
+        };
+        let trunk_install = [
+            "trunk",
+            "install",
+            "-r",
+            "https://registry.example.com",
+            "pgmq",
+            "--version",
+            "1.0.0",
+        ];
+        assert_eq!(trunk_install_command(&cfg, None, "pgmq", "1.0.0"), trunk_install);
+
+        // The headers are only sent by the operator, they must not end up in the logged command
+        cfg.trunk_registry_headers = Some("Authorization: Bearer token".to_string());
+        assert_eq!(trunk_install_command(&cfg, None, "pgmq", "1.0.0"), trunk_install);
+
+        let command = trunk_install_command(&cfg, Some("-----BEGIN CERTIFICATE-----"), "pgmq", "1.0.0");
+        assert_eq!(command[..2], ["sh", "-c"]);
+        assert_eq!(command[3..6], [
+            "sh",
+            "-----BEGIN CERTIFICATE-----",
+            "SSL_CERT_FILE=/tmp/trunk-registry-ca.pem",
+        ]);
+        assert_eq!(command[6..], trunk_install);
+    }
+
+    #[test]
+    fn test_desired_trunk_installs() {
+        let location_status = |version: &str, error_message: Option<String>| ExtensionInstallLocationStatus {
+            database: "postgres".to_string(),
+            schema: Some("public".to_string()),
+            version: Some(version.to_string()),
+            enabled: Some(true),
+            error: Some(error_message.is_some()),
+            error_message,
+            target_version: Some("1.1".to_string()),
+            update_path: None,
+            target_schema: None,
+        };
+        let missing_path = Some(format!(
+            "{} from version 1.0 to 1.1, the target version may not be installed",
+            NO_UPDATE_PATH_MESSAGE
+        ));
+        let trunk_install_status = |name: &str, version: &str| TrunkInstallStatus {
+            name: name.to_string(),
+            version: Some(version.to_string()),
+            error: false,
+            error_message: None,
+            loading: false,
+            installed_to_pods: Some(vec!["test-coredb-24631-1".to_string()]),
+            attempts: None,
+        };
+        let mut cdb = CoreDB::new("test", CoreDBSpec {
+            trunk_installs: vec![TrunkInstall {
+                name: "pg_partman".to_string(),
+                version: Some("1.0".to_string()),
+            }],
+            extensions: vec![Extension {
+                name: "pg_partman".to_string(),
+                description: None,
+                locations: vec![ExtensionInstallLocation {
+                    enabled: true,
+                    database: "postgres".to_string(),
+                    version: Some("1.1".to_string()),
+                    schema: None,
+                }],
+            }],
+            ..CoreDBSpec::default()
+        });
+        cdb.status = Some(CoreDBStatus {
+            extensions: Some(vec![
+                ExtensionStatus {
+                    name: "pg_partman".to_string(),
+                    description: None,
+                    locations: vec![location_status("1.0", missing_path.clone())],
+                },
+                ExtensionStatus {
+                    name: "pgmq".to_string(),
+                    description: None,
+                    locations: vec![location_status("1.0", missing_path)],
+                },
+            ]),
+            trunk_installs: Some(vec![trunk_install_status("pg_partman", "1.0")]),
+            ..CoreDBStatus::default()
+        });
+
+        // The versions with a missing update script are installed
+        let desired = desired_trunk_installs(&cdb);
+        assert_eq!(desired, vec![
+            TrunkInstall {
+                name: "pg_partman".to_string(),
+                version: Some("1.1".to_string()),
+            },
+            TrunkInstall {
+                name: "pgmq".to_string(),
+                version: Some("1.1".to_string()),
+            },
+        ]);
+        let to_install = find_trunk_installs_to_pod(
+            &cdb,
+            &desired,
+            "test-coredb-24631-1",
+            &trunk_install_retry(vec![]),
+            Utc::now(),
+        );
+        assert_eq!(to_install.len(), 2);
+        assert!(find_trunk_installs_to_remove_from_status(&cdb).is_empty());
+
+        // Once the extension is updated, the trunk install stays at its version
+        let status = cdb.status.as_mut().unwrap();
+        status.extensions = Some(vec![ExtensionStatus {
+            name: "pg_partman".to_string(),
+            description: None,
+            locations: vec![location_status("1.1", None)],
+        }]);
+        status.trunk_installs = Some(vec![
+            trunk_install_status("pg_partman", "1.1"),
+            trunk_install_status("pgmq", "1.1"),
+        ]);
+        let desired = desired_trunk_installs(&cdb);
+        assert_eq!(desired, vec![TrunkInstall {
+            name: "pg_partman".to_string(),
+            version: Some("1.1".to_string()),
+        }]);
+        let to_install = find_trunk_installs_to_pod(
+            &cdb,
+            &desired,
+            "test-coredb-24631-1",
+            &trunk_install_retry(vec![]),
+            Utc::now(),
+        );
+        assert!(to_install.is_empty());
     }
 
     #[test]
@@ -626,6 +1519,7 @@ mod tests {
             loading: false,
             error_message: None,
             installed_to_pods: Some(vec!["test-coredb-24631-1".to_string()]),
+            attempts: None,
         };
 
         let trunk_install_status2 = TrunkInstallStatus {
@@ -635,6 +1529,7 @@ mod tests {
             loading: false,
             error_message: None,
             installed_to_pods: Some(vec!["test-coredb-24631-1".to_string()]),
+            attempts: None,
         };
 
         let cdb_with_status = CoreDB {
@@ -13,6 +13,19 @@ pub async fn update_extension_location_in_status(
     ctx: Arc<Context>,
     extension_name: &str,
     new_location_status: &ExtensionInstallLocationStatus,
+) -> Result<Vec<ExtensionStatus>, Action> {
+    update_extension_locations_in_status(cdb, ctx, &[(
+        extension_name.to_owned(),
+        new_location_status.clone(),
+    )])
+    .await
+}
+
+/// Set several location statuses, by extension name, with one patch of the status
+pub async fn update_extension_locations_in_status(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    new_location_statuses: &[(String, ExtensionInstallLocationStatus)],
 ) -> Result<Vec<ExtensionStatus>, Action> {
     let cdb = get_current_coredb_resource(cdb, ctx.clone()).await?;
     let current_extensions_status = match &cdb.status {
@@ -28,10 +41,15 @@ pub async fn update_extension_location_in_status(
             Some(extensions) => extensions.clone(),
         },
     };
-    let new_extensions_status = merge_location_status_into_extension_status_list(
-        extension_name,
-        new_location_status,
+    let new_extensions_status = new_location_statuses.iter().fold(
         current_extensions_status,
+        |extensions_status, (extension_name, new_location_status)| {
+            merge_location_status_into_extension_status_list(
+                extension_name,
+                new_location_status,
+                extensions_status,
+            )
+        },
     );
     update_extensions_status(&cdb, new_extensions_status.clone(), &ctx).await?;
     Ok(new_extensions_status.clone())
@@ -280,7 +298,26 @@ fn update_trunk_installs(
                     installed_to_pods.dedup();
                 }
             }
+            // The latest attempt on a pod replaces the previous one, and the install is failed as
+            // long as the latest attempt on any pod failed
+            if let Some(new_attempts) = &new_trunk_install.attempts {
+                let attempts = update_status.attempts.get_or_insert_with(Vec::new);
+                for new_attempt in new_attempts {
+                    attempts.retain(|attempt| attempt.pod_name != new_attempt.pod_name);
+                    attempts.push(new_attempt.clone());
+                }
+                attempts.sort_by(|a, b| a.pod_name.cmp(&b.pod_name));
+                update_status.error = attempts.iter().any(|attempt| attempt.last_error.is_some());
+                update_status.error_message = if new_trunk_install.error {
+                    new_trunk_install.error_message.clone()
+                } else {
+                    attempts.iter().find_map(|attempt| attempt.last_error.clone())
+                };
+            }
             updated_trunk_installs.push(update_status);
+        } else if existing_status.name == new_trunk_install.name {
+            // A new version replaces the previous one, the pods it was installed to need the new one
+            updated_trunk_installs.push(new_trunk_install.clone());
         } else {
             updated_trunk_installs.push(existing_status.clone());
         }
@@ -302,6 +339,7 @@ fn update_trunk_installs(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::extensions::types::TrunkInstallPodAttempt;
 
     #[test]
     fn test_update_trunk_installs_from_no_pods() {
@@ -312,6 +350,7 @@ mod tests {
             error_message: None,
             loading: false,
             installed_to_pods: None,
+            attempts: None,
         }];
         let new_trunk_install = TrunkInstallStatus {
             name: "pg_stat_statements".to_string(),
@@ -320,6 +359,7 @@ mod tests {
             error_message: None,
             loading: false,
             installed_to_pods: Some(vec!["pod-1".to_string(), "pod-2".to_string()]),
+            attempts: None,
         };
 
         let updated_trunk_installs = update_trunk_installs(current_trunk_installs, &new_trunk_install);
@@ -331,6 +371,72 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_update_trunk_installs_new_version() {
+        let current_trunk_installs = vec![TrunkInstallStatus {
+            name: "pg_stat_statements".to_string(),
+            version: Some("1.0".to_string()),
+            error: false,
+            error_message: None,
+            loading: false,
+            installed_to_pods: Some(vec!["pod-1".to_string(), "pod-2".to_string()]),
+            attempts: None,
+        }];
+        let new_trunk_install = TrunkInstallStatus {
+            name: "pg_stat_statements".to_string(),
+            version: Some("1.1".to_string()),
+            error: false,
+            error_message: None,
+            loading: false,
+            installed_to_pods: Some(vec!["pod-1".to_string()]),
+            attempts: None,
+        };
+
+        let updated_trunk_installs = update_trunk_installs(current_trunk_installs, &new_trunk_install);
+
+        assert_eq!(updated_trunk_installs, vec![new_trunk_install]);
+    }
+
+    #[test]
+    fn test_update_trunk_installs_retry_attempt() {
+        let attempt = |pod_name: &str, attempts: i32, last_error: Option<&str>| TrunkInstallPodAttempt {
+            pod_name: pod_name.to_string(),
+            attempts,
+            last_attempt_at: None,
+            last_error: last_error.map(str::to_string),
+        };
+        let current_trunk_installs = vec![TrunkInstallStatus {
+            name: "pgml".to_string(),
+            version: Some("2.7.1".to_string()),
+            error: true,
+            error_message: Some("timeout".to_string()),
+            loading: false,
+            installed_to_pods: Some(vec!["pod-1".to_string(), "pod-2".to_string()]),
+            attempts: Some(vec![
+                attempt("pod-1", 1, None),
+                attempt("pod-2", 1, Some("timeout")),
+            ]),
+        }];
+        let retried = TrunkInstallStatus {
+            name: "pgml".to_string(),
+            version: Some("2.7.1".to_string()),
+            error: false,
+            error_message: None,
+            loading: false,
+            installed_to_pods: Some(vec!["pod-2".to_string()]),
+            attempts: Some(vec![attempt("pod-2", 2, None)]),
+        };
+
+        let updated_trunk_installs = update_trunk_installs(current_trunk_installs, &retried);
+
+        assert!(!updated_trunk_installs[0].error);
+        assert_eq!(updated_trunk_installs[0].error_message, None);
+        assert_eq!(
+            updated_trunk_installs[0].attempts,
+            Some(vec![attempt("pod-1", 1, None), attempt("pod-2", 2, None)])
+        );
+    }
+
     #[test]
     fn test_add_new_trunk_install_with_same_name_new_host() {
         let initial_trunk_installs = vec![TrunkInstallStatus {
@@ -340,6 +446,7 @@ mod tests {
             version: Some("1.0.0".to_string()),
             loading: false,
             error_message: None,
+            attempts: None,
         }];
 
         let new_trunk_install = TrunkInstallStatus {
@@ -349,6 +456,7 @@ mod tests {
             version: Some("1.0.0".to_string()),
             loading: false,
             error_message: None,
+            attempts: None,
         };
 
         let updated_trunk_installs =
@@ -376,6 +484,7 @@ mod tests {
                 version: Some("1.0.0".to_string()),
                 loading: false,
                 error_message: None,
+                attempts: None,
             },
             TrunkInstallStatus {
                 error: false,
@@ -384,6 +493,7 @@ mod tests {
                 version: Some("1.0.0".to_string()),
                 loading: false,
                 error_message: None,
+                attempts: None,
             },
         ];
 
@@ -394,6 +504,7 @@ mod tests {
             version: Some("1.0.0".to_string()),
             loading: false,
             error_message: None,
+            attempts: None,
         };
 
         let updated_trunk_installs =
@@ -425,6 +536,7 @@ mod tests {
                 version: Some("4.7.3".to_string()),
                 error_message: None,
                 loading: false,
+                attempts: None,
             },
             TrunkInstallStatus {
                 error: false,
@@ -433,6 +545,7 @@ mod tests {
                 version: Some("1.10.0".to_string()),
                 error_message: None,
                 loading: false,
+                attempts: None,
             },
             TrunkInstallStatus {
                 error: false,
@@ -441,6 +554,7 @@ mod tests {
                 version: Some("0.10.0".to_string()),
                 error_message: None,
                 loading: false,
+                attempts: None,
             },
         ];
 
@@ -451,6 +565,7 @@ mod tests {
             version: Some("4.7.3".to_string()),
             error_message: None,
             loading: false,
+            attempts: None,
         };
 
         let updated_trunk_installs =
@@ -473,6 +588,7 @@ mod tests {
             version: Some("1.10.0".to_string()),
             error_message: None,
             loading: false,
+            attempts: None,
         };
 
         let updated_trunk_installs =
@@ -495,6 +611,7 @@ mod tests {
             version: Some("0.10.0".to_string()),
             error_message: None,
             loading: false,
+            attempts: None,
         };
 
         let updated_trunk_installs =
@@ -1,18 +1,20 @@
 pub mod database_queries;
+pub mod dependencies;
 pub mod install;
 pub mod kubernetes_queries;
 pub mod toggle;
 pub mod types;
+pub mod uninstall;
 
 use crate::{
     apis::coredb_types::CoreDB,
+    conditions::{requeue_with_condition, set_condition, EXTENSIONS_READY, TRUNK_INSTALLS_READY},
+    config::Config,
     extensions::types::{ExtensionStatus, TrunkInstallStatus},
     is_postgres_ready, Context,
 };
-use kube::{
-    runtime::{controller::Action, wait::Condition},
-    Api,
-};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Condition as StatusCondition;
+use kube::runtime::{controller::Action, wait::Condition};
 use std::{sync::Arc, time::Duration};
 use tracing::debug;
 
@@ -20,23 +22,135 @@ use tracing::debug;
 pub async fn reconcile_extensions(
     coredb: &CoreDB,
     ctx: Arc<Context>,
-    _cdb_api: &Api<CoreDB>,
-    _name: &str,
+    cfg: &Config,
+    conditions: &mut Vec<StatusCondition>,
 ) -> Result<(Vec<TrunkInstallStatus>, Vec<ExtensionStatus>), Action> {
+    let generation = coredb.metadata.generation;
     // Trunk installs do not require postgres is ready
     let coredb_name = coredb.metadata.name.clone().expect("CoreDB should have a name");
     debug!("Reconciling trunk installs: {}", coredb_name);
-    let trunk_installs = install::reconcile_trunk_installs(coredb, ctx.clone()).await?;
+    let (trunk_installs, uninstalls_blocked) =
+        match install::reconcile_trunk_installs(coredb, ctx.clone(), cfg).await {
+            Ok(result) => result,
+            Err(action) => {
+                return Err(requeue_with_condition(
+                    coredb,
+                    ctx.clone(),
+                    conditions,
+                    TRUNK_INSTALLS_READY,
+                    "TrunkInstallsPending",
+                    "Waiting for trunk installs to complete",
+                    action,
+                )
+                .await);
+            }
+        };
+    match trunk_installs.iter().find(|t| t.error) {
+        // Objects depend on an extension of a removed trunk install
+        _ if !uninstalls_blocked.is_empty() => set_condition(
+            conditions,
+            TRUNK_INSTALLS_READY,
+            false,
+            "TrunkUninstallBlocked",
+            &uninstalls_blocked.join("; "),
+            generation,
+        ),
+        Some(failed) => set_condition(
+            conditions,
+            TRUNK_INSTALLS_READY,
+            false,
+            "TrunkInstallFailed",
+            &format!(
+                "Failed to install {}: {}",
+                failed.name,
+                failed.error_message.clone().unwrap_or_default()
+            ),
+            generation,
+        ),
+        None => set_condition(
+            conditions,
+            TRUNK_INSTALLS_READY,
+            true,
+            "TrunkInstallsReady",
+            "",
+            generation,
+        ),
+    }
 
-    let primary_pod_cnpg = coredb.primary_pod_cnpg(ctx.client.clone()).await?;
+    let primary_pod_cnpg = match coredb.primary_pod_cnpg(ctx.client.clone()).await {
+        Ok(primary_pod_cnpg) => primary_pod_cnpg,
+        Err(action) => {
+            return Err(requeue_with_condition(
+                coredb,
+                ctx.clone(),
+                conditions,
+                EXTENSIONS_READY,
+                "PostgresNotReady",
+                "Waiting for the primary pod before toggling extensions",
+                action,
+            )
+            .await);
+        }
+    };
 
     if !is_postgres_ready().matches_object(Some(&primary_pod_cnpg)) {
         debug!("Did not find postgres ready, waiting a short period");
-        return Err(Action::requeue(Duration::from_secs(5)));
+        return Err(requeue_with_condition(
+            coredb,
+            ctx.clone(),
+            conditions,
+            EXTENSIONS_READY,
+            "PostgresNotReady",
+            "Waiting for postgres to be ready before toggling extensions",
+            Action::requeue(Duration::from_secs(5)),
+        )
+        .await);
     }
 
     // Toggles require postgres is ready
     debug!("Reconciling extension statuses: {}", coredb_name);
-    let extension_statuses = toggle::reconcile_extension_toggle_state(coredb, ctx.clone()).await?;
+    let extension_statuses = match toggle::reconcile_extension_toggle_state(coredb, ctx.clone(), cfg).await {
+        Ok(extension_statuses) => extension_statuses,
+        Err(action) => {
+            return Err(requeue_with_condition(
+                coredb,
+                ctx.clone(),
+                conditions,
+                EXTENSIONS_READY,
+                "ExtensionsPending",
+                "Waiting for extensions to be toggled",
+                action,
+            )
+            .await);
+        }
+    };
+    let failed_locations: Vec<String> = extension_statuses
+        .iter()
+        .flat_map(|ext| {
+            ext.locations
+                .iter()
+                .filter(|loc| loc.error.unwrap_or(false))
+                .map(move |loc| format!("{} ({})", ext.name, loc.database))
+        })
+        .collect();
+    if failed_locations.is_empty() {
+        set_condition(
+            conditions,
+            EXTENSIONS_READY,
+            true,
+            "ExtensionsReady",
+            "",
+            generation,
+        );
+    } else {
+        set_condition(
+            conditions,
+            EXTENSIONS_READY,
+            false,
+            "ExtensionError",
+            &format!("Extensions in error state: {}", failed_locations.join(", ")),
+            generation,
+        );
+    }
     Ok((trunk_installs, extension_statuses))
 }
@@ -1,20 +1,23 @@
 use crate::{
     apis::coredb_types::CoreDB,
+    config::Config,
     extensions::{
         database_queries, kubernetes_queries, types,
-        types::{Extension, ExtensionInstallLocationStatus, ExtensionStatus},
+        types::{Extension, ExtensionInstallLocation, ExtensionInstallLocationStatus, ExtensionStatus},
     },
-    get_current_coredb_resource, Context,
+    get_current_coredb_resource, publish_event, Context,
 };
-use kube::runtime::controller::Action;
+use kube::runtime::{controller::Action, events::EventType};
 
 use crate::{
     apis::coredb_types::CoreDBStatus,
     extensions::{
         database_queries::list_shared_preload_libraries,
-        kubernetes_queries::merge_location_status_into_extension_status_list, types::get_location_status,
+        dependencies::{order_extensions, report_unresolved_dependencies},
+        kubernetes_queries::merge_location_status_into_extension_status_list,
+        types::get_location_status,
     },
-    trunk::extensions_that_require_load,
+    trunk::{extension_dependencies, extensions_that_require_load},
 };
 use std::{collections::BTreeMap, sync::Arc, time::Duration};
 use tracing::{error, warn};
@@ -22,27 +25,224 @@ use tracing::{error, warn};
 pub async fn reconcile_extension_toggle_state(
     cdb: &CoreDB,
     ctx: Arc<Context>,
+    cfg: &Config,
 ) -> Result<Vec<ExtensionStatus>, Action> {
-    let all_actually_installed_extensions = database_queries::get_all_extensions(cdb, ctx.clone()).await?;
+    let all_actually_installed_extensions =
+        database_queries::get_all_extensions(cdb, ctx.clone(), cfg).await?;
+    let dependencies =
+        extension_dependencies(ctx.client.clone(), &cdb.metadata.namespace.clone().unwrap()).await?;
     let ext_status_updates = determine_updated_extensions_status(cdb, all_actually_installed_extensions);
+    // Extensions with missing dependencies are reported in the status and not toggled
+    let ext_status_updates =
+        report_unresolved_dependencies(&cdb.spec.extensions, ext_status_updates, &dependencies);
+    // Extensions with a version change need a path from the installed version to the target version
+    let ext_status_updates =
+        database_queries::determine_update_paths(cdb, ctx.clone(), cfg, ext_status_updates).await?;
+    // Extensions with a schema change need to be relocatable
+    let ext_status_updates =
+        database_queries::determine_relocations(cdb, ctx.clone(), cfg, ext_status_updates).await?;
     kubernetes_queries::update_extensions_status(cdb, ext_status_updates.clone(), &ctx).await?;
     let cdb = get_current_coredb_resource(cdb, ctx.clone()).await?;
-    let toggle_these_extensions = determine_extension_locations_to_toggle(&cdb);
+    let toggle_these_extensions =
+        order_extensions(determine_extension_locations_to_toggle(&cdb), &dependencies);
+    let ext_status_updates = toggle_extensions(
+        ctx.clone(),
+        cfg,
+        ext_status_updates,
+        &cdb,
+        toggle_these_extensions,
+    )
+    .await?;
+    let update_these_extensions =
+        order_extensions(determine_extension_locations_to_update(&cdb), &dependencies);
+    let ext_status_updates = update_extensions(
+        ctx.clone(),
+        cfg,
+        ext_status_updates,
+        &cdb,
+        update_these_extensions,
+    )
+    .await?;
+    let relocate_these_extensions =
+        order_extensions(determine_extension_locations_to_relocate(&cdb), &dependencies);
     let ext_status_updates =
-        toggle_extensions(ctx, ext_status_updates, &cdb, toggle_these_extensions).await?;
+        relocate_extensions(ctx, cfg, ext_status_updates, &cdb, relocate_these_extensions).await?;
+    Ok(ext_status_updates)
+}
+
+async fn relocate_extensions(
+    ctx: Arc<Context>,
+    cfg: &Config,
+    ext_status_updates: Vec<ExtensionStatus>,
+    cdb: &CoreDB,
+    relocate_these_extensions: Vec<Extension>,
+) -> Result<Vec<ExtensionStatus>, Action> {
+    let mut ext_status_updates = ext_status_updates;
+    for extension_to_relocate in relocate_these_extensions {
+        for location_to_relocate in extension_to_relocate.locations {
+            let schema = location_to_relocate.schema.clone().unwrap_or_default();
+            match database_queries::relocate_extension(
+                cdb,
+                &extension_to_relocate.name,
+                location_to_relocate.clone(),
+                ctx.clone(),
+                cfg,
+            )
+            .await
+            {
+                Ok(_) => {
+                    publish_event(
+                        cdb,
+                        ctx.clone(),
+                        EventType::Normal,
+                        "ExtensionRelocated",
+                        format!(
+                            "Moved extension {} to schema {} in database {}",
+                            extension_to_relocate.name, schema, location_to_relocate.database
+                        ),
+                    )
+                    .await;
+                }
+                Err(error_message) => {
+                    publish_event(
+                        cdb,
+                        ctx.clone(),
+                        EventType::Warning,
+                        "ExtensionRelocateFailed",
+                        format!(
+                            "Failed to move extension {} to schema {} in database {}: {}",
+                            extension_to_relocate.name, schema, location_to_relocate.database, error_message
+                        ),
+                    )
+                    .await;
+                    ext_status_updates = set_location_error(
+                        cdb,
+                        ctx.clone(),
+                        &extension_to_relocate.name,
+                        &location_to_relocate.database,
+                        error_message,
+                    )
+                    .await?;
+                }
+            }
+        }
+    }
     Ok(ext_status_updates)
 }
 
+async fn update_extensions(
+    ctx: Arc<Context>,
+    cfg: &Config,
+    ext_status_updates: Vec<ExtensionStatus>,
+    cdb: &CoreDB,
+    update_these_extensions: Vec<Extension>,
+) -> Result<Vec<ExtensionStatus>, Action> {
+    let mut ext_status_updates = ext_status_updates;
+    for extension_to_update in update_these_extensions {
+        for location_to_update in extension_to_update.locations {
+            let version = location_to_update.version.clone().unwrap_or_default();
+            match database_queries::update_extension(
+                cdb,
+                &extension_to_update.name,
+                location_to_update.clone(),
+                ctx.clone(),
+                cfg,
+            )
+            .await
+            {
+                Ok(_) => {
+                    publish_event(
+                        cdb,
+                        ctx.clone(),
+                        EventType::Normal,
+                        "ExtensionUpdated",
+                        format!(
+                            "Updated extension {} to version {} in database {}",
+                            extension_to_update.name, version, location_to_update.database
+                        ),
+                    )
+                    .await;
+                }
+                Err(error_message) => {
+                    publish_event(
+                        cdb,
+                        ctx.clone(),
+                        EventType::Warning,
+                        "ExtensionUpdateFailed",
+                        format!(
+                            "Failed to update extension {} to version {} in database {}: {}",
+                            extension_to_update.name, version, location_to_update.database, error_message
+                        ),
+                    )
+                    .await;
+                    ext_status_updates = set_location_error(
+                        cdb,
+                        ctx.clone(),
+                        &extension_to_update.name,
+                        &location_to_update.database,
+                        error_message,
+                    )
+                    .await?;
+                }
+            }
+        }
+    }
+    Ok(ext_status_updates)
+}
+
+// Record the error of a failed operation on an extension location in the status
+async fn set_location_error(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    extension_name: &str,
+    database: &str,
+    error_message: String,
+) -> Result<Vec<ExtensionStatus>, Action> {
+    let location_status = location_error_status(cdb, extension_name, database, error_message);
+    kubernetes_queries::update_extension_location_in_status(cdb, ctx, extension_name, &location_status).await
+}
+
+// The status of an extension location with the error of a failed operation
+fn location_error_status(
+    cdb: &CoreDB,
+    extension_name: &str,
+    database: &str,
+    error_message: String,
+) -> ExtensionInstallLocationStatus {
+    let mut location_status = match types::get_location_status(cdb, extension_name, database) {
+        None => {
+            error!("There should always be an extension status for a location before attempting to change an extension for that location");
+            ExtensionInstallLocationStatus {
+                database: database.to_owned(),
+                schema: None,
+                version: None,
+                enabled: None,
+                error: Some(true),
+                error_message: None,
+                target_version: None,
+                update_path: None,
+                target_schema: None,
+            }
+        }
+        Some(location_status) => location_status,
+    };
+    location_status.error = Some(true);
+    location_status.error_message = Some(error_message);
+    location_status
+}
+
 async fn toggle_extensions(
     ctx: Arc<Context>,
+    cfg: &Config,
     ext_status_updates: Vec<ExtensionStatus>,
     cdb: &CoreDB,
     toggle_these_extensions: Vec<Extension>,
 ) -> Result<Vec<ExtensionStatus>, Action> {
-    let current_shared_preload_libraries = list_shared_preload_libraries(cdb, ctx.clone()).await?;
+    let current_shared_preload_libraries = list_shared_preload_libraries(cdb, ctx.clone(), cfg).await?;
     let requires_load =
         extensions_that_require_load(ctx.client.clone(), &cdb.metadata.namespace.clone().unwrap()).await?;
-    let mut ext_status_updates = ext_status_updates.clone();
+    // Toggles are applied in one transaction per database, keeping the dependency order
+    let mut batches: BTreeMap<String, Vec<(String, ExtensionInstallLocation)>> = BTreeMap::new();
     for extension_to_toggle in toggle_these_extensions {
         for location_to_toggle in extension_to_toggle.locations {
             let expected_library_name = match requires_load.get(&extension_to_toggle.name) {
@@ -67,48 +267,83 @@ async fn toggle_extensions(
                     requires_load.clone(),
                 )?;
             }
-            match database_queries::toggle_extension(
-                cdb,
-                &extension_to_toggle.name,
-                location_to_toggle.clone(),
-                ctx.clone(),
-            )
+            batches
+                .entry(location_to_toggle.database.clone())
+                .or_default()
+                .push((extension_to_toggle.name.clone(), location_to_toggle));
+        }
+    }
+    let mut failed_locations: Vec<(String, ExtensionInstallLocationStatus)> = vec![];
+    for (database, toggles) in batches {
+        match database_queries::toggle_extensions_in_database(cdb, &database, &toggles, ctx.clone(), cfg)
             .await
-            {
-                Ok(_) => {}
-                Err(error_message) => {
-                    let mut location_status = match types::get_location_status(
+        {
+            Ok(_) => {
+                for (extension_name, location_to_toggle) in &toggles {
+                    publish_event(
                         cdb,
-                        &extension_to_toggle.name,
-                        &location_to_toggle.database,
-                    ) {
-                        None => {
-                            error!("There should always be an extension status for a location before attempting to toggle an extension for that location");
-                            ExtensionInstallLocationStatus {
-                                database: location_to_toggle.database.clone(),
-                                schema: None,
-                                version: None,
-                                enabled: None,
-                                error: Some(true),
-                                error_message: None,
-                            }
-                        }
-                        Some(location_status) => location_status,
-                    };
-                    location_status.error = Some(true);
-                    location_status.error_message = Some(error_message);
-                    ext_status_updates = kubernetes_queries::update_extension_location_in_status(
+                        ctx.clone(),
+                        EventType::Normal,
+                        "ExtensionToggled",
+                        format!(
+                            "{} extension {} in database {}",
+                            if location_to_toggle.enabled {
+                                "Enabled"
+                            } else {
+                                "Disabled"
+                            },
+                            extension_name,
+                            database
+                        ),
+                    )
+                    .await;
+                }
+            }
+            Err(batch_error) => {
+                // The batch was rolled back. Only the extension that failed is marked, the others
+                // are toggled again on the next reconcile.
+                for extension_name in failed_toggles(&toggles, &batch_error) {
+                    publish_event(
                         cdb,
                         ctx.clone(),
-                        &extension_to_toggle.name,
-                        &location_status,
+                        EventType::Warning,
+                        "ExtensionToggleFailed",
+                        format!(
+                            "Failed to toggle extension {} in database {}: {}",
+                            extension_name, database, batch_error.error_message
+                        ),
                     )
-                    .await?;
+                    .await;
+                    failed_locations.push((
+                        extension_name.clone(),
+                        location_error_status(
+                            cdb,
+                            &extension_name,
+                            &database,
+                            batch_error.error_message.clone(),
+                        ),
+                    ));
                 }
             }
         }
     }
-    Ok(ext_status_updates)
+    if failed_locations.is_empty() {
+        return Ok(ext_status_updates);
+    }
+    kubernetes_queries::update_extension_locations_in_status(cdb, ctx, &failed_locations).await
+}
+
+// The extensions of a batch that are marked as failed, which is every extension of the batch when
+// the statement that failed is not known
+fn failed_toggles(
+    toggles: &[(String, ExtensionInstallLocation)],
+    batch_error: &database_queries::ToggleBatchError,
+) -> Vec<String> {
+    let all: Vec<String> = toggles.iter().map(|(name, _)| name.clone()).collect();
+    match &batch_error.extension_name {
+        Some(failed) if all.contains(failed) => vec![failed.clone()],
+        _ => all,
+    }
 }
 
 // In this function, we check if we are awaiting restart on shared_preload_libraries
@@ -121,10 +356,10 @@ fn requeue_if_expecting_shared_preload_library(
         None => extension_to_toggle,
         Some(expected_library_name) => expected_library_name,
     };
-    // Get config by name
+    // Get config by name, extension configs never include shared_preload_libraries
     match cdb
         .spec
-        .get_pg_config_by_name("shared_preload_libraries", requires_load.clone())
+        .get_pg_config_by_name("shared_preload_libraries", requires_load.clone(), BTreeMap::new())
     {
         // If there is not an error
         Ok(shared_preload_libraries_config_value) => match shared_preload_libraries_config_value {
@@ -180,29 +415,49 @@ pub fn determine_updated_extensions_status(
                 version: actual_location.version.clone(),
                 error: Some(false),
                 error_message: None,
+                target_version: None,
+                update_path: None,
+                target_schema: None,
             };
             // If there is a current status, retain the error and error message if the schema has not changed
-            match types::get_location_status(
+            let current_status = types::get_location_status(
                 cdb,
                 &actual_extension.name.clone(),
                 &actual_location.database.clone(),
-            ) {
-                None => {}
-                Some(current_status) => {
-                    if current_status.schema == actual_location.schema {
-                        location_status.error = current_status.error;
-                        location_status.error_message = current_status.error_message;
-                    }
+            );
+            if let Some(current_status) = &current_status {
+                if current_status.schema == actual_location.schema {
+                    location_status.error = current_status.error;
+                    location_status.error_message = current_status.error_message.clone();
                 }
             }
             // If the desired state matches the actual state, unset the error and error message
             match types::get_location_spec(cdb, &actual_extension.name, &actual_location.database) {
                 None => {}
                 Some(desired_location) => {
-                    if actual_location.enabled == Some(desired_location.enabled) {
+                    let target_version = determine_target_version(&actual_location, &desired_location);
+                    let target_schema = determine_target_schema(&actual_location, &desired_location);
+                    // An error about the same target version or schema is retained, so a failed update
+                    // or relocation is not retried until the spec changes
+                    let same_target = current_status.is_some_and(|status| {
+                        (target_version.is_some() && status.target_version == target_version)
+                            || (target_schema.is_some() && status.target_schema == target_schema)
+                    });
+                    if actual_location.enabled == Some(desired_location.enabled) && !same_target {
                         location_status.error = Some(false);
                         location_status.error_message = None;
                     }
+                    if let (Some(version), Some(target)) = (&actual_location.version, &target_version) {
+                        if types::is_downgrade(version, target) {
+                            location_status.error = Some(true);
+                            location_status.error_message = Some(format!(
+                                "Downgrading extension {} from version {} to {} is not supported",
+                                actual_extension.name, version, target
+                            ));
+                        }
+                    }
+                    location_status.target_version = target_version;
+                    location_status.target_schema = target_schema;
                 }
             }
             extension_status.locations.push(location_status);
@@ -244,6 +499,9 @@ pub fn determine_updated_extensions_status(
                     version: desired_location.version.clone(),
                     error: Some(true),
                     error_message: Some("Extension is not installed".to_string()),
+                    target_version: None,
+                    update_path: None,
+                    target_schema: None,
                 };
                 ext_status_updates = merge_location_status_into_extension_status_list(
                     &desired_extension.name.clone(),
@@ -259,6 +517,91 @@ pub fn determine_updated_extensions_status(
     ext_status_updates
 }
 
+// The version an enabled extension should be updated to, if the spec requests a different version
+// than the one that is installed
+fn determine_target_version(
+    actual_location: &ExtensionInstallLocationStatus,
+    desired_location: &ExtensionInstallLocation,
+) -> Option<String> {
+    if actual_location.enabled != Some(true) || !desired_location.enabled {
+        return None;
+    }
+    match &desired_location.version {
+        Some(version) if actual_location.version.as_ref() != Some(version) => Some(version.clone()),
+        _ => None,
+    }
+}
+
+// The schema an enabled extension should be moved to, if the spec requests a different schema
+// than the one it is in
+fn determine_target_schema(
+    actual_location: &ExtensionInstallLocationStatus,
+    desired_location: &ExtensionInstallLocation,
+) -> Option<String> {
+    if actual_location.enabled != Some(true) || !desired_location.enabled {
+        return None;
+    }
+    match &desired_location.schema {
+        Some(schema) if actual_location.schema.as_ref() != Some(schema) => Some(schema.clone()),
+        _ => None,
+    }
+}
+
+pub fn determine_extension_locations_to_relocate(cdb: &CoreDB) -> Vec<Extension> {
+    let mut extensions_to_relocate: Vec<Extension> = vec![];
+    for desired_extension in &cdb.spec.extensions {
+        let mut extension_to_relocate = desired_extension.clone();
+        extension_to_relocate.locations = vec![];
+        for desired_location in &desired_extension.locations {
+            let Some(actual_status) =
+                types::get_location_status(cdb, &desired_extension.name, &desired_location.database)
+            else {
+                continue;
+            };
+            // Only relocate extensions that are enabled and without errors
+            if actual_status.error == Some(false)
+                && actual_status.enabled == Some(true)
+                && actual_status.target_schema.is_some()
+                && actual_status.target_schema == desired_location.schema
+            {
+                extension_to_relocate.locations.push(desired_location.clone());
+            }
+        }
+        if !extension_to_relocate.locations.is_empty() {
+            extensions_to_relocate.push(extension_to_relocate);
+        }
+    }
+    extensions_to_relocate
+}
+
+pub fn determine_extension_locations_to_update(cdb: &CoreDB) -> Vec<Extension> {
+    let mut extensions_to_update: Vec<Extension> = vec![];
+    for desired_extension in &cdb.spec.extensions {
+        let mut extension_to_update = desired_extension.clone();
+        extension_to_update.locations = vec![];
+        for desired_location in &desired_extension.locations {
+            let Some(actual_status) =
+                types::get_location_status(cdb, &desired_extension.name, &desired_location.database)
+            else {
+                continue;
+            };
+            // Only update extensions that are enabled, without errors, and have a path to the target version
+            if actual_status.error == Some(false)
+                && actual_status.enabled == Some(true)
+                && actual_status.update_path.is_some()
+                && actual_status.target_version.is_some()
+                && actual_status.target_version == desired_location.version
+            {
+                extension_to_update.locations.push(desired_location.clone());
+            }
+        }
+        if !extension_to_update.locations.is_empty() {
+            extensions_to_update.push(extension_to_update);
+        }
+    }
+    extensions_to_update
+}
+
 pub fn determine_extension_locations_to_toggle(cdb: &CoreDB) -> Vec<Extension> {
     let mut extensions_to_toggle: Vec<Extension> = vec![];
     for desired_extension in &cdb.spec.extensions {
@@ -313,4 +656,183 @@ mod tests {
         let result = determine_updated_extensions_status(&cdb, all_actually_installed_extensions);
         assert_eq!(result, vec![]);
     }
+
+    #[test]
+    fn test_determine_updated_extensions_status_version_change() {
+        let desired = |name: &str, version: &str| Extension {
+            name: name.to_string(),
+            description: None,
+            locations: vec![ExtensionInstallLocation {
+                enabled: true,
+                database: "postgres".to_string(),
+                schema: None,
+                version: Some(version.to_string()),
+            }],
+        };
+        let actual = |name: &str, version: &str| ExtensionStatus {
+            name: name.to_string(),
+            description: None,
+            locations: vec![ExtensionInstallLocationStatus {
+                database: "postgres".to_string(),
+                schema: Some("public".to_string()),
+                version: Some(version.to_string()),
+                enabled: Some(true),
+                error: None,
+                error_message: None,
+                target_version: None,
+                update_path: None,
+                target_schema: None,
+            }],
+        };
+        let mut cdb = CoreDB {
+            metadata: Default::default(),
+            spec: CoreDBSpec {
+                extensions: vec![
+                    desired("pg_partman", "4.7.3"),
+                    desired("pgmq", "0.10.0"),
+                    desired("vector", "0.5.0"),
+                ],
+                ..Default::default()
+            },
+            status: None,
+        };
+        let result = determine_updated_extensions_status(&cdb, vec![
+            actual("pg_partman", "4.7.3"),
+            actual("pgmq", "0.14.2"),
+            actual("vector", "0.4.4"),
+        ]);
+
+        // Same version
+        assert_eq!(result[0].locations[0].target_version, None);
+        // Downgrade
+        assert_eq!(result[1].locations[0].target_version, Some("0.10.0".to_string()));
+        assert_eq!(result[1].locations[0].error, Some(true));
+        // Upgrade
+        assert_eq!(result[2].locations[0].target_version, Some("0.5.0".to_string()));
+        assert_eq!(result[2].locations[0].error, Some(false));
+
+        // Only the upgrade with an update path is applied
+        let mut status = result;
+        status[2].locations[0].update_path = Some("0.4.4--0.5.0".to_string());
+        cdb.status = Some(CoreDBStatus {
+            extensions: Some(status),
+            ..CoreDBStatus::default()
+        });
+        let to_update = determine_extension_locations_to_update(&cdb);
+        assert_eq!(to_update.len(), 1);
+        assert_eq!(to_update[0].name, "vector");
+    }
+
+    #[test]
+    fn test_failed_toggles() {
+        let toggle = |name: &str| {
+            (name.to_string(), ExtensionInstallLocation {
+                enabled: true,
+                database: "postgres".to_string(),
+                schema: None,
+                version: None,
+            })
+        };
+        let toggles = vec![toggle("pgmq"), toggle("pg_cron"), toggle("vectorize")];
+        let batch_error = |extension_name: Option<&str>| database_queries::ToggleBatchError {
+            extension_name: extension_name.map(str::to_string),
+            error_message: "ERROR: failed".to_string(),
+        };
+
+        assert_eq!(failed_toggles(&toggles, &batch_error(Some("pg_cron"))), vec![
+            "pg_cron"
+        ]);
+        // Without the statement that failed, the whole batch is marked
+        assert_eq!(failed_toggles(&toggles, &batch_error(None)), vec![
+            "pgmq",
+            "pg_cron",
+            "vectorize"
+        ]);
+        assert_eq!(failed_toggles(&toggles, &batch_error(Some("hstore"))).len(), 3);
+    }
+
+    #[test]
+    fn test_determine_updated_extensions_status_schema_change() {
+        let desired = |name: &str, schema: Option<&str>| Extension {
+            name: name.to_string(),
+            description: None,
+            locations: vec![ExtensionInstallLocation {
+                enabled: true,
+                database: "postgres".to_string(),
+                schema: schema.map(str::to_string),
+                version: None,
+            }],
+        };
+        let actual = |name: &str, enabled: bool| ExtensionStatus {
+            name: name.to_string(),
+            description: None,
+            locations: vec![ExtensionInstallLocationStatus {
+                database: "postgres".to_string(),
+                schema: enabled.then(|| "public".to_string()),
+                version: Some("1.0.0".to_string()),
+                enabled: Some(enabled),
+                error: None,
+                error_message: None,
+                target_version: None,
+                update_path: None,
+                target_schema: None,
+            }],
+        };
+        let mut cdb = CoreDB {
+            metadata: Default::default(),
+            spec: CoreDBSpec {
+                extensions: vec![
+                    desired("citext", Some("extensions")),
+                    desired("hstore", Some("public")),
+                    desired("ltree", Some("extensions")),
+                    desired("pgcrypto", None),
+                ],
+                ..Default::default()
+            },
+            status: None,
+        };
+        let result = determine_updated_extensions_status(&cdb, vec![
+            actual("citext", true),
+            actual("hstore", true),
+            actual("ltree", false),
+            actual("pgcrypto", true),
+        ]);
+
+        // Schema change
+        assert_eq!(
+            result[0].locations[0].target_schema,
+            Some("extensions".to_string())
+        );
+        // Same schema
+        assert_eq!(result[1].locations[0].target_schema, None);
+        // Not enabled yet, the schema is set when it is created
+        assert_eq!(result[2].locations[0].target_schema, None);
+        // No schema in the spec
+        assert_eq!(result[3].locations[0].target_schema, None);
+
+        cdb.status = Some(CoreDBStatus {
+            extensions: Some(result.clone()),
+            ..CoreDBStatus::default()
+        });
+        let to_relocate = determine_extension_locations_to_relocate(&cdb);
+        assert_eq!(to_relocate.len(), 1);
+        assert_eq!(to_relocate[0].name, "citext");
+
+        // A failed relocation is not retried until the schema in the spec changes
+        let mut status = result;
+        status[0].locations[0].error = Some(true);
+        status[0].locations[0].error_message = Some("Not relocatable".to_string());
+        cdb.status = Some(CoreDBStatus {
+            extensions: Some(status),
+            ..CoreDBStatus::default()
+        });
+        let result = determine_updated_extensions_status(&cdb, vec![actual("citext", true)]);
+        assert_eq!(result[0].locations[0].error, Some(true));
+        assert!(determine_extension_locations_to_relocate(&cdb).is_empty());
+
+        cdb.spec.extensions[0] = desired("citext", Some("public"));
+        let result = determine_updated_extensions_status(&cdb, vec![actual("citext", true)]);
+        assert_eq!(result[0].locations[0].error, Some(false));
+        assert_eq!(result[0].locations[0].target_schema, None);
+    }
 }
@@ -1,6 +1,9 @@
 use crate::{apis::coredb_types::CoreDB, defaults, extensions::database_queries::check_input};
+use chrono::{DateTime, Utc};
 use lazy_static::lazy_static;
+use regex::Regex;
 use schemars::JsonSchema;
+use semver::Version;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use tracing::warn;
@@ -15,6 +18,10 @@ lazy_static! {
         );
         m
     };
+    static ref VALID_VERSION: Regex = Regex::new(r"^[0-9a-zA-Z][0-9a-zA-Z._+-]*$").unwrap();
+    // The error of a statement in a batch of toggles, see generate_extension_toggle_batch_cmd
+    static ref TOGGLE_BATCH_ERROR: Regex =
+        Regex::new(r"Failed to (?:enable|disable) extension (\S+) \(statement \d+ of \d+\)").unwrap();
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, Hash, JsonSchema, Serialize, PartialEq, ToSchema)]
@@ -41,6 +48,18 @@ pub struct TrunkInstallStatus {
     pub loading: bool,
     pub error_message: Option<String>,
     pub installed_to_pods: Option<Vec<String>>,
+    #[serde(default)]
+    pub attempts: Option<Vec<TrunkInstallPodAttempt>>,
+}
+
+/// Install attempts of a trunk install on one pod, failed installs are retried with backoff
+#[derive(Clone, Debug, Deserialize, Eq, Hash, JsonSchema, Serialize, PartialEq, ToSchema)]
+pub struct TrunkInstallPodAttempt {
+    pub pod_name: String,
+    pub attempts: i32,
+    #[schema(value_type = Option<String>)]
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, Hash, JsonSchema, Serialize, PartialEq, ToSchema)]
@@ -147,6 +166,96 @@ pub fn generate_extension_enable_cmd(
     Ok(command)
 }
 
+/// generates one transaction that toggles several extensions in the same database. Each command
+/// reports the extension and the statement that failed, and a failure rolls back the whole batch.
+pub fn generate_extension_toggle_batch_cmd(
+    toggles: &[(String, ExtensionInstallLocation)],
+) -> Result<String, (String, String)> {
+    let mut command = "BEGIN;".to_string();
+    for (index, (ext_name, ext_loc)) in toggles.iter().enumerate() {
+        let statement =
+            generate_extension_enable_cmd(ext_name, ext_loc).map_err(|e| (ext_name.clone(), e))?;
+        command.push_str(&format!(
+            " DO $toggle$ BEGIN {} EXCEPTION WHEN OTHERS THEN RAISE EXCEPTION 'Failed to {} extension {} (statement {} of {}): %', SQLERRM; END $toggle$;",
+            statement,
+            if ext_loc.enabled { "enable" } else { "disable" },
+            ext_name,
+            index + 1,
+            toggles.len()
+        ));
+    }
+    command.push_str(" COMMIT;");
+    Ok(command)
+}
+
+/// The extension whose statement failed in a batch of toggles, from the error of the batch
+pub fn parse_toggle_batch_error(error_message: &str) -> Option<String> {
+    TOGGLE_BATCH_ERROR
+        .captures(error_message)
+        .map(|captures| captures[1].to_string())
+}
+
+pub fn check_version(version: &str) -> bool {
+    VALID_VERSION.is_match(version)
+}
+
+/// generates the ALTER EXTENSION command to update an extension to a given version
+pub fn generate_extension_update_cmd(ext_name: &str, version: &str) -> Result<String, String> {
+    if !check_version(version) {
+        warn!(
+            "Extension.Database.Version is not formatted properly. Skipping operation. {}",
+            version
+        );
+        return Err("Version is not formatted properly".to_string());
+    }
+    Ok(format!(
+        "ALTER EXTENSION \"{}\" UPDATE TO '{}';",
+        ext_name, version
+    ))
+}
+
+/// generates the commands to move an extension to another schema, creating the schema if needed.
+/// They run in a transaction, so the schema is not left behind if the extension can not be moved.
+pub fn generate_extension_relocate_cmd(ext_name: &str, schema: &str) -> Result<String, String> {
+    if !check_input(schema) {
+        warn!(
+            "Extension.Database.Schema is not formatted properly. Skipping operation. {}",
+            schema
+        );
+        return Err("Schema name is not formatted properly".to_string());
+    }
+    Ok(format!(
+        "BEGIN; CREATE SCHEMA IF NOT EXISTS {}; ALTER EXTENSION \"{}\" SET SCHEMA {}; COMMIT;",
+        schema, ext_name, schema
+    ))
+}
+
+// Extension versions are often missing the patch number, like 1.2, so pad them before parsing
+fn parse_extension_version(version: &str) -> Option<Version> {
+    let version = version.trim_start_matches('v');
+    let (core, suffix) = match version.find(['-', '+']) {
+        Some(index) => version.split_at(index),
+        None => (version, ""),
+    };
+    let mut parts: Vec<&str> = core.split('.').collect();
+    while parts.len() < 3 {
+        parts.push("0");
+    }
+    Version::parse(&format!("{}{}", parts.join("."), suffix)).ok()
+}
+
+/// Whether going from the installed version to the target version is a downgrade. Versions that
+/// can not be compared are not considered a downgrade, Postgres decides if there is a path.
+pub fn is_downgrade(installed_version: &str, target_version: &str) -> bool {
+    match (
+        parse_extension_version(installed_version),
+        parse_extension_version(target_version),
+    ) {
+        (Some(installed), Some(target)) => target < installed,
+        _ => false,
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Eq, Hash, JsonSchema, Serialize, PartialEq, ToSchema)]
 pub struct ExtensionStatus {
     pub name: String,
@@ -166,6 +275,15 @@ pub struct ExtensionInstallLocationStatus {
     // Optional to handle upgrading existing resources
     pub error: Option<bool>,
     pub error_message: Option<String>,
+    /// Version requested in the spec, when it differs from the installed version
+    #[serde(default)]
+    pub target_version: Option<String>,
+    /// Versions the extension goes through to reach the target version, like 1.0--1.1--1.2
+    #[serde(default)]
+    pub update_path: Option<String>,
+    /// Schema requested in the spec, when it differs from the schema the extension is in
+    #[serde(default)]
+    pub target_schema: Option<String>,
 }
 
 pub fn get_location_status(
@@ -235,6 +353,9 @@ mod tests {
             enabled: Some(true),
             error: Some(false),
             error_message: None,
+            target_version: None,
+            update_path: None,
+            target_schema: None,
         };
         let cdb = CoreDB {
             metadata: Default::default(),
@@ -317,6 +438,84 @@ mod tests {
         assert_eq!(cmd.unwrap(), "DROP EXTENSION IF EXISTS \"my_ext\" CASCADE;");
     }
 
+    #[test]
+    fn test_generate_extension_update_cmd() {
+        let cmd = generate_extension_update_cmd("my_ext", "1.2.0");
+        assert_eq!(cmd.unwrap(), "ALTER EXTENSION \"my_ext\" UPDATE TO '1.2.0';");
+
+        let cmd = generate_extension_update_cmd("my_ext", "1.2'; DROP TABLE foo; --");
+        assert!(cmd.is_err());
+    }
+
+    #[test]
+    fn test_generate_extension_toggle_batch_cmd() {
+        let location = |enabled: bool| ExtensionInstallLocation {
+            enabled,
+            database: "postgres".to_string(),
+            schema: None,
+            version: None,
+        };
+        let cmd = generate_extension_toggle_batch_cmd(&[
+            ("pgmq".to_string(), location(true)),
+            ("hstore".to_string(), location(false)),
+        ]);
+        assert_eq!(
+            cmd.unwrap(),
+            "BEGIN; \
+             DO $toggle$ BEGIN CREATE EXTENSION IF NOT EXISTS \"pgmq\" CASCADE; EXCEPTION WHEN OTHERS THEN RAISE EXCEPTION 'Failed to enable extension pgmq (statement 1 of 2): %', SQLERRM; END $toggle$; \
+             DO $toggle$ BEGIN DROP EXTENSION IF EXISTS \"hstore\" CASCADE; EXCEPTION WHEN OTHERS THEN RAISE EXCEPTION 'Failed to disable extension hstore (statement 2 of 2): %', SQLERRM; END $toggle$; \
+             COMMIT;"
+        );
+
+        let mut invalid = location(true);
+        invalid.schema = Some("public; DROP TABLE foo".to_string());
+        let cmd = generate_extension_toggle_batch_cmd(&[
+            ("pgmq".to_string(), location(true)),
+            ("hstore".to_string(), invalid),
+        ]);
+        assert_eq!(
+            cmd.unwrap_err(),
+            (
+                "hstore".to_string(),
+                "Schema name is not formatted properly".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_toggle_batch_error() {
+        let stderr = "ERROR:  Failed to enable extension pgmq (statement 1 of 2): extension \"pgmq\" is not available\nCONTEXT:  PL/pgSQL function inline_code_block line 1 at RAISE";
+        assert_eq!(parse_toggle_batch_error(stderr), Some("pgmq".to_string()));
+        assert_eq!(
+            parse_toggle_batch_error("FATAL:  the database system is starting up"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_generate_extension_relocate_cmd() {
+        let cmd = generate_extension_relocate_cmd("my_ext", "extensions");
+        assert_eq!(
+            cmd.unwrap(),
+            "BEGIN; CREATE SCHEMA IF NOT EXISTS extensions; ALTER EXTENSION \"my_ext\" SET SCHEMA extensions; COMMIT;"
+        );
+
+        let cmd = generate_extension_relocate_cmd("my_ext", "public; DROP TABLE foo");
+        assert!(cmd.is_err());
+    }
+
+    #[test]
+    fn test_is_downgrade() {
+        assert!(is_downgrade("1.2.0", "1.1.0"));
+        assert!(is_downgrade("1.10", "1.9"));
+        assert!(is_downgrade("2.0", "1.9.9"));
+        assert!(!is_downgrade("1.1", "1.2.0"));
+        assert!(!is_downgrade("1.2", "1.2.0"));
+        assert!(!is_downgrade("0.3.0-dev", "0.3.0"));
+        // Versions that are not semver can not be compared
+        assert!(!is_downgrade("unpackaged", "1.0"));
+    }
+
     #[test]
     fn test_toggle_logic() {
         let desired_extensions = vec![
@@ -418,6 +617,9 @@ mod tests {
                     version: None,
                     error: Some(false),
                     error_message: None,
+                    target_version: None,
+                    update_path: None,
+                    target_schema: None,
                 },
                 // Requesting to disable a currently enabled extension
                 ExtensionInstallLocationStatus {
@@ -427,6 +629,9 @@ mod tests {
                     version: None,
                     error: Some(false),
                     error_message: None,
+                    target_version: None,
+                    update_path: None,
+                    target_schema: None,
                 },
                 ExtensionInstallLocationStatus {
                     enabled: Some(false),
@@ -436,6 +641,9 @@ mod tests {
                     version: None,
                     error: Some(true),
                     error_message: Some("Failed to enable extension".to_string()),
+                    target_version: None,
+                    update_path: None,
+                    target_schema: None,
                 },
                 ExtensionInstallLocationStatus {
                     enabled: None,
@@ -446,6 +654,9 @@ mod tests {
                     version: None,
                     error: Some(true),
                     error_message: Some("Extension is not installed".to_string()),
+                    target_version: None,
+                    update_path: None,
+                    target_schema: None,
                 },
                 ExtensionInstallLocationStatus {
                     enabled: Some(false),
@@ -454,6 +665,9 @@ mod tests {
                     version: None,
                     error: Some(true),
                     error_message: Some("Failed to enable extension".to_string()),
+                    target_version: None,
+                    update_path: None,
+                    target_schema: None,
                 },
             ],
         }];
@@ -482,6 +696,9 @@ mod tests {
                         schema: Some("public".to_string()),
                         version: None,
                         error_message: None,
+                        target_version: None,
+                        update_path: None,
+                        target_schema: None,
                     },
                     ExtensionInstallLocationStatus {
                         enabled: Some(true),
@@ -490,6 +707,9 @@ mod tests {
                         schema: Some("public".to_string()),
                         version: None,
                         error_message: None,
+                        target_version: None,
+                        update_path: None,
+                        target_schema: None,
                     },
                     ExtensionInstallLocationStatus {
                         enabled: Some(false),
@@ -498,6 +718,9 @@ mod tests {
                         schema: Some("public".to_string()),
                         version: None,
                         error_message: None,
+                        target_version: None,
+                        update_path: None,
+                        target_schema: None,
                     },
                     ExtensionInstallLocationStatus {
                         enabled: Some(true),
@@ -506,6 +729,9 @@ mod tests {
                         schema: Some("public".to_string()),
                         version: None,
                         error_message: None,
+                        target_version: None,
+                        update_path: None,
+                        target_schema: None,
                     },
                     ExtensionInstallLocationStatus {
                         enabled: Some(false),
@@ -515,6 +741,9 @@ mod tests {
                         schema: Some("public".to_string()),
                         version: None,
                         error_message: None,
+                        target_version: None,
+                        update_path: None,
+                        target_schema: None,
                     },
                     ExtensionInstallLocationStatus {
                         enabled: Some(false),
@@ -523,6 +752,9 @@ mod tests {
                         schema: Some("public".to_string()),
                         version: None,
                         error_message: None,
+                        target_version: None,
+                        update_path: None,
+                        target_schema: None,
                     },
                 ],
             },
@@ -537,6 +769,9 @@ mod tests {
                         schema: Some("public".to_string()),
                         version: None,
                         error_message: None,
+                        target_version: None,
+                        update_path: None,
+                        target_schema: None,
                     },
                     ExtensionInstallLocationStatus {
                         enabled: Some(true),
@@ -545,6 +780,9 @@ mod tests {
                         schema: Some("public".to_string()),
                         version: None,
                         error_message: None,
+                        target_version: None,
+                        update_path: None,
+                        target_schema: None,
                     },
                 ],
             },
@@ -685,6 +923,9 @@ mod tests {
                 version: None,
                 error: Some(false),
                 error_message: None,
+                target_version: None,
+                update_path: None,
+                target_schema: None,
             }],
         }];
         let new_location_status = ExtensionInstallLocationStatus {
@@ -694,6 +935,9 @@ mod tests {
             version: None,
             error: Some(false),
             error_message: None,
+            target_version: None,
+            update_path: None,
+            target_schema: None,
         };
 
         // Try updating existing from disabled to enabled
@@ -720,6 +964,9 @@ mod tests {
                 version: None,
                 error: Some(false),
                 error_message: None,
+                target_version: None,
+                update_path: None,
+                target_schema: None,
             }],
         }];
         let new_location_status = ExtensionInstallLocationStatus {
@@ -729,6 +976,9 @@ mod tests {
             version: None,
             error: Some(false),
             error_message: None,
+            target_version: None,
+            update_path: None,
+            target_schema: None,
         };
 
         let result = merge_location_status_into_extension_status_list(
@@ -759,6 +1009,9 @@ mod tests {
                 version: None,
                 error: Some(false),
                 error_message: None,
+                target_version: None,
+                update_path: None,
+                target_schema: None,
             }],
         }];
         let new_location_status = ExtensionInstallLocationStatus {
@@ -768,6 +1021,9 @@ mod tests {
             version: None,
             error: Some(false),
             error_message: None,
+            target_version: None,
+            update_path: None,
+            target_schema: None,
         };
 
         let result = merge_location_status_into_extension_status_list(
@@ -800,6 +1056,9 @@ mod tests {
                 enabled: Some(true),
                 error: Some(false),
                 error_message: None,
+                target_version: None,
+                update_path: None,
+                target_schema: None,
             }],
         };
         let extension: Extension = status.into();
@@ -818,6 +1077,7 @@ mod tests {
             error_message: None,
             installed_to_pods: None,
             loading: false,
+            attempts: None,
         };
         let trunk_install: TrunkInstall = status.into();
         assert_eq!(trunk_install.name, "pgmq");
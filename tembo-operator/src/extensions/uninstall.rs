@@ -0,0 +1,376 @@
+use crate::{
+    apis::coredb_types::CoreDB,
+    config::Config,
+    extensions::database_queries::{check_input, list_shared_preload_libraries},
+    publish_event,
+    trunk::{TrunkInstalledFiles, TrunkRegistry},
+    Context,
+};
+use k8s_openapi::api::core::v1::Pod;
+use kube::runtime::{controller::Action, events::EventType};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+use tracing::{info, instrument, warn};
+
+/// Trunk installs that were removed from the spec, which are uninstalled before they are removed
+/// from the status. Extensions that are still enabled in spec.extensions are not uninstalled.
+pub fn trunk_installs_to_uninstall(cdb: &CoreDB) -> Vec<String> {
+    cdb.status
+        .as_ref()
+        .and_then(|status| status.trunk_installs.as_ref())
+        .into_iter()
+        .flatten()
+        .map(|ext_status| ext_status.name.clone())
+        .filter(|name| !cdb.spec.trunk_installs.iter().any(|ext| &ext.name == name))
+        .filter(|name| {
+            !cdb.spec
+                .extensions
+                .iter()
+                .any(|ext| &ext.name == name && ext.locations.iter().any(|loc| loc.enabled))
+        })
+        .collect()
+}
+
+/// Libraries of the trunk installs being uninstalled, which have to be removed from
+/// shared_preload_libraries before their files are deleted
+pub fn libraries_to_unload(cdb: &CoreDB, requires_load: &BTreeMap<String, String>) -> BTreeSet<String> {
+    trunk_installs_to_uninstall(cdb)
+        .iter()
+        .filter_map(|name| requires_load.get(name).cloned())
+        .collect()
+}
+
+/// Databases where the extension is currently enabled
+fn enabled_databases(cdb: &CoreDB, name: &str) -> Vec<String> {
+    cdb.status
+        .as_ref()
+        .and_then(|status| status.extensions.as_ref())
+        .into_iter()
+        .flatten()
+        .filter(|ext| ext.name == name)
+        .flat_map(|ext| ext.locations.iter())
+        .filter(|loc| loc.enabled == Some(true))
+        .map(|loc| loc.database.clone())
+        .collect()
+}
+
+// Paths of the manifest are relative to the pkglibdir or the sharedir, and are quoted in the command
+fn check_installed_path(path: &str) -> bool {
+    !path.is_empty()
+        && !path.starts_with('/')
+        && !path.split('/').any(|part| part == "..")
+        && path
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '+' | '/'))
+}
+
+/// The command that deletes the files of an extension from the pkglibdir and sharedir volumes.
+/// The files listed by the manifest of the trunk archive are deleted, otherwise the files are
+/// found from the names of the extension and of its library.
+fn delete_files_command(
+    name: &str,
+    library_name: &str,
+    installed_files: Option<&TrunkInstalledFiles>,
+) -> Vec<String> {
+    let delete_files = match installed_files {
+        Some(installed_files) => {
+            let paths: Vec<String> = installed_files
+                .pkglibdir
+                .iter()
+                .map(|path| ("$pkglibdir", path))
+                .chain(installed_files.sharedir.iter().map(|path| ("$sharedir", path)))
+                .filter(|(_, path)| {
+                    let valid = check_installed_path(path);
+                    if !valid {
+                        warn!("Not deleting file {} of {}, its path is not valid", path, name);
+                    }
+                    valid
+                })
+                .map(|(dir, path)| format!("\"{}/{}\"", dir, path))
+                .collect();
+            format!("rm -f {}", paths.join(" "))
+        }
+        None => format!(
+            "rm -f \"$sharedir/extension/{name}.control\" \"$sharedir\"/extension/{name}--*.sql; \
+             rm -f \"$pkglibdir/{library_name}.so\" \"$pkglibdir/bitcode/{library_name}.index.bc\"; \
+             rm -rf \"$pkglibdir/bitcode/{library_name}\""
+        ),
+    };
+    let script = format!(
+        "set -e; \
+         pkglibdir=$(pg_config --pkglibdir); \
+         sharedir=$(pg_config --sharedir); \
+         {delete_files}"
+    );
+    vec!["/bin/sh".to_owned(), "-c".to_owned(), script]
+}
+
+// The files of the installed version, from the manifest of its trunk archive
+async fn installed_files(cdb: &CoreDB, registry: &TrunkRegistry, name: &str) -> Option<TrunkInstalledFiles> {
+    let version = cdb
+        .status
+        .as_ref()
+        .and_then(|status| status.trunk_installs.as_ref())
+        .into_iter()
+        .flatten()
+        .find(|ext_status| ext_status.name == name)
+        .and_then(|ext_status| ext_status.version.clone())?;
+    match registry.installed_files(name, &version).await {
+        Ok(installed_files) => installed_files,
+        Err(e) => {
+            warn!(
+                "Could not get the files of {} {} from trunk, deleting them by name: {}",
+                name, version, e
+            );
+            None
+        }
+    }
+}
+
+// Drop the extension without CASCADE, so that objects created on top of it are never dropped along
+// with a trunk install. They are dropped when the extension is disabled in spec.extensions.
+async fn drop_extension(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    cfg: &Config,
+    name: &str,
+    database: &str,
+) -> Result<(), String> {
+    if !check_input(database) {
+        return Err("Database name is not formatted properly".to_string());
+    }
+    let command = format!("DROP EXTENSION IF EXISTS \"{}\";", name);
+    match cdb.execute_sql(command, database.to_owned(), ctx, cfg).await {
+        Ok(output) if output.success => Ok(()),
+        Ok(output) => Err(output
+            .stderr
+            .unwrap_or_else(|| "Failed to drop the extension, and found no output".to_string())),
+        Err(_) => Err("Could not connect to the database".to_string()),
+    }
+}
+
+/// Uninstall a trunk install: drop the extension from every database, wait for its library to be
+/// unloaded, then delete its files from every pod.
+/// Returns whether the uninstall is complete, otherwise it is continued on the next reconcile. When
+/// other objects depend on the extension, the reason is added to blocked.
+#[instrument(skip(cdb, ctx, requires_load, pods, cfg, registry, blocked), fields(cdb_name = %cdb.metadata.name.clone().unwrap_or_default()))]
+pub async fn uninstall_trunk_install(
+    cdb: &CoreDB,
+    ctx: Arc<Context>,
+    cfg: &Config,
+    registry: &TrunkRegistry,
+    name: &str,
+    requires_load: &BTreeMap<String, String>,
+    pods: &[Pod],
+    blocked: &mut Vec<String>,
+) -> Result<bool, Action> {
+    let coredb_name = cdb.metadata.name.clone().expect("CoreDB should have a name");
+    let library_name = requires_load.get(name).map_or(name, String::as_str);
+    if !check_input(name) || !check_input(library_name) {
+        warn!(
+            "Trunk install {} is not formatted properly, not deleting files for {}",
+            name, coredb_name
+        );
+        return Ok(true);
+    }
+
+    // Drop the extension in every database where it is enabled
+    for database in enabled_databases(cdb, name) {
+        if let Err(error_message) = drop_extension(cdb, ctx.clone(), cfg, name, &database).await {
+            warn!(
+                "Failed to drop extension {} in database {} for {}: {}",
+                name, database, coredb_name, error_message
+            );
+            blocked.push(format!(
+                "Extension {} is still used in database {}, disable it in spec.extensions to drop the objects that depend on it: {}",
+                name,
+                database,
+                error_message.trim()
+            ));
+            publish_event(
+                cdb,
+                ctx.clone(),
+                EventType::Warning,
+                "TrunkUninstallFailed",
+                format!(
+                    "Failed to drop extension {} in database {}: {}",
+                    name, database, error_message
+                ),
+            )
+            .await;
+            return Ok(false);
+        }
+    }
+
+    // The library is removed from shared_preload_libraries of the cluster, which restarts
+    // Postgres. Its files can only be deleted once it is not loaded anymore.
+    if requires_load.contains_key(name) {
+        // The loaded libraries can't be checked while Postgres is restarting
+        let loaded = match list_shared_preload_libraries(cdb, ctx.clone(), cfg).await {
+            Ok(libraries) => libraries.iter().any(|library| library == library_name),
+            Err(_) => true,
+        };
+        if loaded {
+            info!(
+                "Waiting for {} to be unloaded before uninstalling {} from {}",
+                library_name, name, coredb_name
+            );
+            return Ok(false);
+        }
+    }
+
+    // Delete the files from every pod
+    let installed_files = installed_files(cdb, registry, name).await;
+    let command = delete_files_command(name, library_name, installed_files.as_ref());
+    for pod in pods {
+        let pod_name = pod.metadata.name.clone().expect("Pod should always have a name");
+        match cdb.exec(pod_name.clone(), ctx.client.clone(), &command).await {
+            Ok(output) if output.success => {}
+            Ok(output) => {
+                warn!(
+                    "Failed to delete the files of {} from {}: {}",
+                    name,
+                    pod_name,
+                    output.stderr.unwrap_or_default()
+                );
+                return Ok(false);
+            }
+            Err(e) => {
+                warn!(
+                    "Kube exec error deleting the files of {} from {}: {}",
+                    name, pod_name, e
+                );
+                return Ok(false);
+            }
+        }
+    }
+
+    info!("Uninstalled {} from {}", name, coredb_name);
+    publish_event(
+        cdb,
+        ctx.clone(),
+        EventType::Normal,
+        "TrunkUninstalled",
+        format!("Uninstalled {}", name),
+    )
+    .await;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        apis::coredb_types::{CoreDBSpec, CoreDBStatus},
+        extensions::types::{
+            Extension, ExtensionInstallLocation, ExtensionInstallLocationStatus, ExtensionStatus,
+            TrunkInstall, TrunkInstallStatus,
+        },
+    };
+
+    fn trunk_install_status(name: &str) -> TrunkInstallStatus {
+        TrunkInstallStatus {
+            name: name.to_string(),
+            version: Some("1.0.0".to_string()),
+            error: false,
+            loading: false,
+            error_message: None,
+            installed_to_pods: Some(vec!["test-coredb-1".to_string()]),
+            attempts: None,
+        }
+    }
+
+    #[test]
+    fn test_trunk_installs_to_uninstall() {
+        let cdb = CoreDB {
+            metadata: Default::default(),
+            spec: CoreDBSpec {
+                trunk_installs: vec![TrunkInstall {
+                    name: "pgmq".to_string(),
+                    version: Some("1.0.0".to_string()),
+                }],
+                extensions: vec![Extension {
+                    name: "pg_cron".to_string(),
+                    description: None,
+                    locations: vec![ExtensionInstallLocation {
+                        enabled: true,
+                        ..ExtensionInstallLocation::default()
+                    }],
+                }],
+                ..CoreDBSpec::default()
+            },
+            status: Some(CoreDBStatus {
+                trunk_installs: Some(vec![
+                    trunk_install_status("pgmq"),
+                    trunk_install_status("pg_cron"),
+                    trunk_install_status("pg_partman"),
+                ]),
+                extensions: Some(vec![ExtensionStatus {
+                    name: "pg_partman".to_string(),
+                    description: None,
+                    locations: vec![
+                        ExtensionInstallLocationStatus {
+                            database: "postgres".to_string(),
+                            schema: Some("public".to_string()),
+                            version: Some("4.7.3".to_string()),
+                            enabled: Some(true),
+                            error: Some(false),
+                            error_message: None,
+                            target_version: None,
+                            update_path: None,
+                            target_schema: None,
+                        },
+                        ExtensionInstallLocationStatus {
+                            database: "app".to_string(),
+                            schema: Some("public".to_string()),
+                            version: Some("4.7.3".to_string()),
+                            enabled: Some(false),
+                            error: Some(false),
+                            error_message: None,
+                            target_version: None,
+                            update_path: None,
+                            target_schema: None,
+                        },
+                    ],
+                }]),
+                ..CoreDBStatus::default()
+            }),
+        };
+
+        // pg_cron is still enabled, so it is not uninstalled
+        assert_eq!(trunk_installs_to_uninstall(&cdb), vec!["pg_partman"]);
+        assert_eq!(enabled_databases(&cdb, "pg_partman"), vec!["postgres"]);
+        let requires_load = BTreeMap::from([
+            ("pg_partman".to_string(), "pg_partman_bgw".to_string()),
+            ("pg_cron".to_string(), "pg_cron".to_string()),
+        ]);
+        assert_eq!(
+            libraries_to_unload(&cdb, &requires_load),
+            BTreeSet::from(["pg_partman_bgw".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_delete_files_command() {
+        let command = delete_files_command("pg_partman", "pg_partman_bgw", None);
+        assert_eq!(command[0], "/bin/sh");
+        assert!(command[2].contains("\"$sharedir\"/extension/pg_partman--*.sql"));
+        assert!(command[2].contains("rm -f \"$pkglibdir/pg_partman_bgw.so\""));
+
+        // The files of the manifest are deleted, unless their path is not valid
+        let installed_files = TrunkInstalledFiles {
+            pkglibdir: vec!["pg_partman_bgw.so".to_string(), "../../bin/postgres".to_string()],
+            sharedir: vec![
+                "extension/pg_partman.control".to_string(),
+                "extension/pg_partman--4.7.3.sql".to_string(),
+                "extension/$(reboot).sql".to_string(),
+            ],
+        };
+        let command = delete_files_command("pg_partman", "pg_partman_bgw", Some(&installed_files));
+        assert!(command[2].ends_with(
+            "rm -f \"$pkglibdir/pg_partman_bgw.so\" \"$sharedir/extension/pg_partman.control\" \"$sharedir/extension/pg_partman--4.7.3.sql\""
+        ));
+    }
+}
@@ -1,6 +1,7 @@
 //! Helper methods only available for tests
 use crate::{
     apis::coredb_types::{CoreDB, CoreDBSpec, CoreDBStatus},
+    postgres_client::PostgresPools,
     Context, Metrics, COREDB_FINALIZER,
 };
 use assert_json_diff::assert_json_include;
@@ -202,6 +203,7 @@ impl Context {
                 client: mock_client,
                 metrics: Metrics::default().register(&registry).unwrap(),
                 diagnostics: Arc::default(),
+                postgres_pools: PostgresPools::default(),
             }),
             ApiServerVerifier(handle),
             registry,
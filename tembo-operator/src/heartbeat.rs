@@ -1,4 +1,4 @@
-use crate::{apis::coredb_types::CoreDB, psql::PsqlOutput, Context};
+use crate::{apis::coredb_types::CoreDB, config::Config, psql::PsqlOutput, Context};
 use kube::{runtime::controller::Action, ResourceExt};
 use std::sync::Arc;
 use tokio::time::Duration;
@@ -52,10 +52,10 @@ $$;
 
 // reconcile_heartbeat is a function to run the setup_heartbeat function on the database instance
 // and then run the run_heartbeat function to insert a timestamp into the heartbeat_table.
-pub async fn reconcile_heartbeat(coredb: &CoreDB, ctx: Arc<Context>) -> Result<(), Action> {
+pub async fn reconcile_heartbeat(coredb: &CoreDB, ctx: Arc<Context>, cfg: &Config) -> Result<(), Action> {
     // Match to make sure the HEARTBEAT_FUNCTION is installed on the database instance, requeue if
     // it fails for some reason.
-    match setup_heartbeat(coredb, ctx.clone()).await {
+    match setup_heartbeat(coredb, ctx.clone(), cfg).await {
         Ok(_) => debug!(
             "Successfully created setup_heartbeat function on instance {}",
             coredb.name_any()
@@ -67,10 +67,11 @@ pub async fn reconcile_heartbeat(coredb: &CoreDB, ctx: Arc<Context>) -> Result<(
     }
     // Run the setup_pgbouncer function
     coredb
-        .psql(
+        .execute_sql(
             "SELECT run_heartbeat();".to_string(),
             "postgres".to_string(),
             ctx.clone(),
+            cfg,
         )
         .await?;
 
@@ -79,13 +80,14 @@ pub async fn reconcile_heartbeat(coredb: &CoreDB, ctx: Arc<Context>) -> Result<(
 
 // setup_heartbeat is a function to create a schema and table to write to everytime there is a
 // reconciliation loop.
-async fn setup_heartbeat(coredb: &CoreDB, ctx: Arc<Context>) -> Result<PsqlOutput, Action> {
+async fn setup_heartbeat(coredb: &CoreDB, ctx: Arc<Context>, cfg: &Config) -> Result<PsqlOutput, Action> {
     // Install or update the HEARTBEAT_FUNCTION function on the database instance
     let query = coredb
-        .psql(
+        .execute_sql(
             HEARTBEAT_FUNCTION.to_string(),
             "postgres".to_string(),
             ctx.clone(),
+            cfg,
         )
         .await?;
     Ok(query)
@@ -73,6 +73,23 @@ fn postgres_ingress_route_tcp(
     }
 }
 
+// IngressRouteTCPs are named with their prefix followed by an index, e.g. "name-rw-0". Matching on
+// the index keeps one prefix from claiming the routes of another, like "name-pooler-" and
+// "name-pooler-ro-". The IngressRouteTCP named after the instance predates the prefixes, it is
+// always for the read-write service.
+fn is_ingress_route_tcp_for_prefix(
+    ingress_route_tcp_name: &str,
+    cdb_name: &str,
+    ingress_name_prefix: &str,
+) -> bool {
+    if ingress_route_tcp_name == cdb_name {
+        return ingress_name_prefix == format!("{}-rw-", cdb_name);
+    }
+    ingress_route_tcp_name
+        .strip_prefix(ingress_name_prefix)
+        .is_some_and(|index| index.parse::<u32>().is_ok())
+}
+
 // For end-user provided, extra domain names,
 // we allow for update and deletion of domain names.
 pub async fn reconcile_extra_postgres_ing_route_tcp(
@@ -272,9 +289,11 @@ pub async fn reconcile_postgres_ing_route_tcp(
     for ingress_route_tcp in &ingress_route_tcps {
         let ingress_route_tcp_name = match ingress_route_tcp.metadata.name.clone() {
             Some(ingress_route_tcp_name) => {
-                if !(ingress_route_tcp_name.starts_with(ingress_name_prefix)
-                    || ingress_route_tcp_name == cdb.name_any())
-                {
+                if !is_ingress_route_tcp_for_prefix(
+                    &ingress_route_tcp_name,
+                    &cdb.name_any(),
+                    ingress_name_prefix,
+                ) {
                     debug!(
                         "Skipping ingress route tcp without prefix {}: {}",
                         ingress_name_prefix, ingress_route_tcp_name
@@ -520,10 +539,31 @@ pub fn valid_cidrs(source_range: &[String]) -> Vec<String> {
 mod tests {
     use crate::{
         apis::coredb_types::{CoreDB, CoreDBSpec},
-        ingress::generate_ip_allow_list_middleware_tcp,
+        ingress::{generate_ip_allow_list_middleware_tcp, is_ingress_route_tcp_for_prefix},
     };
     use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
 
+    #[test]
+    fn test_is_ingress_route_tcp_for_prefix() {
+        assert!(is_ingress_route_tcp_for_prefix("test-rw-0", "test", "test-rw-"));
+        assert!(is_ingress_route_tcp_for_prefix("test-ro-12", "test", "test-ro-"));
+        // The legacy IngressRouteTCP only belongs to the read-write service
+        assert!(is_ingress_route_tcp_for_prefix("test", "test", "test-rw-"));
+        assert!(!is_ingress_route_tcp_for_prefix("test", "test", "test-ro-"));
+        assert!(!is_ingress_route_tcp_for_prefix("test", "test", "test-pooler-"));
+        // The read-only pooler routes do not belong to the read-write pooler
+        assert!(!is_ingress_route_tcp_for_prefix(
+            "test-pooler-ro-0",
+            "test",
+            "test-pooler-"
+        ));
+        assert!(is_ingress_route_tcp_for_prefix(
+            "test-pooler-ro-0",
+            "test",
+            "test-pooler-ro-"
+        ));
+    }
+
     #[test]
     fn test_no_ip_allow_list() {
         let cdb = CoreDB {
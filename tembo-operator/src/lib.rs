@@ -15,11 +15,13 @@ mod exec;
 /// Metrics
 mod metrics;
 pub use metrics::Metrics;
-mod config;
+pub mod config;
 pub mod defaults;
 pub mod errors;
 
 pub mod cloudnativepg;
+pub mod conditions;
+mod databases;
 mod deployment_postgres_exporter;
 #[cfg(test)] pub mod fixtures;
 pub mod heartbeat;
@@ -29,13 +31,20 @@ pub use traefik::ingress_route_crd;
 mod certmanager;
 mod network_policies;
 pub mod postgres_certificates;
+pub mod postgres_client;
 pub mod psql;
 mod rbac;
 mod secret;
 mod service;
 mod trunk;
+pub mod webhook;
 
 pub const RESTARTED_AT: &str = "kubectl.kubernetes.io/restartedAt";
+pub const HIBERNATION: &str = "cnpg.io/hibernation";
+// Comma separated names of trunk installs to retry right away, removed once they are retried
+pub const RETRY_TRUNK_INSTALLS: &str = "tembo.io/retry-trunk-installs";
+// Timestamp of an on-demand backup, a new value requests another backup
+pub const BACKUP_REQUESTED_AT: &str = "tembo.io/backup-requested-at";
 
 use thiserror::Error;
 
@@ -63,6 +72,12 @@ pub enum Error {
 
     #[error("Invalid Data: {0}")]
     InvalidErr(String),
+
+    #[error("Postgres Error: {0}")]
+    PostgresError(#[from] sqlx::Error),
+
+    #[error("Object Store Error: {0}")]
+    ObjectStoreError(String),
 }
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
@@ -1,6 +1,12 @@
 use actix_web::{get, middleware, web::Data, App, HttpRequest, HttpResponse, HttpServer, Responder};
-pub use controller::{self, telemetry, State};
+pub use controller::{self, config::Config, telemetry, webhook, State};
+use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
 use prometheus::{Encoder, TextEncoder};
+use std::time::Duration;
+use tracing::info;
+
+// How often the mounted webhook certificate is checked for a renewal
+const WEBHOOK_CERT_CHECK_INTERVAL: Duration = Duration::from_secs(60);
 
 #[get("/metrics")]
 async fn metrics(c: Data<State>, _req: HttpRequest) -> impl Responder {
@@ -46,11 +52,69 @@ async fn app_main() -> anyhow::Result<()> {
     .bind(("0.0.0.0", server_port))?
     .shutdown_timeout(5);
 
-    // Both runtimes implements graceful shutdown, so poll until both are done
-    tokio::join!(controller, server.run()).1?;
+    // The webhook server is only started when it is enabled
+    let cfg = Config::default();
+    let webhook_server = async {
+        if cfg.enable_webhook {
+            run_webhook(&cfg).await
+        } else {
+            Ok(())
+        }
+    };
+
+    // All runtimes implement graceful shutdown, so poll until all are done. A failure of a
+    // server stops the others, so that the pod restarts instead of running without it.
+    tokio::try_join!(
+        async {
+            controller.await;
+            Ok(())
+        },
+        async { server.run().await.map_err(anyhow::Error::from) },
+        webhook_server
+    )?;
     Ok(())
 }
 
+// The admission and conversion webhooks require TLS, so they run on their own port. The
+// certificate is renewed by cert-manager in the mounted secret, the server is started again with
+// the new certificate when it changes.
+async fn run_webhook(cfg: &Config) -> anyhow::Result<()> {
+    loop {
+        let cert = std::fs::read(&cfg.webhook_tls_cert)?;
+        let mut tls_config = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+        tls_config.set_private_key_file(&cfg.webhook_tls_key, SslFiletype::PEM)?;
+        tls_config.set_certificate_chain_file(&cfg.webhook_tls_cert)?;
+        let server = HttpServer::new(|| {
+            App::new()
+                .wrap(middleware::Logger::default())
+                .service(webhook::validate)
+                .service(webhook::convert)
+        })
+        .bind_openssl(("0.0.0.0", cfg.webhook_port), tls_config)?
+        .shutdown_timeout(5)
+        .run();
+        let handle = server.handle();
+        tokio::pin!(server);
+
+        let cert_changed = async {
+            loop {
+                tokio::time::sleep(WEBHOOK_CERT_CHECK_INTERVAL).await;
+                if std::fs::read(&cfg.webhook_tls_cert).is_ok_and(|new_cert| new_cert != cert) {
+                    break;
+                }
+            }
+        };
+        tokio::select! {
+            result = &mut server => return Ok(result?),
+            _ = cert_changed => {
+                info!("The webhook certificate changed, restarting the webhook server");
+                let (_, result) = tokio::join!(handle.stop(true), server);
+                result?;
+            }
+        }
+    }
+}
+
 fn main() -> anyhow::Result<()> {
     let rt = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
@@ -1,17 +1,16 @@
+use crate::Error;
 use k8s_openapi::api::{
     core::v1::{Endpoints, Service},
     networking::v1::NetworkPolicy,
 };
 use kube::{
     api::{Patch, PatchParams},
-    runtime::controller::Action,
     Api, Client,
 };
 use serde_json::Value;
-use std::time::Duration;
 use tracing::{debug, error};
 
-pub async fn reconcile_network_policies(client: Client, namespace: &str) -> Result<(), Action> {
+pub async fn reconcile_network_policies(client: Client, namespace: &str) -> Result<(), Error> {
     let kubernetes_api_ip_addresses = lookup_kubernetes_api_ips(&client).await?;
 
     let np_api: Api<NetworkPolicy> = Api::namespaced(client, namespace);
@@ -253,28 +252,32 @@ pub async fn reconcile_network_policies(client: Client, namespace: &str) -> Resu
 // kubectl get svc -n default kubernetes
 // kubectl get endpoints -n default kubernetes
 // To return the IP addresses of the kubernetes API server
-async fn lookup_kubernetes_api_ips(client: &Client) -> Result<Vec<String>, Action> {
+async fn lookup_kubernetes_api_ips(client: &Client) -> Result<Vec<String>, Error> {
     let service_api = Api::<Service>::namespaced(client.clone(), "default");
     // Look up IP address of 'kubernetes' service in default namespace
     let kubernetes_service = match service_api.get("kubernetes").await {
         Ok(s) => s,
-        Err(_) => {
-            error!("Failed to get kubernetes service");
-            return Err(Action::requeue(Duration::from_secs(300)));
+        Err(e) => {
+            error!("Failed to get kubernetes service: {}", e);
+            return Err(Error::KubeError(e));
         }
     };
     let kubernetes_service_spec = match kubernetes_service.spec {
         Some(s) => s,
         None => {
             error!("while discovering kubernetes API IP address, service has no spec");
-            return Err(Action::requeue(Duration::from_secs(300)));
+            return Err(Error::InvalidErr(
+                "while discovering kubernetes API IP address, service has no spec".to_string(),
+            ));
         }
     };
     let cluster_ip = match kubernetes_service_spec.cluster_ip.clone() {
         Some(c) => c,
         None => {
             error!("while discovering kubernetes API IP address, service has no cluster IP");
-            return Err(Action::requeue(Duration::from_secs(300)));
+            return Err(Error::InvalidErr(
+                "while discovering kubernetes API IP address, service has no cluster IP".to_string(),
+            ));
         }
     };
     let mut results = Vec::new();
@@ -284,26 +287,33 @@ async fn lookup_kubernetes_api_ips(client: &Client) -> Result<Vec<String>, Actio
         Ok(endpoint) => endpoint,
         Err(e) => {
             error!("Failed to get kubernetes endpoint: {}", e);
-            return Err(Action::requeue(Duration::from_secs(300)));
+            return Err(Error::KubeError(e));
         }
     };
     let kubernetes_endpoint_subsets = match kubernetes_endpoint.subsets {
         Some(s) => s,
         None => {
             error!("while discovering kubernetes API IP address, endpoint has no subsets");
-            return Err(Action::requeue(Duration::from_secs(300)));
+            return Err(Error::InvalidErr(
+                "while discovering kubernetes API IP address, endpoint has no subsets".to_string(),
+            ));
         }
     };
     if kubernetes_endpoint_subsets.is_empty() {
         error!("While discovering kubernetes API IP address, found no endpoints");
-        return Err(Action::requeue(Duration::from_secs(300)));
+        return Err(Error::InvalidErr(
+            "While discovering kubernetes API IP address, found no endpoints".to_string(),
+        ));
     }
     for subset in kubernetes_endpoint_subsets {
         let addresses = match subset.addresses {
             Some(a) => a,
             None => {
                 error!("while discovering kubernetes API IP address, endpoint subset has no addresses");
-                return Err(Action::requeue(Duration::from_secs(300)));
+                return Err(Error::InvalidErr(
+                    "while discovering kubernetes API IP address, endpoint subset has no addresses"
+                        .to_string(),
+                ));
             }
         };
         for address in addresses {
@@ -314,12 +324,15 @@ async fn lookup_kubernetes_api_ips(client: &Client) -> Result<Vec<String>, Actio
     Ok(results)
 }
 
-async fn apply_network_policy(namespace: &str, np_api: &Api<NetworkPolicy>, np: Value) -> Result<(), Action> {
+async fn apply_network_policy(namespace: &str, np_api: &Api<NetworkPolicy>, np: Value) -> Result<(), Error> {
     let network_policy: NetworkPolicy = match serde_json::from_value(np) {
         Ok(np) => np,
-        Err(_) => {
+        Err(e) => {
             error!("Failed to deserialize Network Policy namespace {}", namespace);
-            return Err(Action::requeue(Duration::from_secs(300)));
+            return Err(Error::InvalidErr(format!(
+                "Failed to deserialize Network Policy: {}",
+                e
+            )));
         }
     };
     let name = network_policy
@@ -332,12 +345,12 @@ async fn apply_network_policy(namespace: &str, np_api: &Api<NetworkPolicy>, np:
     debug!("\nApplying Network Policy {} in namespace {}", name, namespace);
     let _o: NetworkPolicy = match np_api.patch(&name, &params, &Patch::Apply(&network_policy)).await {
         Ok(np) => np,
-        Err(_) => {
+        Err(e) => {
             error!(
-                "Failed to create Network Policy {} in namespace {}",
-                name, namespace
+                "Failed to create Network Policy {} in namespace {}: {}",
+                name, namespace, e
             );
-            return Err(Action::requeue(Duration::from_secs(300)));
+            return Err(Error::KubeError(e));
         }
     };
     Ok(())
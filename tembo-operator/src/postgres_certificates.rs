@@ -2,22 +2,21 @@ use crate::{
     apis::coredb_types::CoreDB,
     certmanager::certificates::Certificate,
     secret::{b64_encode, fetch_decoded_data_key_from_secret},
+    Error,
 };
 use k8s_openapi::api::core::v1::Secret;
 use kube::{
     api::{Api, Patch, PatchParams},
-    runtime::controller::Action,
     Client,
 };
 use serde_json::{json, Value};
-use std::time::Duration;
 use tracing::{debug, error};
 
 const POSTGRES_CA_SECRET_NAME: &str = "postgres-ca-secret";
 const POSTGRES_CA_SECRET_CERT_KEY_NAME: &str = "ca.crt";
 const POSTGRES_CERTIFICATE_ISSUER_NAME: &str = "postgres-server-issuer";
 
-pub async fn reconcile_certificates(client: Client, coredb: &CoreDB, namespace: &str) -> Result<(), Action> {
+pub async fn reconcile_certificates(client: Client, coredb: &CoreDB, namespace: &str) -> Result<(), Error> {
     match std::env::var("USE_SHARED_CA") {
         Ok(_) => {}
         Err(_) => {
@@ -44,7 +43,10 @@ pub async fn reconcile_certificates(client: Client, coredb: &CoreDB, namespace:
                 "Failed to fetch CA certificate from cert-manager namespace: {}",
                 e
             );
-            return Err(Action::requeue(Duration::from_secs(300)));
+            return Err(Error::MissingSecretError(format!(
+                "Failed to fetch CA certificate from cert-manager namespace: {}",
+                e
+            )));
         }
     };
 
@@ -73,7 +75,7 @@ pub async fn reconcile_certificates(client: Client, coredb: &CoreDB, namespace:
                 "Failed to apply CA certificate secret from cert-manager namespace to namespace {}, {}",
                 namespace, e
             );
-            return Err(Action::requeue(Duration::from_secs(300)));
+            return Err(Error::KubeError(e));
         }
     };
 
@@ -183,12 +185,15 @@ async fn apply_certificate(
     namespace: &str,
     cert_api: &Api<Certificate>,
     cert_value: Value,
-) -> Result<(), Action> {
+) -> Result<(), Error> {
     let certificate: Certificate = match serde_json::from_value(cert_value) {
         Ok(cert) => cert,
-        Err(_) => {
+        Err(e) => {
             error!("Failed to deserialize Certificate in namespace {}", namespace);
-            return Err(Action::requeue(Duration::from_secs(300)));
+            return Err(Error::InvalidErr(format!(
+                "Failed to deserialize Certificate: {}",
+                e
+            )));
         }
     };
     let name = certificate
@@ -206,7 +211,7 @@ async fn apply_certificate(
                 "Failed to create Certificate {} in namespace {}. {:?}",
                 name, namespace, e
             );
-            return Err(Action::requeue(Duration::from_secs(300)));
+            return Err(Error::KubeError(e));
         }
     };
     Ok(())
@@ -24,6 +24,8 @@ impl PsqlOutput {
     }
 }
 
+// Runs psql in the primary pod over kube exec. When the native Postgres client is enabled, this is
+// only used when the operator can not connect to the instance, see postgres_client.
 pub struct PsqlCommand {
     pod_name: String,
     namespace: String,
@@ -51,7 +51,9 @@ pub async fn reconcile_secret(cdb: &CoreDB, ctx: Arc<Context>) -> Result<(), Err
         }
     };
 
-    let data = secret_data(cdb, &ns, password);
+    // Hostnames routed through Traefik are only known when the data plane has a base domain
+    let basedomain = std::env::var("DATA_PLANE_BASEDOMAIN").ok();
+    let data = secret_data(cdb, &ns, password, basedomain.as_deref());
 
     let secret: Secret = Secret {
         metadata: ObjectMeta {
@@ -73,7 +75,12 @@ pub async fn reconcile_secret(cdb: &CoreDB, ctx: Arc<Context>) -> Result<(), Err
     Ok(())
 }
 
-fn secret_data(cdb: &CoreDB, ns: &str, password: String) -> BTreeMap<String, ByteString> {
+fn secret_data(
+    cdb: &CoreDB,
+    ns: &str,
+    password: String,
+    basedomain: Option<&str>,
+) -> BTreeMap<String, ByteString> {
     let mut data = BTreeMap::new();
 
     // encode and insert user into secret data
@@ -100,6 +107,8 @@ fn secret_data(cdb: &CoreDB, ns: &str, password: String) -> BTreeMap<String, Byt
     let ro_host: String = format!("{}-ro.{}.svc.cluster.local", &cdb.name_any(), &ns);
     // pooler host
     let pooler_host = format!("{}-pooler.{}.svc.cluster.local", &cdb.name_any(), &ns);
+    // read only pooler host
+    let ro_pooler_host = format!("{}-pooler-ro.{}.svc.cluster.local", &cdb.name_any(), &ns);
 
     // encode and insert host into secret data
     let b64_host = b64_encode(&r_host);
@@ -125,6 +134,38 @@ fn secret_data(cdb: &CoreDB, ns: &str, password: String) -> BTreeMap<String, Byt
         let pooler_uri = format!("postgresql://{}:{}@{}:{}", &user, &password, &pooler_host, &port);
         let b64_pooler_uri = b64_encode(&pooler_uri);
         data.insert("pooler_uri".to_owned(), b64_pooler_uri);
+
+        // encode and insert read-only pooler uri into secret data
+        if cdb.spec.connectionPooler.readOnly {
+            let ro_pooler_uri = format!(
+                "postgresql://{}:{}@{}:{}",
+                &user, &password, &ro_pooler_host, &port
+            );
+            let b64_ro_pooler_uri = b64_encode(&ro_pooler_uri);
+            data.insert("ro_pooler_uri".to_owned(), b64_ro_pooler_uri);
+        }
+    }
+
+    // encode and insert the read-only hostnames routed through Traefik into secret data
+    if let Some(basedomain) = basedomain {
+        let ro_external_host = format!("{}-ro.{}", &cdb.name_any(), basedomain);
+        let ro_external_uri = format!(
+            "postgresql://{}:{}@{}:{}",
+            &user, &password, &ro_external_host, &port
+        );
+        data.insert("ro_external_uri".to_owned(), b64_encode(&ro_external_uri));
+
+        if cdb.spec.connectionPooler.enabled && cdb.spec.connectionPooler.readOnly {
+            let ro_pooler_external_host = format!("{}-pooler-ro.{}", &cdb.name_any(), basedomain);
+            let ro_pooler_external_uri = format!(
+                "postgresql://{}:{}@{}:{}",
+                &user, &password, &ro_pooler_external_host, &port
+            );
+            data.insert(
+                "ro_pooler_external_uri".to_owned(),
+                b64_encode(&ro_pooler_external_uri),
+            );
+        }
     }
 
     data
@@ -238,3 +279,47 @@ fn generate_password() -> String {
     };
     pg.generate_one().unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::coredb_types::{ConnectionPooler, CoreDBSpec};
+
+    #[test]
+    fn test_secret_data_read_only_endpoints() {
+        let mut cdb = CoreDB::new("test", CoreDBSpec {
+            port: 5432,
+            connectionPooler: ConnectionPooler {
+                enabled: true,
+                readOnly: true,
+                ..ConnectionPooler::default()
+            },
+            ..CoreDBSpec::default()
+        });
+        let decode = |data: &BTreeMap<String, ByteString>, key: &str| {
+            String::from_utf8(data.get(key).unwrap().0.clone()).unwrap()
+        };
+
+        let data = secret_data(&cdb, "org-test", "pw".to_string(), Some("data-1.example.com"));
+        assert_eq!(
+            decode(&data, "ro_pooler_uri"),
+            "postgresql://postgres:pw@test-pooler-ro.org-test.svc.cluster.local:5432"
+        );
+        assert_eq!(
+            decode(&data, "ro_external_uri"),
+            "postgresql://postgres:pw@test-ro.data-1.example.com:5432"
+        );
+        assert_eq!(
+            decode(&data, "ro_pooler_external_uri"),
+            "postgresql://postgres:pw@test-pooler-ro.data-1.example.com:5432"
+        );
+
+        // Without the read-only pooler or a base domain, only the in-cluster read-only uri is set
+        cdb.spec.connectionPooler.readOnly = false;
+        let data = secret_data(&cdb, "org-test", "pw".to_string(), None);
+        assert!(data.contains_key("ro_uri"));
+        assert!(data.contains_key("pooler_uri"));
+        assert!(!data.contains_key("ro_pooler_uri"));
+        assert!(!data.contains_key("ro_external_uri"));
+    }
+}
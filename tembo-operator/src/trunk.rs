@@ -1,12 +1,15 @@
 use k8s_openapi::api::core::v1::ConfigMap;
 use kube::{runtime::controller::Action, Api, Client};
 use lazy_static::lazy_static;
-use std::{collections::BTreeMap, env, time::Duration};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Certificate,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{collections::BTreeMap, fs, io::Read, time::Duration};
 
-use crate::configmap::apply_configmap;
-use tracing::log::error;
-
-const DEFAULT_TRUNK_REGISTRY_DOMAIN: &str = "registry.pgtrunk.io";
+use crate::{apis::postgres_parameters::PgConfig, config::Config, configmap::apply_configmap};
+use tracing::log::{error, warn};
 
 // One configmap per namespace
 // multiple DBs in the same namespace can share the same configmap
@@ -35,6 +38,47 @@ lazy_static! {
     };
 }
 
+// Dependencies between extensions that are not described in Trunk yet.
+// The extensions listed here are enabled by CREATE EXTENSION ... CASCADE, but they
+// have to be installed first.
+lazy_static! {
+    pub static ref EXTRA_EXTENSION_DEPENDENCIES: BTreeMap<String, Vec<String>> = {
+        let mut extra_extension_dependencies = BTreeMap::new();
+        extra_extension_dependencies.insert("vectorize".to_string(), vec![
+            "pg_cron".to_string(),
+            "pgmq".to_string(),
+        ]);
+        extra_extension_dependencies
+    };
+}
+
+// Postgres settings that come with enabling an extension, when Trunk does not recommend a
+// default value or the default value does not work in Tembo. These take precedence over Trunk.
+lazy_static! {
+    pub static ref EXTRA_EXTENSION_CONFIGS: BTreeMap<String, BTreeMap<String, String>> = {
+        let mut extra_extension_configs = BTreeMap::new();
+        extra_extension_configs.insert(
+            "pg_cron".to_string(),
+            BTreeMap::from([("cron.host".to_string(), "/controller/run".to_string())]),
+        );
+        extra_extension_configs.insert(
+            "pg_partman".to_string(),
+            BTreeMap::from([
+                ("pg_partman_bgw.dbname".to_string(), "postgres".to_string()),
+                ("pg_partman_bgw.role".to_string(), "postgres".to_string()),
+            ]),
+        );
+        extra_extension_configs.insert(
+            "vectorize".to_string(),
+            BTreeMap::from([(
+                "vectorize.host".to_string(),
+                "postgresql:///postgres?host=/controller/run".to_string(),
+            )]),
+        );
+        extra_extension_configs
+    };
+}
+
 pub async fn extensions_that_require_load(
     client: Client,
     namespace: &str,
@@ -76,56 +120,478 @@ pub async fn extensions_that_require_load(
     }
 }
 
-pub async fn reconcile_trunk_configmap(client: Client, namespace: &str) -> Result<(), Action> {
-    let libraries = match requires_load_list_from_trunk().await {
-        Ok(libraries) => libraries,
+// Returns the extensions that each extension depends on, by extension name
+pub async fn extension_dependencies(
+    client: Client,
+    namespace: &str,
+) -> Result<BTreeMap<String, Vec<String>>, Action> {
+    let cm_api: Api<ConfigMap> = Api::namespaced(client, namespace);
+
+    let cm = match cm_api.get(TRUNK_CONFIGMAP_NAME).await {
+        Ok(configmap) => configmap,
+        Err(_) => {
+            error!("Failed to get trunk configmap in namespace {}", namespace);
+            return Err(Action::requeue(Duration::from_secs(300)));
+        }
+    };
+    let mut dependencies_map = BTreeMap::new();
+    // Configmaps written by older versions of the operator only have the libraries
+    match cm.data.as_ref().and_then(|data| data.get("dependencies")) {
+        Some(dependencies_str) => match serde_json::from_str(dependencies_str) {
+            Ok(dependencies) => dependencies_map = dependencies,
+            Err(e) => error!(
+                "Invalid dependencies in trunk metadata configmap in namespace {}: {}",
+                namespace, e
+            ),
+        },
+        None => warn!(
+            "No dependencies in trunk metadata configmap in namespace {}",
+            namespace
+        ),
+    }
+    // Add any extra dependencies
+    for (name, dependencies) in EXTRA_EXTENSION_DEPENDENCIES.iter() {
+        let entry: &mut Vec<String> = dependencies_map.entry(name.clone()).or_default();
+        for dependency in dependencies {
+            if !entry.contains(dependency) {
+                entry.push(dependency.clone());
+            }
+        }
+    }
+    Ok(dependencies_map)
+}
+
+// Returns the Postgres settings that each extension comes with, by extension name
+pub async fn extension_configs(
+    client: Client,
+    namespace: &str,
+) -> Result<BTreeMap<String, Vec<PgConfig>>, Action> {
+    let cm_api: Api<ConfigMap> = Api::namespaced(client, namespace);
+
+    let cm = match cm_api.get(TRUNK_CONFIGMAP_NAME).await {
+        Ok(configmap) => configmap,
+        Err(_) => {
+            error!("Failed to get trunk configmap in namespace {}", namespace);
+            return Err(Action::requeue(Duration::from_secs(300)));
+        }
+    };
+    let mut configs_map: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+    // Configmaps written by older versions of the operator don't have the configurations
+    match cm.data.as_ref().and_then(|data| data.get("configurations")) {
+        Some(configs_str) => match serde_json::from_str(configs_str) {
+            Ok(configs) => configs_map = configs,
+            Err(e) => error!(
+                "Invalid configurations in trunk metadata configmap in namespace {}: {}",
+                namespace, e
+            ),
+        },
+        None => warn!(
+            "No configurations in trunk metadata configmap in namespace {}",
+            namespace
+        ),
+    }
+    // Add any extra configurations
+    for (name, configs) in EXTRA_EXTENSION_CONFIGS.iter() {
+        configs_map
+            .entry(name.clone())
+            .or_default()
+            .extend(configs.clone());
+    }
+    Ok(configs_map
+        .into_iter()
+        .map(|(name, configs)| {
+            let configs = configs
+                .into_iter()
+                .map(|(config_name, value)| PgConfig {
+                    name: config_name,
+                    value: value.parse().expect("parsing a config value can not fail"),
+                })
+                .collect();
+            (name, configs)
+        })
+        .collect())
+}
+
+pub async fn reconcile_trunk_configmap(
+    client: Client,
+    namespace: &str,
+    cfg: &Config,
+) -> Result<(), TrunkError> {
+    let registry = TrunkRegistry::from_config(cfg)?;
+    // The libraries and the projects are fetched independently, each key is only updated when its
+    // request succeeded, the others keep the metadata of the last successful request
+    let (libraries, projects) = tokio::join!(
+        requires_load_list_from_trunk(&registry),
+        trunk_projects_from_trunk(&registry)
+    );
+
+    let cm_api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+    let mut data = cm_api
+        .get_opt(TRUNK_CONFIGMAP_NAME)
+        .await?
+        .and_then(|cm| cm.data)
+        .unwrap_or_default();
+
+    let libraries = match libraries {
+        Ok(libraries) => Some(libraries),
         Err(e) => {
-            error!("Failed to update extensions libraries list from trunk: {:?}", e);
-            let cm_api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
-            match cm_api.get(TRUNK_CONFIGMAP_NAME).await {
-                Ok(_) => {
-                    // If the configmap is already present, we can just log the error and continue
-                    return Ok(());
-                }
-                Err(_e) => {
-                    // If the configmap is not already present, then we should requeue the request
-                    // as an unexpected error.
-                    return Err(Action::requeue(Duration::from_secs(300)));
+            error!(
+                "Failed to update the libraries that require load from trunk: {:?}",
+                e
+            );
+            if data.contains_key("libraries") {
+                None
+            } else {
+                // If the libraries have never been loaded, fall back to the local list of libraries
+                match requires_load_list_from_local(client.clone(), cfg).await {
+                    Ok(Some(libraries)) => {
+                        warn!("Using the local list of libraries in namespace {}", namespace);
+                        Some(libraries)
+                    }
+                    Ok(None) => return Err(e),
+                    Err(local_error) => {
+                        error!("Failed to load the local list of libraries: {:?}", local_error);
+                        return Err(local_error);
+                    }
                 }
             }
         }
     };
-
-    let mut data = BTreeMap::new();
-    data.insert("libraries".to_string(), libraries.join(","));
+    let projects = match projects {
+        Ok(projects) => Some(projects),
+        Err(e) => {
+            error!(
+                "Failed to update the dependencies and configurations from trunk: {:?}",
+                e
+            );
+            None
+        }
+    };
+    if libraries.is_none() && projects.is_none() {
+        // Nothing to update, the configmap keeps the last metadata
+        return Ok(());
+    }
+    update_trunk_metadata(&mut data, libraries, projects.as_deref());
 
     match apply_configmap(client, namespace, TRUNK_CONFIGMAP_NAME, data).await {
         Ok(_) => Ok(()),
         Err(e) => {
             error!("Failed to update trunk configmap: {:?}", e);
-            Err(Action::requeue(Duration::from_secs(300)))
+            Err(TrunkError::ConfigMapApplyError)
         }
     }
 }
 
-async fn requires_load_list_from_trunk() -> Result<Vec<String>, TrunkError> {
-    let domain =
-        env::var("TRUNK_REGISTRY_DOMAIN").unwrap_or_else(|_| DEFAULT_TRUNK_REGISTRY_DOMAIN.to_string());
-    let url = format!("https://{}/extensions/libraries", domain);
+// Write the metadata that could be fetched into the data of the trunk configmap
+fn update_trunk_metadata(
+    data: &mut BTreeMap<String, String>,
+    libraries: Option<Vec<String>>,
+    projects: Option<&[TrunkProject]>,
+) {
+    if let Some(libraries) = libraries {
+        data.insert("libraries".to_string(), libraries.join(","));
+    }
+    if let Some(projects) = projects {
+        data.insert(
+            "dependencies".to_string(),
+            serde_json::to_string(&dependencies_from_projects(projects))
+                .expect("a map of strings can always be serialized"),
+        );
+        data.insert(
+            "configurations".to_string(),
+            serde_json::to_string(&configurations_from_projects(projects))
+                .expect("a map of strings can always be serialized"),
+        );
+    }
+}
 
-    let response = reqwest::get(&url).await?;
+/// The Trunk registry, which is the public registry unless the operator is configured to use a
+/// self-hosted mirror
+pub struct TrunkRegistry {
+    url: String,
+    client: reqwest::Client,
+}
 
-    if response.status().is_success() {
-        let response_body = response.text().await?;
-        let libraries: Vec<String> = serde_json::from_str(&response_body)?;
-        Ok(libraries)
-    } else {
-        error!(
-            "Failed to update extensions libraries list from trunk: {}",
-            response.status()
-        );
-        Err(TrunkError::ConfigMapApplyError)
+impl TrunkRegistry {
+    pub fn from_config(cfg: &Config) -> Result<Self, TrunkError> {
+        let mut builder = reqwest::Client::builder().default_headers(registry_headers(cfg)?);
+        if let Some(ca_bundle) = &cfg.trunk_registry_ca_bundle {
+            for certificate in parse_ca_bundle(&fs::read(ca_bundle)?)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        Ok(TrunkRegistry {
+            url: registry_url(cfg),
+            client: builder.build()?,
+        })
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, TrunkError> {
+        let response = self.client.get(format!("{}{}", self.url, path)).send().await?;
+        if response.status().is_success() {
+            let response_body = response.text().await?;
+            Ok(serde_json::from_str(&response_body)?)
+        } else {
+            error!(
+                "Failed to get {} from trunk registry {}: {}",
+                path,
+                self.url,
+                response.status()
+            );
+            Err(TrunkError::ConfigMapApplyError)
+        }
+    }
+
+    /// Files installed by a version of a trunk project, from the manifest of its archive. Returns
+    /// None for archives whose manifest does not list their files.
+    pub async fn installed_files(
+        &self,
+        name: &str,
+        version: &str,
+    ) -> Result<Option<TrunkInstalledFiles>, TrunkError> {
+        let path = format!("/extensions/{}/{}/download", name, version);
+        let response = self.client.get(format!("{}{}", self.url, path)).send().await?;
+        if !response.status().is_success() {
+            error!(
+                "Failed to get {} from trunk registry {}: {}",
+                path,
+                self.url,
+                response.status()
+            );
+            return Err(TrunkError::ConfigMapApplyError);
+        }
+        installed_files_from_archive(&response.bytes().await?)
+    }
+}
+
+// The manifest of a trunk archive, its files are listed from manifest version 2
+#[derive(Deserialize)]
+struct TrunkManifest {
+    files: Option<BTreeMap<String, TrunkManifestFile>>,
+}
+
+#[derive(Deserialize)]
+struct TrunkManifestFile {
+    #[serde(rename = "type")]
+    file_type: String,
+}
+
+/// Files installed by trunk, relative to the pkglibdir and the sharedir of Postgres
+#[derive(Debug, Default, PartialEq)]
+pub struct TrunkInstalledFiles {
+    pub pkglibdir: Vec<String>,
+    pub sharedir: Vec<String>,
+}
+
+// Read the manifest.json of a .tar.gz archive, shared objects and bitcode are installed to the
+// pkglibdir and every other file to the sharedir
+fn installed_files_from_archive(archive: &[u8]) -> Result<Option<TrunkInstalledFiles>, TrunkError> {
+    let archive_error = |e: std::io::Error| TrunkError::ArchiveError(e.to_string());
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(archive));
+    for entry in archive.entries().map_err(archive_error)? {
+        let mut entry = entry.map_err(archive_error)?;
+        let path = entry.path().map_err(archive_error)?;
+        if path.to_string_lossy().trim_start_matches("./") != "manifest.json" {
+            continue;
+        }
+        let mut manifest = String::new();
+        entry.read_to_string(&mut manifest).map_err(archive_error)?;
+        let manifest: TrunkManifest = serde_json::from_str(&manifest)?;
+        return Ok(manifest.files.map(|files| {
+            let mut installed_files = TrunkInstalledFiles::default();
+            for (path, file) in files {
+                match file.file_type.as_str() {
+                    "SharedObject" | "Bitcode" => installed_files.pkglibdir.push(path),
+                    _ => installed_files.sharedir.push(path),
+                }
+            }
+            installed_files
+        }));
+    }
+    Err(TrunkError::ArchiveError(
+        "no manifest.json in the archive".to_string(),
+    ))
+}
+
+/// The URL of the registry, like https://registry.pgtrunk.io, also used by trunk install in pods
+pub fn registry_url(cfg: &Config) -> String {
+    format!("{}://{}", cfg.trunk_registry_scheme, cfg.trunk_registry_domain)
+}
+
+fn registry_headers(cfg: &Config) -> Result<HeaderMap, TrunkError> {
+    let mut headers = HeaderMap::new();
+    for line in cfg.trunk_registry_headers.iter().flat_map(|h| h.lines()) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (name, value) = line.split_once(':').ok_or_else(|| {
+            TrunkError::InvalidRegistryConfiguration("headers must be formatted as Name: value".to_string())
+        })?;
+        let name = HeaderName::from_bytes(name.trim().as_bytes())
+            .map_err(|e| TrunkError::InvalidRegistryConfiguration(e.to_string()))?;
+        let mut value = HeaderValue::from_str(value.trim())
+            .map_err(|e| TrunkError::InvalidRegistryConfiguration(e.to_string()))?;
+        // Keep credentials out of debug logs
+        value.set_sensitive(true);
+        headers.insert(name, value);
     }
+    Ok(headers)
+}
+
+// reqwest reads one certificate at a time, a bundle can have several
+fn parse_ca_bundle(pem: &[u8]) -> Result<Vec<Certificate>, TrunkError> {
+    const END_CERTIFICATE: &str = "-----END CERTIFICATE-----";
+    let pem = String::from_utf8_lossy(pem);
+    let certificates = pem
+        .split_inclusive(END_CERTIFICATE)
+        .filter(|block| block.contains("-----BEGIN CERTIFICATE-----"))
+        .map(|block| Certificate::from_pem(block.as_bytes()))
+        .collect::<Result<Vec<_>, _>>()?;
+    if certificates.is_empty() {
+        return Err(TrunkError::InvalidRegistryConfiguration(
+            "no certificate found in the CA bundle".to_string(),
+        ));
+    }
+    Ok(certificates)
+}
+
+async fn requires_load_list_from_trunk(registry: &TrunkRegistry) -> Result<Vec<String>, TrunkError> {
+    registry.get("/extensions/libraries").await
+}
+
+// The local list is either a JSON array, like the response of trunk, or comma separated
+fn parse_libraries(libraries: &str) -> Vec<String> {
+    match serde_json::from_str::<Vec<String>>(libraries) {
+        Ok(libraries) => libraries,
+        Err(_) => libraries
+            .split([',', '\n'])
+            .map(|library| library.trim().to_string())
+            .filter(|library| !library.is_empty())
+            .collect(),
+    }
+}
+
+fn requires_load_list_from_file(path: &str) -> Result<Vec<String>, TrunkError> {
+    Ok(parse_libraries(&fs::read_to_string(path)?))
+}
+
+// Returns None when no local list is configured
+async fn requires_load_list_from_local(
+    client: Client,
+    cfg: &Config,
+) -> Result<Option<Vec<String>>, TrunkError> {
+    if let Some(path) = &cfg.trunk_libraries_file {
+        return Ok(Some(requires_load_list_from_file(path)?));
+    }
+    if let Some(configmap) = &cfg.trunk_libraries_configmap {
+        let (namespace, name) = configmap.split_once('/').ok_or_else(|| {
+            TrunkError::InvalidRegistryConfiguration(
+                "the ConfigMap must be given as namespace/name".to_string(),
+            )
+        })?;
+        let cm_api: Api<ConfigMap> = Api::namespaced(client, namespace);
+        let cm = cm_api.get(name).await?;
+        let libraries = cm
+            .data
+            .and_then(|mut data| data.remove("libraries"))
+            .ok_or_else(|| {
+                TrunkError::InvalidRegistryConfiguration(format!("no libraries in ConfigMap {}", configmap))
+            })?;
+        return Ok(Some(parse_libraries(&libraries)));
+    }
+    Ok(None)
+}
+
+#[derive(Deserialize)]
+struct TrunkProject {
+    #[serde(default)]
+    version: String,
+    // Major versions of Postgres this version of the project was built for
+    postgres_versions: Option<Vec<u32>>,
+    #[serde(default)]
+    extensions: Vec<TrunkProjectExtension>,
+}
+
+#[derive(Deserialize)]
+struct TrunkProjectExtension {
+    extension_name: String,
+    dependencies_extension_names: Option<Vec<String>>,
+    configurations: Option<Vec<TrunkProjectConfiguration>>,
+}
+
+#[derive(Deserialize)]
+struct TrunkProjectConfiguration {
+    configuration_name: String,
+    recommended_default_value: Option<String>,
+}
+
+// Every version of a project is listed, the dependencies of all versions are merged
+fn dependencies_from_projects(projects: &[TrunkProject]) -> BTreeMap<String, Vec<String>> {
+    let mut dependencies_map: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for extension in projects.iter().flat_map(|project| &project.extensions) {
+        let dependencies = extension.dependencies_extension_names.clone().unwrap_or_default();
+        if dependencies.is_empty() {
+            continue;
+        }
+        let entry = dependencies_map
+            .entry(extension.extension_name.clone())
+            .or_default();
+        for dependency in dependencies {
+            if !entry.contains(&dependency) {
+                entry.push(dependency);
+            }
+        }
+    }
+    dependencies_map
+}
+
+// Only configurations with a recommended default value are set, when a configuration is listed by
+// several versions of a project, the latest version wins
+fn configurations_from_projects(projects: &[TrunkProject]) -> BTreeMap<String, BTreeMap<String, String>> {
+    let mut configs_map: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+    for extension in projects.iter().flat_map(|project| &project.extensions) {
+        for config in extension.configurations.iter().flatten() {
+            if let Some(value) = &config.recommended_default_value {
+                configs_map
+                    .entry(extension.extension_name.clone())
+                    .or_default()
+                    .insert(config.configuration_name.clone(), value.clone());
+            }
+        }
+    }
+    configs_map
+}
+
+async fn trunk_projects_from_trunk(registry: &TrunkRegistry) -> Result<Vec<TrunkProject>, TrunkError> {
+    registry.get("/api/v1/trunk-projects").await
+}
+
+// Versions of a project that can be installed on a major version of Postgres. Versions that don't
+// list the Postgres versions they were built for are not refused.
+fn compatible_versions_from_projects(projects: &[TrunkProject], postgres_major_version: u32) -> Vec<String> {
+    projects
+        .iter()
+        .filter(|project| {
+            project
+                .postgres_versions
+                .as_ref()
+                .is_none_or(|versions| versions.contains(&postgres_major_version))
+        })
+        .map(|project| project.version.clone())
+        .collect()
+}
+
+/// Versions of a trunk project that can be installed on a major version of Postgres
+pub async fn compatible_versions(
+    registry: &TrunkRegistry,
+    name: &str,
+    postgres_major_version: u32,
+) -> Result<Vec<String>, TrunkError> {
+    let projects: Vec<TrunkProject> = registry.get(&format!("/api/v1/trunk-projects/{}", name)).await?;
+    Ok(compatible_versions_from_projects(
+        &projects,
+        postgres_major_version,
+    ))
 }
 
 // Define error type
@@ -137,4 +603,269 @@ pub enum TrunkError {
     ParsingIssue(#[from] serde_json::Error),
     #[error("Failed to apply trunk configmap")]
     ConfigMapApplyError,
+    #[error("Invalid trunk registry configuration: {0}")]
+    InvalidRegistryConfiguration(String),
+    #[error("Failed to read local trunk metadata: {0}")]
+    LocalMetadata(#[from] std::io::Error),
+    #[error("Failed to get local trunk metadata: {0}")]
+    KubeError(#[from] kube::Error),
+    #[error("Failed to read trunk archive: {0}")]
+    ArchiveError(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::{
+        matchers::{header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    fn mirror_config(server: &MockServer) -> Config {
+        Config {
+            trunk_registry_scheme: "http".to_string(),
+            trunk_registry_domain: server.address().to_string(),
+            trunk_registry_headers: Some("Authorization: Bearer secret\nX-Mirror: tembo".to_string()),
+            ..Config::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_requires_load_list_from_mirror() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/extensions/libraries"))
+            .and(header("Authorization", "Bearer secret"))
+            .and(header("X-Mirror", "tembo"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(vec!["pg_cron", "pg_stat_statements"]))
+            .mount(&server)
+            .await;
+        let registry = TrunkRegistry::from_config(&mirror_config(&server)).unwrap();
+
+        let libraries = requires_load_list_from_trunk(&registry).await.unwrap();
+        assert_eq!(libraries, vec!["pg_cron", "pg_stat_statements"]);
+    }
+
+    #[tokio::test]
+    async fn test_compatible_versions_from_mirror() {
+        let server = MockServer::start().await;
+        let response_body = r#"[
+            {"name": "pgmq", "version": "0.33.3", "postgres_versions": [15], "extensions": []},
+            {"name": "pgmq", "version": "1.1.1", "postgres_versions": [14, 15, 16], "extensions": []},
+            {"name": "pgmq", "version": "0.10.0", "extensions": []}
+        ]"#;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/trunk-projects/pgmq"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(response_body))
+            .mount(&server)
+            .await;
+        let registry = TrunkRegistry::from_config(&mirror_config(&server)).unwrap();
+
+        assert_eq!(compatible_versions(&registry, "pgmq", 15).await.unwrap(), vec![
+            "0.33.3", "1.1.1", "0.10.0"
+        ]);
+        assert_eq!(compatible_versions(&registry, "pgmq", 16).await.unwrap(), vec![
+            "1.1.1", "0.10.0"
+        ]);
+        assert_eq!(compatible_versions(&registry, "pgmq", 13).await.unwrap(), vec![
+            "0.10.0"
+        ]);
+        // Projects that are not in the registry can't be checked
+        assert!(compatible_versions(&registry, "pg_cron", 15).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_installed_files_from_mirror() {
+        let manifest = r#"{
+            "extension_name": "pg_partman",
+            "extension_version": "4.7.3",
+            "manifest_version": 2,
+            "files": {
+                "pg_partman_bgw.so": {"type": "SharedObject"},
+                "bitcode/pg_partman_bgw.index.bc": {"type": "Bitcode"},
+                "extension/pg_partman.control": {"type": "ControlFile"},
+                "extension/pg_partman--4.7.3.sql": {"type": "SqlFile"}
+            }
+        }"#;
+        let mut archive = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        let mut entry = tar::Header::new_gnu();
+        entry.set_size(manifest.len() as u64);
+        entry.set_mode(0o644);
+        entry.set_cksum();
+        archive
+            .append_data(&mut entry, "manifest.json", manifest.as_bytes())
+            .unwrap();
+        let archive = archive.into_inner().unwrap().finish().unwrap();
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/extensions/pg_partman/4.7.3/download"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(archive))
+            .mount(&server)
+            .await;
+        let registry = TrunkRegistry::from_config(&mirror_config(&server)).unwrap();
+
+        let installed_files = registry.installed_files("pg_partman", "4.7.3").await.unwrap();
+        assert_eq!(
+            installed_files,
+            Some(TrunkInstalledFiles {
+                pkglibdir: vec![
+                    "bitcode/pg_partman_bgw.index.bc".to_string(),
+                    "pg_partman_bgw.so".to_string(),
+                ],
+                sharedir: vec![
+                    "extension/pg_partman--4.7.3.sql".to_string(),
+                    "extension/pg_partman.control".to_string(),
+                ],
+            })
+        );
+        assert!(registry.installed_files("pg_partman", "4.7.2").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_trunk_projects_from_unavailable_mirror() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/trunk-projects"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+        let registry = TrunkRegistry::from_config(&mirror_config(&server)).unwrap();
+
+        assert!(trunk_projects_from_trunk(&registry).await.is_err());
+    }
+
+    #[test]
+    fn test_registry_configuration() {
+        let cfg = Config {
+            trunk_registry_scheme: "http".to_string(),
+            trunk_registry_domain: "trunk.internal:8080".to_string(),
+            trunk_registry_headers: Some("Authorization Bearer secret".to_string()),
+            ..Config::default()
+        };
+        assert_eq!(registry_url(&cfg), "http://trunk.internal:8080");
+        assert!(registry_headers(&cfg).is_err());
+        assert!(parse_ca_bundle(b"not a certificate").is_err());
+    }
+
+    #[test]
+    fn test_requires_load_list_from_file() {
+        let path = std::env::temp_dir().join("test_requires_load_list_from_file.json");
+        fs::write(&path, r#"["pg_cron", "pg_partman_bgw"]"#).unwrap();
+        let libraries = requires_load_list_from_file(path.to_str().unwrap()).unwrap();
+        assert_eq!(libraries, vec!["pg_cron", "pg_partman_bgw"]);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(parse_libraries("pg_cron, pg_partman_bgw\npgml\n"), vec![
+            "pg_cron",
+            "pg_partman_bgw",
+            "pgml"
+        ]);
+    }
+
+    #[test]
+    fn test_dependencies_from_projects() {
+        let response_body = r#"[
+            {
+                "name": "vectorize",
+                "extensions": [
+                    {"extension_name": "vectorize", "version": "0.9.0", "dependencies_extension_names": ["pgmq", "pg_cron"]},
+                    {"extension_name": "vectorize", "version": "0.10.0", "dependencies_extension_names": ["pgmq", "pg_cron", "vector"]}
+                ]
+            },
+            {
+                "name": "pgmq",
+                "extensions": [
+                    {"extension_name": "pgmq", "version": "1.1.1", "dependencies_extension_names": null}
+                ]
+            },
+            {
+                "name": "postgis",
+                "extensions": [
+                    {"extension_name": "postgis_raster", "version": "3.4.0", "dependencies_extension_names": ["postgis"]}
+                ]
+            }
+        ]"#;
+        let projects: Vec<TrunkProject> = serde_json::from_str(response_body).unwrap();
+        let dependencies = dependencies_from_projects(&projects);
+        assert_eq!(dependencies.len(), 2);
+        assert_eq!(dependencies["vectorize"], vec!["pgmq", "pg_cron", "vector"]);
+        assert_eq!(dependencies["postgis_raster"], vec!["postgis"]);
+        assert!(!dependencies.contains_key("pgmq"));
+    }
+
+    #[test]
+    fn test_configurations_from_projects() {
+        let response_body = r#"[
+            {
+                "name": "pg_partman",
+                "extensions": [
+                    {
+                        "extension_name": "pg_partman",
+                        "version": "4.7.3",
+                        "configurations": [
+                            {"is_required": false, "configuration_name": "pg_partman_bgw.interval", "recommended_default_value": "3600"}
+                        ]
+                    },
+                    {
+                        "extension_name": "pg_partman",
+                        "version": "4.7.4",
+                        "configurations": [
+                            {"is_required": false, "configuration_name": "pg_partman_bgw.interval", "recommended_default_value": "60"},
+                            {"is_required": true, "configuration_name": "pg_partman_bgw.dbname", "recommended_default_value": null}
+                        ]
+                    }
+                ]
+            },
+            {
+                "name": "pgmq",
+                "extensions": [
+                    {"extension_name": "pgmq", "version": "1.1.1", "configurations": null}
+                ]
+            }
+        ]"#;
+        let projects: Vec<TrunkProject> = serde_json::from_str(response_body).unwrap();
+        let configs = configurations_from_projects(&projects);
+        assert_eq!(configs.len(), 1);
+        assert_eq!(
+            configs["pg_partman"],
+            BTreeMap::from([("pg_partman_bgw.interval".to_string(), "60".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_update_trunk_metadata() {
+        let response_body = r#"[
+            {
+                "name": "vectorize",
+                "extensions": [
+                    {"extension_name": "vectorize", "version": "0.10.0", "dependencies_extension_names": ["pgmq"]}
+                ]
+            }
+        ]"#;
+        let projects: Vec<TrunkProject> = serde_json::from_str(response_body).unwrap();
+        let mut data = BTreeMap::from([
+            ("libraries".to_string(), "pg_cron".to_string()),
+            ("dependencies".to_string(), "{}".to_string()),
+            ("configurations".to_string(), "{}".to_string()),
+        ]);
+
+        // The projects could not be fetched, only the libraries are updated
+        update_trunk_metadata(
+            &mut data,
+            Some(vec!["pg_cron".to_string(), "pg_partman_bgw".to_string()]),
+            None,
+        );
+        assert_eq!(data["libraries"], "pg_cron,pg_partman_bgw");
+        assert_eq!(data["dependencies"], "{}");
+
+        // The libraries could not be fetched, only the projects are updated
+        update_trunk_metadata(&mut data, None, Some(&projects));
+        assert_eq!(data["libraries"], "pg_cron,pg_partman_bgw");
+        assert_eq!(data["dependencies"], r#"{"vectorize":["pgmq"]}"#);
+        assert_eq!(data["configurations"], "{}");
+    }
 }
@@ -0,0 +1,830 @@
+use crate::{
+    apis::{
+        coredb_types::{AzureCredentials, CoreDB, CoreDBSpec, GoogleCredentials},
+        postgres_parameters::{ConfigValue, PgConfig, DISALLOWED_CONFIGS},
+        v1beta1,
+    },
+    cloudnativepg::cnpg::{parse_storage_size, parse_target_time},
+    databases::check_setting,
+    extensions::database_queries::check_input,
+    ingress::VALID_IPV4_CIDR_BLOCK,
+};
+use actix_web::{post, web, HttpResponse, Responder};
+use kube::core::{
+    admission::{AdmissionRequest, AdmissionResponse, AdmissionReview},
+    conversion::{ConversionRequest, ConversionResponse, ConversionReview},
+    DynamicObject, Status,
+};
+use regex::Regex;
+use std::collections::BTreeSet;
+use tracing::*;
+
+// Validating admission webhook for CoreDB resources. This runs the same checks the
+// controller would otherwise only find out about during reconciliation, so that an
+// invalid spec is rejected by the API server instead of looping on requeues.
+#[post("/validate")]
+pub async fn validate(body: web::Json<AdmissionReview<DynamicObject>>) -> impl Responder {
+    let req: AdmissionRequest<DynamicObject> = match body.into_inner().try_into() {
+        Ok(req) => req,
+        Err(err) => {
+            error!("Invalid AdmissionReview received: {}", err);
+            return HttpResponse::BadRequest()
+                .json(AdmissionResponse::invalid(err.to_string()).into_review());
+        }
+    };
+    HttpResponse::Ok().json(admission_response(&req).into_review())
+}
+
+pub fn admission_response(req: &AdmissionRequest<DynamicObject>) -> AdmissionResponse {
+    let res = AdmissionResponse::from(req);
+    // DELETE requests do not carry an object, there is nothing to validate
+    let obj = match &req.object {
+        Some(obj) => obj,
+        None => return res,
+    };
+    // The finalizer and annotations of a CoreDB are updated by the operator, which must not be
+    // blocked by a spec that was accepted before the current rules, or while it is deleted
+    if obj.metadata.deletion_timestamp.is_some() {
+        return res;
+    }
+    let old_obj = req.old_object.as_ref();
+    if old_obj.is_some_and(|old_obj| old_obj.data.get("spec") == obj.data.get("spec")) {
+        return res;
+    }
+    let cdb: CoreDB = match serde_json::to_value(obj).and_then(serde_json::from_value) {
+        Ok(cdb) => cdb,
+        Err(err) => return res.deny(format!("Failed to parse CoreDB: {}", err)),
+    };
+    let mut errors = validate_coredb_spec(&cdb.spec);
+    // On UPDATE, only the errors that the change introduces are reported
+    if let Some(old_cdb) = old_obj
+        .and_then(|old_obj| serde_json::to_value(old_obj).ok())
+        .and_then(|old_obj| serde_json::from_value::<CoreDB>(old_obj).ok())
+    {
+        let old_errors = validate_coredb_spec(&old_cdb.spec);
+        errors.retain(|error| !old_errors.contains(error));
+    }
+    if errors.is_empty() {
+        res
+    } else {
+        warn!(
+            "Rejecting CoreDB {} in namespace {}: {}",
+            req.name,
+            req.namespace.clone().unwrap_or_default(),
+            errors.join("; ")
+        );
+        res.deny(errors.join("; "))
+    }
+}
+
+// Conversion webhook for CoreDB resources, called by the API server whenever an object
+// is requested in a different version than the one it is stored in.
+#[post("/convert")]
+pub async fn convert(body: web::Json<ConversionReview>) -> impl Responder {
+    let req: ConversionRequest = match body.into_inner().try_into() {
+        Ok(req) => req,
+        Err(err) => {
+            error!("Invalid ConversionReview received: {}", err);
+            return HttpResponse::BadRequest().json(
+                ConversionResponse::invalid(Status::failure(&err.to_string(), "InvalidRequest"))
+                    .into_review(),
+            );
+        }
+    };
+    HttpResponse::Ok().json(conversion_response(req).into_review())
+}
+
+pub fn conversion_response(req: ConversionRequest) -> ConversionResponse {
+    let desired_api_version = req.desired_api_version.clone();
+    let mut converted_objects = Vec::with_capacity(req.objects.len());
+    for obj in req.objects.iter() {
+        match convert_coredb(obj.clone(), &desired_api_version) {
+            Ok(converted) => converted_objects.push(converted),
+            Err(err) => {
+                error!("Failed to convert CoreDB to {}: {}", desired_api_version, err);
+                return ConversionResponse::for_request(req)
+                    .failure(Status::failure(&err, "ConversionFailed"));
+            }
+        }
+    }
+    ConversionResponse::for_request(req).success(converted_objects)
+}
+
+// Converts a single CoreDB object between the v1alpha1 and v1beta1 API versions
+pub fn convert_coredb(
+    obj: serde_json::Value,
+    desired_api_version: &str,
+) -> Result<serde_json::Value, String> {
+    let api_version = obj
+        .get("apiVersion")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| "object is missing apiVersion".to_string())?
+        .to_string();
+    if api_version == desired_api_version {
+        return Ok(obj);
+    }
+    let converted = match (api_version.as_str(), desired_api_version) {
+        ("coredb.io/v1alpha1", "coredb.io/v1beta1") => {
+            let cdb: CoreDB = serde_json::from_value(obj).map_err(|e| e.to_string())?;
+            serde_json::to_value(v1beta1::CoreDB::from(cdb))
+        }
+        ("coredb.io/v1beta1", "coredb.io/v1alpha1") => {
+            let cdb: v1beta1::CoreDB = serde_json::from_value(obj).map_err(|e| e.to_string())?;
+            serde_json::to_value(CoreDB::from(cdb))
+        }
+        (from, to) => return Err(format!("unsupported conversion from {} to {}", from, to)),
+    };
+    let mut converted = converted.map_err(|e| e.to_string())?;
+    converted["apiVersion"] = serde_json::Value::String(desired_api_version.to_string());
+    Ok(converted)
+}
+
+// Databases that exist in every instance
+const RESERVED_DATABASES: [&str; 3] = ["postgres", "template0", "template1"];
+// Roles created by Postgres, CloudNativePG or the operator
+const RESERVED_ROLES: [&str; 5] = [
+    "postgres",
+    "streaming_replica",
+    "cnpg_pooler_pgbouncer",
+    "readonly",
+    "postgres_exporter",
+];
+
+// Returns one message per invalid field, an empty list means the spec is valid
+pub fn validate_coredb_spec(spec: &CoreDBSpec) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    if let Some(ip_allow_list) = &spec.ip_allow_list {
+        let cidr_regex =
+            Regex::new(VALID_IPV4_CIDR_BLOCK).expect("Failed to compile regex for IPv4 CIDR block");
+        for (i, ip) in ip_allow_list.iter().enumerate() {
+            if !cidr_regex.is_match(ip) {
+                errors.push(format!(
+                    "spec.ipAllowList[{}]: '{}' is not a valid IPv4 address or CIDR block",
+                    i, ip
+                ));
+            }
+        }
+    }
+
+    if parse_storage_size(&spec.storage).is_err() {
+        errors.push(format!(
+            "spec.storage: '{}' is not a valid quantity",
+            spec.storage.0
+        ));
+    }
+
+    if let Some(wal_storage) = &spec.wal_storage {
+        if parse_storage_size(&wal_storage.size).is_err() {
+            errors.push(format!(
+                "spec.walStorage.size: '{}' is not a valid quantity",
+                wal_storage.size.0
+            ));
+        }
+    }
+
+    if let Some(high_availability) = &spec.high_availability {
+        let sync_replicas = high_availability.sync_replicas;
+        if sync_replicas < 0 {
+            errors.push(format!(
+                "spec.highAvailability.syncReplicas: {} can not be negative",
+                sync_replicas
+            ));
+        } else if sync_replicas >= spec.replicas {
+            errors.push(format!(
+                "spec.highAvailability.syncReplicas: {} synchronous replicas need at least {} replicas, but replicas is {}",
+                sync_replicas,
+                sync_replicas + 1,
+                spec.replicas
+            ));
+        }
+        if high_availability.zone_election_constraint && sync_replicas < 1 {
+            errors.push(
+                "spec.highAvailability.zoneElectionConstraint: requires syncReplicas to be at least 1"
+                    .to_string(),
+            );
+        }
+        if high_availability.topology_key.is_empty() {
+            errors.push("spec.highAvailability.topologyKey: can not be empty".to_string());
+        }
+    }
+
+    let pooler = &spec.connectionPooler;
+    if pooler.instances < 1 {
+        errors.push(format!(
+            "spec.connectionPooler.instances: {} must be at least 1",
+            pooler.instances
+        ));
+    }
+    if let Some(autoscaling) = &pooler.autoscaling {
+        if autoscaling.minReplicas < 1 || autoscaling.minReplicas > autoscaling.maxReplicas {
+            errors.push(format!(
+                "spec.connectionPooler.autoscaling: minReplicas {} must be at least 1 and at most maxReplicas {}",
+                autoscaling.minReplicas, autoscaling.maxReplicas
+            ));
+        }
+        if autoscaling.targetCPUUtilizationPercentage < 1 {
+            errors.push(format!(
+                "spec.connectionPooler.autoscaling.targetCPUUtilizationPercentage: {} must be at least 1",
+                autoscaling.targetCPUUtilizationPercentage
+            ));
+        }
+    }
+    if let Some(min_available) = pooler.minAvailable {
+        // A PodDisruptionBudget that never allows a disruption blocks node drains
+        let lowest_instances = pooler
+            .autoscaling
+            .as_ref()
+            .map_or(pooler.instances, |autoscaling| autoscaling.minReplicas);
+        if min_available < 0 || min_available >= lowest_instances {
+            errors.push(format!(
+                "spec.connectionPooler.minAvailable: {} must be at least 0 and less than the {} pooler instances",
+                min_available, lowest_instances
+            ));
+        }
+    }
+
+    let mut database_names = BTreeSet::new();
+    for (i, database) in spec.databases.iter().enumerate() {
+        if !check_input(&database.name) {
+            errors.push(format!(
+                "spec.databases[{}].name: '{}' is not a valid database name",
+                i, database.name
+            ));
+        } else if RESERVED_DATABASES.contains(&database.name.as_str()) {
+            errors.push(format!(
+                "spec.databases[{}].name: '{}' is created by Postgres and can not be managed",
+                i, database.name
+            ));
+        } else if !database_names.insert(database.name.as_str()) {
+            errors.push(format!(
+                "spec.databases[{}].name: '{}' is listed more than once",
+                i, database.name
+            ));
+        }
+        if !check_input(&database.owner) {
+            errors.push(format!(
+                "spec.databases[{}].owner: '{}' is not a valid role name",
+                i, database.owner
+            ));
+        }
+        for (field, setting) in [("encoding", &database.encoding), ("locale", &database.locale)] {
+            if let Some(setting) = setting {
+                if !check_setting(setting) {
+                    errors.push(format!(
+                        "spec.databases[{}].{}: '{}' is not a valid {}",
+                        i, field, setting, field
+                    ));
+                }
+            }
+        }
+    }
+
+    let mut role_names = BTreeSet::new();
+    for (i, role) in spec.roles.iter().enumerate() {
+        if !check_input(&role.name) {
+            errors.push(format!(
+                "spec.roles[{}].name: '{}' is not a valid role name",
+                i, role.name
+            ));
+        } else if RESERVED_ROLES.contains(&role.name.as_str()) {
+            errors.push(format!(
+                "spec.roles[{}].name: '{}' is managed by the operator and can not be set",
+                i, role.name
+            ));
+        } else if !role_names.insert(role.name.as_str()) {
+            errors.push(format!(
+                "spec.roles[{}].name: '{}' is listed more than once",
+                i, role.name
+            ));
+        }
+        if role.connection_limit < -1 {
+            errors.push(format!(
+                "spec.roles[{}].connectionLimit: {} must be at least -1",
+                i, role.connection_limit
+            ));
+        }
+    }
+
+    if let Some(jobs) = spec.backup.jobs.filter(|jobs| *jobs < 1) {
+        errors.push(format!("spec.backup.jobs: {} must be at least 1", jobs));
+    }
+    if let Some(max_parallel) = spec
+        .backup
+        .wal_max_parallel
+        .filter(|max_parallel| *max_parallel < 1)
+    {
+        errors.push(format!(
+            "spec.backup.walMaxParallel: {} must be at least 1",
+            max_parallel
+        ));
+    }
+
+    validate_object_store_credentials(
+        "spec.backup",
+        spec.backup.google_credentials.as_ref(),
+        spec.backup.azure_credentials.as_ref(),
+        &mut errors,
+    );
+
+    if let Some(restore) = &spec.restore {
+        if let Err(err) = parse_target_time(restore.recovery_target_time.as_deref()) {
+            errors.push(format!(
+                "spec.restore.recoveryTargetTime: '{}' is not a supported timestamp format: {}",
+                restore.recovery_target_time.clone().unwrap_or_default(),
+                err
+            ));
+        }
+        validate_object_store_credentials(
+            "spec.restore",
+            restore.google_credentials.as_ref(),
+            restore.azure_credentials.as_ref(),
+            &mut errors,
+        );
+    }
+
+    if let Some(stack_configs) = spec.stack.as_ref().and_then(|s| s.postgres_config.as_ref()) {
+        validate_pg_configs("spec.stack.postgres_config", stack_configs, &mut errors);
+    }
+    if let Some(runtime_configs) = &spec.runtime_config {
+        validate_pg_configs("spec.runtime_config", runtime_configs, &mut errors);
+    }
+    if let Some(override_configs) = &spec.override_configs {
+        validate_pg_configs("spec.override_configs", override_configs, &mut errors);
+    }
+
+    errors
+}
+
+// Settings managed by the operator are dropped from every layer of the configuration, and
+// multi-valued settings are split on commas when they are deserialized, so an empty entry comes
+// from a stray comma
+fn validate_pg_configs(path: &str, configs: &[PgConfig], errors: &mut Vec<String>) {
+    for (i, cfg) in configs.iter().enumerate() {
+        if DISALLOWED_CONFIGS.contains(&cfg.name.as_str()) {
+            errors.push(format!(
+                "{}[{}]: '{}' is managed by the operator and can not be set",
+                path, i, cfg.name
+            ));
+        }
+        if let ConfigValue::Multiple(values) = &cfg.value {
+            if values.contains("") {
+                errors.push(format!(
+                    "{}[{}]: '{}' is multi-valued and has an empty entry",
+                    path, i, cfg.name
+                ));
+            }
+        }
+    }
+}
+
+// The object store of a backup or a restore is in one provider, with one way to authenticate
+fn validate_object_store_credentials(
+    path: &str,
+    google: Option<&GoogleCredentials>,
+    azure: Option<&AzureCredentials>,
+    errors: &mut Vec<String>,
+) {
+    if google.is_some() && azure.is_some() {
+        errors.push(format!(
+            "{}: only one of googleCredentials and azureCredentials can be set",
+            path
+        ));
+    }
+    if let Some(google) = google {
+        let gke_environment = google.gke_environment.unwrap_or(false);
+        if google.application_credentials.is_some() == gke_environment {
+            errors.push(format!(
+                "{}.googleCredentials: set either applicationCredentials or gkeEnvironment",
+                path
+            ));
+        }
+    }
+    if let Some(azure) = azure {
+        let methods = [
+            azure.connection_string.is_some(),
+            azure.storage_key.is_some(),
+            azure.storage_sas_token.is_some(),
+            azure.inherit_from_azure_ad.unwrap_or(false),
+        ];
+        if methods.iter().filter(|method| **method).count() != 1 {
+            errors.push(format!(
+                "{}.azureCredentials: set one of connectionString, storageKey, storageSasToken or inheritFromAzureAD",
+                path
+            ));
+        } else if (azure.storage_key.is_some() || azure.storage_sas_token.is_some())
+            && azure.storage_account.is_none()
+        {
+            errors.push(format!(
+                "{}.azureCredentials: storageAccount is required with storageKey or storageSasToken",
+                path
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        apis::coredb_types::{
+            AzureCredentialsStorageKey, ConnectionPooler, Database, HighAvailability, PoolerAutoscaling,
+            Restore, Role, Stack, WalStorage,
+        },
+        stacks::{get_stack, types::StackType},
+    };
+    use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_valid_spec() {
+        let spec = CoreDBSpec {
+            ip_allow_list: Some(vec!["10.0.0.0/8".to_string(), "192.168.1.1".to_string()]),
+            restore: Some(Restore {
+                server_name: "test".to_string(),
+                recovery_target_time: Some("2023-09-26T21:15:42Z".to_string()),
+                ..Restore::default()
+            }),
+            override_configs: Some(vec![PgConfig {
+                name: "max_connections".to_string(),
+                value: ConfigValue::Single("200".to_string()),
+            }]),
+            storage: Quantity("10Gi".to_string()),
+            wal_storage: Some(WalStorage {
+                size: Quantity("1Gi".to_string()),
+                storage_class: None,
+            }),
+            replicas: 3,
+            connectionPooler: ConnectionPooler {
+                instances: 3,
+                minAvailable: Some(1),
+                autoscaling: Some(PoolerAutoscaling {
+                    minReplicas: 2,
+                    maxReplicas: 5,
+                    targetCPUUtilizationPercentage: 80,
+                }),
+                ..ConnectionPooler::default()
+            },
+            high_availability: Some(HighAvailability {
+                sync_replicas: 2,
+                zone_election_constraint: true,
+                ..HighAvailability::default()
+            }),
+            ..CoreDBSpec::default()
+        };
+        assert!(validate_coredb_spec(&spec).is_empty());
+    }
+
+    #[test]
+    fn test_validate_invalid_spec() {
+        let spec = CoreDBSpec {
+            ip_allow_list: Some(vec!["10.0.0.0/8".to_string(), "10.0.0.300/33".to_string()]),
+            restore: Some(Restore {
+                server_name: "test".to_string(),
+                recovery_target_time: Some("yesterday".to_string()),
+                ..Restore::default()
+            }),
+            // Configurations are deserialized like they are in an admission review
+            override_configs: serde_json::from_value(json!([
+                {"name": "archive_command", "value": "/bin/true"}
+            ]))
+            .unwrap(),
+            stack: Some(Stack {
+                name: "test".to_string(),
+                postgres_config: serde_json::from_value(json!([
+                    {"name": "shared_preload_libraries", "value": "pg_cron"},
+                    {"name": "wal_level", "value": "logical"}
+                ]))
+                .unwrap(),
+            }),
+            runtime_config: serde_json::from_value(json!([
+                {"name": "shared_preload_libraries", "value": "pg_partman_bgw,"}
+            ]))
+            .unwrap(),
+            storage: Quantity("10Gi".to_string()),
+            wal_storage: Some(WalStorage {
+                size: Quantity("ten gigs".to_string()),
+                storage_class: None,
+            }),
+            replicas: 2,
+            connectionPooler: ConnectionPooler {
+                instances: 2,
+                minAvailable: Some(2),
+                ..ConnectionPooler::default()
+            },
+            high_availability: Some(HighAvailability {
+                sync_replicas: 2,
+                topology_key: "".to_string(),
+                ..HighAvailability::default()
+            }),
+            ..CoreDBSpec::default()
+        };
+        let errors = validate_coredb_spec(&spec);
+        assert_eq!(errors.len(), 9);
+        assert!(errors[0].starts_with("spec.ipAllowList[1]"));
+        assert!(errors[1].starts_with("spec.walStorage.size: 'ten gigs'"));
+        assert_eq!(
+            errors[2],
+            "spec.highAvailability.syncReplicas: 2 synchronous replicas need at least 3 replicas, but replicas is 2"
+        );
+        assert!(errors[3].starts_with("spec.highAvailability.topologyKey"));
+        assert_eq!(
+            errors[4],
+            "spec.connectionPooler.minAvailable: 2 must be at least 0 and less than the 2 pooler instances"
+        );
+        assert!(errors[5].starts_with("spec.restore.recoveryTargetTime"));
+        assert!(errors[6].starts_with("spec.stack.postgres_config[1]: 'wal_level'"));
+        assert_eq!(
+            errors[7],
+            "spec.runtime_config[0]: 'shared_preload_libraries' is multi-valued and has an empty entry"
+        );
+        assert!(errors[8].starts_with("spec.override_configs[0]: 'archive_command'"));
+    }
+
+    #[test]
+    fn test_validate_object_store_credentials() {
+        let google = GoogleCredentials {
+            application_credentials: None,
+            gke_environment: Some(true),
+        };
+        let azure = AzureCredentials {
+            inherit_from_azure_ad: Some(true),
+            ..AzureCredentials::default()
+        };
+        let mut spec = CoreDBSpec {
+            storage: Quantity("10Gi".to_string()),
+            ..CoreDBSpec::default()
+        };
+        spec.backup.google_credentials = Some(google.clone());
+        spec.restore = Some(Restore {
+            server_name: "test".to_string(),
+            azure_credentials: Some(azure.clone()),
+            ..Restore::default()
+        });
+        assert_eq!(validate_coredb_spec(&spec), Vec::<String>::new());
+
+        spec.backup.azure_credentials = Some(azure);
+        spec.backup.google_credentials = Some(GoogleCredentials {
+            application_credentials: None,
+            gke_environment: None,
+        });
+        spec.restore = Some(Restore {
+            server_name: "test".to_string(),
+            azure_credentials: Some(AzureCredentials {
+                storage_key: Some(AzureCredentialsStorageKey {
+                    key: "key".to_string(),
+                    name: "azure-creds".to_string(),
+                }),
+                ..AzureCredentials::default()
+            }),
+            ..Restore::default()
+        });
+        let errors = validate_coredb_spec(&spec);
+        assert_eq!(errors, vec![
+            "spec.backup: only one of googleCredentials and azureCredentials can be set",
+            "spec.backup.googleCredentials: set either applicationCredentials or gkeEnvironment",
+            "spec.restore.azureCredentials: storageAccount is required with storageKey or storageSasToken",
+        ]);
+    }
+
+    #[test]
+    fn test_validate_backup_parallelism() {
+        let mut spec = CoreDBSpec {
+            storage: Quantity("10Gi".to_string()),
+            ..CoreDBSpec::default()
+        };
+        spec.backup.jobs = Some(1);
+        spec.backup.wal_max_parallel = Some(8);
+        assert_eq!(validate_coredb_spec(&spec), Vec::<String>::new());
+
+        spec.backup.jobs = Some(0);
+        spec.backup.wal_max_parallel = Some(-1);
+        assert_eq!(validate_coredb_spec(&spec), vec![
+            "spec.backup.jobs: 0 must be at least 1",
+            "spec.backup.walMaxParallel: -1 must be at least 1",
+        ]);
+    }
+
+    #[test]
+    fn test_validate_stack_configs() {
+        let stack_types = [
+            StackType::DataWarehouse,
+            StackType::Standard,
+            StackType::MessageQueue,
+            StackType::MachineLearning,
+            StackType::OLAP,
+            StackType::OLTP,
+            StackType::VectorDB,
+        ];
+        for stack_type in stack_types {
+            let stack = get_stack(stack_type);
+            let mut errors = Vec::new();
+            validate_pg_configs(
+                "spec.stack.postgres_config",
+                &stack.postgres_config.unwrap_or_default(),
+                &mut errors,
+            );
+            assert_eq!(errors, Vec::<String>::new(), "stack {}", stack.name);
+        }
+    }
+
+    #[test]
+    fn test_validate_databases_and_roles() {
+        let database = Database {
+            name: "app".to_string(),
+            owner: "app".to_string(),
+            encoding: Some("UTF8".to_string()),
+            locale: Some("en_US.UTF-8".to_string()),
+        };
+        let role = Role {
+            name: "app".to_string(),
+            login: true,
+            in_roles: vec!["pg_read_all_data".to_string()],
+            connection_limit: -1,
+            password_secret: Some("app-credentials".to_string()),
+        };
+        let spec = CoreDBSpec {
+            databases: vec![database.clone()],
+            roles: vec![role.clone()],
+            storage: Quantity("10Gi".to_string()),
+            ..CoreDBSpec::default()
+        };
+        assert!(validate_coredb_spec(&spec).is_empty());
+
+        let spec = CoreDBSpec {
+            databases: vec![database.clone(), database.clone(), Database {
+                name: "template1".to_string(),
+                locale: Some("C'; DROP DATABASE app; --".to_string()),
+                ..database
+            }],
+            roles: vec![
+                Role {
+                    name: "readonly".to_string(),
+                    ..role.clone()
+                },
+                Role {
+                    connection_limit: -2,
+                    ..role
+                },
+            ],
+            storage: Quantity("10Gi".to_string()),
+            ..CoreDBSpec::default()
+        };
+        let errors = validate_coredb_spec(&spec);
+        assert_eq!(errors, vec![
+            "spec.databases[1].name: 'app' is listed more than once",
+            "spec.databases[2].name: 'template1' is created by Postgres and can not be managed",
+            "spec.databases[2].locale: 'C'; DROP DATABASE app; --' is not a valid locale",
+            "spec.roles[0].name: 'readonly' is managed by the operator and can not be set",
+            "spec.roles[1].connectionLimit: -2 must be at least -1",
+        ]);
+    }
+
+    #[test]
+    fn test_conversion_response() {
+        let review: ConversionReview = serde_json::from_value(serde_json::json!({
+            "apiVersion": "apiextensions.k8s.io/v1",
+            "kind": "ConversionReview",
+            "request": {
+                "uid": "705ab4f5-6393-11e8-b7cc-42010a800002",
+                "desiredAPIVersion": "coredb.io/v1beta1",
+                "objects": [
+                    {
+                        "apiVersion": "coredb.io/v1alpha1",
+                        "kind": "CoreDB",
+                        "metadata": {"name": "test", "namespace": "default"},
+                        "spec": {"trunk_installs": [{"name": "pgmq", "version": "0.10.2"}]}
+                    },
+                    {
+                        "apiVersion": "coredb.io/v1beta1",
+                        "kind": "CoreDB",
+                        "metadata": {"name": "test2", "namespace": "default"},
+                        "spec": {"trunkInstalls": []}
+                    }
+                ]
+            }
+        }))
+        .unwrap();
+        let res = conversion_response(review.try_into().unwrap());
+        assert_eq!(res.uid, "705ab4f5-6393-11e8-b7cc-42010a800002");
+        assert_eq!(res.converted_objects.len(), 2);
+        let converted = &res.converted_objects[0];
+        assert_eq!(converted["apiVersion"], "coredb.io/v1beta1");
+        assert_eq!(converted["kind"], "CoreDB");
+        assert_eq!(converted["metadata"]["name"], "test");
+        assert_eq!(converted["spec"]["trunkInstalls"][0]["name"], "pgmq");
+        assert!(converted["spec"].get("trunk_installs").is_none());
+        assert_eq!(res.converted_objects[1]["metadata"]["name"], "test2");
+    }
+
+    #[test]
+    fn test_convert_coredb_unsupported_version() {
+        let obj = serde_json::json!({"apiVersion": "coredb.io/v2", "kind": "CoreDB"});
+        assert!(convert_coredb(obj, "coredb.io/v1alpha1").is_err());
+    }
+
+    #[test]
+    fn test_admission_response_denies_invalid_coredb() {
+        let review: AdmissionReview<DynamicObject> = serde_json::from_value(serde_json::json!({
+            "apiVersion": "admission.k8s.io/v1",
+            "kind": "AdmissionReview",
+            "request": {
+                "uid": "705ab4f5-6393-11e8-b7cc-42010a800002",
+                "kind": {"group": "coredb.io", "version": "v1alpha1", "kind": "CoreDB"},
+                "resource": {"group": "coredb.io", "version": "v1alpha1", "resource": "coredbs"},
+                "name": "test",
+                "namespace": "default",
+                "operation": "CREATE",
+                "userInfo": {},
+                "object": {
+                    "apiVersion": "coredb.io/v1alpha1",
+                    "kind": "CoreDB",
+                    "metadata": {"name": "test", "namespace": "default"},
+                    "spec": {"ipAllowList": ["not-an-ip"]}
+                },
+                "dryRun": false
+            }
+        }))
+        .unwrap();
+        let req: AdmissionRequest<DynamicObject> = review.try_into().unwrap();
+        let res = admission_response(&req);
+        assert!(!res.allowed);
+        assert_eq!(
+            res.result.message,
+            "spec.ipAllowList[0]: 'not-an-ip' is not a valid IPv4 address or CIDR block"
+        );
+    }
+
+    fn update_review(
+        old_spec: serde_json::Value,
+        spec: serde_json::Value,
+        deletion_timestamp: Option<&str>,
+    ) -> AdmissionRequest<DynamicObject> {
+        let mut metadata = serde_json::json!({"name": "test", "namespace": "default"});
+        if let Some(deletion_timestamp) = deletion_timestamp {
+            metadata["deletionTimestamp"] = serde_json::json!(deletion_timestamp);
+        }
+        let review: AdmissionReview<DynamicObject> = serde_json::from_value(serde_json::json!({
+            "apiVersion": "admission.k8s.io/v1",
+            "kind": "AdmissionReview",
+            "request": {
+                "uid": "705ab4f5-6393-11e8-b7cc-42010a800002",
+                "kind": {"group": "coredb.io", "version": "v1alpha1", "kind": "CoreDB"},
+                "resource": {"group": "coredb.io", "version": "v1alpha1", "resource": "coredbs"},
+                "name": "test",
+                "namespace": "default",
+                "operation": "UPDATE",
+                "userInfo": {},
+                "object": {
+                    "apiVersion": "coredb.io/v1alpha1",
+                    "kind": "CoreDB",
+                    "metadata": metadata,
+                    "spec": spec
+                },
+                "oldObject": {
+                    "apiVersion": "coredb.io/v1alpha1",
+                    "kind": "CoreDB",
+                    "metadata": {"name": "test", "namespace": "default"},
+                    "spec": old_spec
+                },
+                "dryRun": false
+            }
+        }))
+        .unwrap();
+        review.try_into().unwrap()
+    }
+
+    #[test]
+    fn test_admission_response_allows_existing_errors() {
+        let invalid = serde_json::json!({
+            "ipAllowList": ["not-an-ip"],
+            "runtime_config": [{"name": "wal_level", "value": "logical"}]
+        });
+
+        // Metadata updates, like the removal of the finalizer or of an annotation
+        let res = admission_response(&update_review(invalid.clone(), invalid.clone(), None));
+        assert!(res.allowed);
+
+        let res = admission_response(&update_review(
+            serde_json::json!({}),
+            invalid.clone(),
+            Some("2023-10-17T00:00:00Z"),
+        ));
+        assert!(res.allowed);
+
+        // A change to the spec is only denied for the errors it introduces
+        let mut changed = invalid.clone();
+        changed["replicas"] = serde_json::json!(2);
+        let res = admission_response(&update_review(invalid.clone(), changed, None));
+        assert!(res.allowed);
+
+        let mut changed = invalid.clone();
+        changed["ipAllowList"] = serde_json::json!(["not-an-ip", "also-not-an-ip"]);
+        let res = admission_response(&update_review(invalid, changed, None));
+        assert!(!res.allowed);
+        assert_eq!(
+            res.result.message,
+            "spec.ipAllowList[1]: 'also-not-an-ip' is not a valid IPv4 address or CIDR block"
+        );
+    }
+}